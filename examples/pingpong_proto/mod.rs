@@ -25,6 +25,26 @@ pub enum ServerResponse<'a> {
     ReadSleep { payload: &'a [u8] },
 }
 
+pub struct ClientProto;
+
+asansio::message! {
+    ClientProto {
+        type Request<'r> = ClientRequest<'r>;
+        type Response<'r> = ClientResponse<'r>;
+    }
+}
+
+pub struct ServerProto;
+
+asansio::message! {
+    ServerProto {
+        type Request<'r> = ServerRequest<'r>;
+        type Response<'r> = ServerResponse<'r>;
+    }
+}
+
+/// The copy of the Response data for the next Request. The Request could not borrow the Response
+/// it answers and messages have no length limit, so the buffer is reused between exchanges.
 struct Cache {
     read: Vec<u8>,
 }
@@ -35,38 +55,37 @@ impl Cache {
     }
 }
 
-fn client_read_message<'a>(payload: &'a [u8]) -> ClientRequest<'a> {
+fn client_read_message<'a>(cache: &'a mut Cache, payload: &[u8]) -> ClientRequest<'a> {
+    cache.read.clear();
+    cache.read.extend_from_slice(payload);
     ClientRequest::Message {
-        msg: str::from_utf8(payload).unwrap_or("(wrong utf-8 encoding)"),
+        msg: str::from_utf8(&cache.read).unwrap_or("(wrong utf-8 encoding)"),
     }
 }
 
-fn client_message<'a>(msg: &'a str) -> ClientRequest<'a> {
+fn client_message<'a>(cache: &'a mut Cache, msg: &str) -> ClientRequest<'a> {
+    cache.read.clear();
+    cache.read.extend_from_slice(msg.as_bytes());
     ClientRequest::WriteMessage {
-        payload: msg.as_bytes(),
+        payload: cache.read.as_slice(),
     }
 }
 
-fn client_sleep<'a>(cache: &'a mut Cache, duration: Duration) -> ClientRequest<'a> {
-    cache.read.clear();
-    cache
-        .read
-        .push(duration.as_millis().clamp(0, u8::max_value() as u128) as u8);
-    ClientRequest::WriteSleep {
-        payload: cache.read.as_slice(),
-    }
+fn client_sleep(buf: &mut [u8; 1], duration: Duration) -> ClientRequest<'_> {
+    buf[0] = duration.as_millis().clamp(0, u8::MAX as u128) as u8;
+    ClientRequest::WriteSleep { payload: buf }
 }
 
-pub async fn run_client<'a>(sans: Sans<ClientRequest<'a>, ClientResponse<'a>>) {
+pub async fn run_client(mut sans: Sans<'_, ClientProto>) {
     let mut cache = Cache::new();
+    let mut sleep = [0; 1];
 
     let mut sans_resp = sans.start(&ClientRequest::Ready).await;
     loop {
         let request = match sans_resp.response() {
-            Some(ClientResponse::ReadMessage { payload }) => client_read_message(payload),
-            Some(ClientResponse::Message { msg }) => client_message(msg),
-            Some(ClientResponse::Sleep { duration }) => client_sleep(&mut cache, *duration),
-            None => break,
+            ClientResponse::ReadMessage { payload } => client_read_message(&mut cache, payload),
+            ClientResponse::Message { msg } => client_message(&mut cache, msg),
+            ClientResponse::Sleep { duration } => client_sleep(&mut sleep, *duration),
         };
         sans_resp = sans.handle(sans_resp, &request).await;
     }
@@ -91,17 +110,14 @@ fn server_read_sleep<'a>(payload: &[u8]) -> ServerRequest<'a> {
     }
 }
 
-pub async fn run_server<'a>(sans: Sans<ServerRequest<'a>, ServerResponse<'a>>) {
+pub async fn run_server(mut sans: Sans<'_, ServerProto>) {
     let mut cache = Cache::new();
 
     let mut sans_resp = sans.start(&ServerRequest::Read).await;
     loop {
         let request = match sans_resp.response() {
-            Some(ServerResponse::ReadMessage { payload }) => {
-                server_read_message(&mut cache, payload)
-            }
-            Some(ServerResponse::ReadSleep { payload }) => server_read_sleep(payload),
-            None => break,
+            ServerResponse::ReadMessage { payload } => server_read_message(&mut cache, payload),
+            ServerResponse::ReadSleep { payload } => server_read_sleep(payload),
         };
         sans_resp = sans.handle(sans_resp, &request).await;
     }
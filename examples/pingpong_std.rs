@@ -1,5 +1,6 @@
 mod tlv_pingpong_proto;
 
+use asansio::Session;
 use clap::Parser;
 use std::io::Read;
 use std::io::Write;
@@ -37,10 +38,8 @@ struct Cache {
 
 impl Cache {
     fn new() -> Self {
-        let mut buf = Vec::new();
-        buf.reserve(1024);
         Self {
-            buf,
+            buf: Vec::with_capacity(1024),
             msg: String::new(),
             count_read: 0,
             count_send: 0,
@@ -63,7 +62,7 @@ fn client_process_read_payload<'a>(
         })
     } else {
         cache.count_read += 1;
-        if cache.count_read % 3 == 0 {
+        if cache.count_read.is_multiple_of(3) {
             return Some(ClientResponse::Sleep {
                 duration: Duration::from_millis(200),
             });
@@ -104,19 +103,19 @@ fn client_process(mut tcp: TcpStream) {
     tcp.set_read_timeout(Some(Duration::from_millis(10)))
         .unwrap();
 
-    let (sans, io) = asansio::new();
+    let mut session = Session::new();
+    let (sans, io) = session.split();
     let task = pin!(tlv_pingpong_proto::run_client(sans));
 
     let mut request = io.start(task);
     while request.is_some() {
         let response = match request.as_ref().unwrap().request() {
-            Some(ClientRequest::ReadPayload) => client_process_read_payload(&mut cache, &mut tcp),
-            Some(ClientRequest::WritePayload { payload }) => {
+            ClientRequest::ReadPayload => client_process_read_payload(&mut cache, &mut tcp),
+            ClientRequest::WritePayload { payload } => {
                 client_process_write_payload(&mut tcp, payload)
             }
-            Some(ClientRequest::Message { msg }) => client_process_message(&mut cache, msg),
-            Some(ClientRequest::Error) => break,
-            None => break,
+            ClientRequest::Message { msg } => client_process_message(&mut cache, msg),
+            ClientRequest::Error => break,
         };
         let Some(response) = response else {
             break;
@@ -1,5 +1,6 @@
 mod tlv_pingpong_proto;
 
+use asansio::Session;
 use clap::Parser;
 use clap::Subcommand;
 use std::net::SocketAddr;
@@ -69,10 +70,8 @@ struct Cache {
 
 impl Cache {
     fn new() -> Self {
-        let mut buf = Vec::new();
-        buf.reserve(1024);
         Self {
-            buf,
+            buf: Vec::with_capacity(1024),
             msg: String::new(),
             count_read: 0,
             count_send: 0,
@@ -102,7 +101,7 @@ async fn client_process_read_payload<'a>(
         })
     } else {
         cache.count_read += 1;
-        if cache.count_read % 3 == 0 {
+        if cache.count_read.is_multiple_of(3) {
             return Some(ClientResponse::Sleep {
                 duration: Duration::from_millis(200),
             });
@@ -140,21 +139,19 @@ async fn client_process_message<'a>(cache: &mut Cache, msg: &str) -> Option<Clie
 async fn client_process(mut tcp: TcpStream) {
     let mut cache = Cache::new();
 
-    let (sans, io) = asansio::new();
+    let mut session = Session::new();
+    let (sans, io) = session.split();
     let task = pin!(tlv_pingpong_proto::run_client(sans));
 
     let mut request = io.start(task);
     while request.is_some() {
         let response = match request.as_ref().unwrap().request() {
-            Some(ClientRequest::ReadPayload) => {
-                client_process_read_payload(&mut cache, &mut tcp).await
-            }
-            Some(ClientRequest::WritePayload { payload }) => {
+            ClientRequest::ReadPayload => client_process_read_payload(&mut cache, &mut tcp).await,
+            ClientRequest::WritePayload { payload } => {
                 client_process_write_payload(&mut tcp, payload).await
             }
-            Some(ClientRequest::Message { msg }) => client_process_message(&mut cache, msg).await,
-            Some(ClientRequest::Error) => break,
-            None => break,
+            ClientRequest::Message { msg } => client_process_message(&mut cache, msg).await,
+            ClientRequest::Error => break,
         };
         let Some(response) = response else {
             break;
@@ -196,21 +193,19 @@ async fn server_process_sleep<'a>(duration: Duration) -> Option<ServerResponse<'
 async fn server_process(mut tcp: TcpStream) {
     let mut cache = Cache::new();
 
-    let (sans, io) = asansio::new();
+    let mut session = Session::new();
+    let (sans, io) = session.split();
     let task = pin!(tlv_pingpong_proto::run_server(sans));
 
     let mut request = io.start(task);
     while request.is_some() {
         let response = match request.as_ref().unwrap().request() {
-            Some(ServerRequest::ReadPayload) => {
-                server_process_read_payload(&mut cache, &mut tcp).await
-            }
-            Some(ServerRequest::WritePayload { payload }) => {
+            ServerRequest::ReadPayload => server_process_read_payload(&mut cache, &mut tcp).await,
+            ServerRequest::WritePayload { payload } => {
                 server_process_write_payload(&mut tcp, payload).await
             }
-            Some(ServerRequest::Sleep { duration }) => server_process_sleep(*duration).await,
-            Some(ServerRequest::Error) => break,
-            None => break,
+            ServerRequest::Sleep { duration } => server_process_sleep(*duration).await,
+            ServerRequest::Error => break,
         };
         let Some(response) = response else {
             break;
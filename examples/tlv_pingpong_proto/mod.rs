@@ -5,15 +5,23 @@ mod pingpong_proto;
 #[path = "../tlv_proto/mod.rs"]
 mod tlv_proto;
 
+use asansio::Io;
+use asansio::IoRequest;
+use asansio::Message;
 use asansio::Sans;
+use asansio::Session;
+use pingpong_proto::ClientProto as PpClientProto;
 use pingpong_proto::ClientRequest as PpClientRequest;
 use pingpong_proto::ClientResponse as PpClientResponse;
+use pingpong_proto::ServerProto as PpServerProto;
 use pingpong_proto::ServerRequest as PpServerRequest;
 use pingpong_proto::ServerResponse as PpServerResponse;
 use std::pin::pin;
 use std::time::Duration;
+use tlv_proto::ClientProto as TlvClientProto;
 use tlv_proto::ClientRequest as TlvClientRequest;
 use tlv_proto::ClientResponse as TlvClientResponse;
+use tlv_proto::ServerProto as TlvServerProto;
 use tlv_proto::ServerRequest as TlvServerRequest;
 use tlv_proto::ServerResponse as TlvServerResponse;
 
@@ -41,14 +49,49 @@ pub enum ServerResponse<'a> {
     ReadPayload { payload: &'a [u8] },
 }
 
+pub struct ClientProto;
+
+asansio::message! {
+    ClientProto {
+        type Request<'r> = ClientRequest<'r>;
+        type Response<'r> = ClientResponse<'r>;
+    }
+}
+
+pub struct ServerProto;
+
+asansio::message! {
+    ServerProto {
+        type Request<'r> = ServerRequest<'r>;
+        type Response<'r> = ServerResponse<'r>;
+    }
+}
+
+/// The part of the session which produced the latest message
+enum Step {
+    Sans,
+    Tlv,
+    Pp,
+}
+
+fn io_handle<'a, M: Message, Task: Future<Output = ()>>(
+    io: &Io<'_, M>,
+    io_request: &mut Option<IoRequest<'a, M, Task>>,
+    response: &M::Response<'_>,
+    step: Step,
+) -> Option<Step> {
+    *io_request = io.handle(io_request.take().unwrap(), response);
+    io_request.as_ref().map(|_| step)
+}
+
 enum Client<'a> {
     Tlv(TlvClientResponse<'a>),
     Pp(PpClientResponse<'a>),
     Request(ClientRequest<'a>),
 }
 
-fn client_tlv_request<'a>(request: Option<&TlvClientRequest<'a>>) -> Option<Client<'a>> {
-    request.map(|request| match request {
+fn client_tlv_request<'a>(request: &TlvClientRequest<'a>) -> Client<'a> {
+    match request {
         TlvClientRequest::WritePayload { payload } => {
             Client::Request(ClientRequest::WritePayload { payload })
         }
@@ -57,11 +100,12 @@ fn client_tlv_request<'a>(request: Option<&TlvClientRequest<'a>>) -> Option<Clie
             _ => Client::Request(ClientRequest::Error),
         },
         TlvClientRequest::ReadPayload => Client::Request(ClientRequest::ReadPayload),
-    })
+        TlvClientRequest::Error { .. } => Client::Request(ClientRequest::Error),
+    }
 }
 
-fn client_pp_request<'a>(request: Option<&PpClientRequest<'a>>) -> Option<Client<'a>> {
-    request.map(|request| match request {
+fn client_pp_request<'a>(request: &PpClientRequest<'a>) -> Client<'a> {
+    match request {
         PpClientRequest::WriteMessage { payload } => Client::Tlv(TlvClientResponse::Write {
             tag: 0,
             val: payload,
@@ -72,11 +116,11 @@ fn client_pp_request<'a>(request: Option<&PpClientRequest<'a>>) -> Option<Client
         }),
         PpClientRequest::Message { msg } => Client::Request(ClientRequest::Message { msg }),
         PpClientRequest::Ready => Client::Tlv(TlvClientResponse::ReadPayload { payload: &[] }),
-    })
+    }
 }
 
-fn client_response<'a>(response: Option<&ClientResponse<'a>>) -> Option<Client<'a>> {
-    response.map(|response| match response {
+fn client_response<'a>(response: &ClientResponse<'a>) -> Client<'a> {
+    match response {
         ClientResponse::ReadPayload { payload } => {
             Client::Tlv(TlvClientResponse::ReadPayload { payload })
         }
@@ -84,14 +128,16 @@ fn client_response<'a>(response: Option<&ClientResponse<'a>>) -> Option<Client<'
         ClientResponse::Sleep { duration } => Client::Pp(PpClientResponse::Sleep {
             duration: *duration,
         }),
-    })
+    }
 }
 
-pub async fn run_client<'a>(sans: Sans<ClientRequest<'a>, ClientResponse<'a>>) {
-    let (pp_sans, pp_io) = asansio::new();
+pub async fn run_client(mut sans: Sans<'_, ClientProto>) {
+    let mut pp_session = Session::<PpClientProto>::new();
+    let (pp_sans, pp_io) = pp_session.split();
     let pp_task = pin!(pingpong_proto::run_client(pp_sans));
 
-    let (tlv_sans, tlv_io) = asansio::new();
+    let mut tlv_session = Session::<TlvClientProto>::new();
+    let (tlv_sans, tlv_io) = tlv_session.split();
     let tlv_task = pin!(tlv_proto::run_client(tlv_sans));
 
     let mut pp_io_request = pp_io.start(pp_task);
@@ -104,32 +150,41 @@ pub async fn run_client<'a>(sans: Sans<ClientRequest<'a>, ClientResponse<'a>>) {
     };
 
     let mut sans_resp = sans.start(&ClientRequest::ReadPayload).await;
-    let mut client = client_response(sans_resp.response());
+    let mut step = Step::Sans;
     loop {
-        client = match client {
-            Some(Client::Tlv(response)) => {
-                tlv_io_request = tlv_io.handle(tlv_io_request.take().unwrap(), &response);
-                if tlv_io_request.is_none() {
-                    return;
-                };
-                client_tlv_request(tlv_io_request.as_ref().unwrap().request())
-            }
-
-            Some(Client::Pp(response)) => {
-                pp_io_request = pp_io.handle(pp_io_request.take().unwrap(), &response);
-                if pp_io_request.is_none() {
-                    return;
-                };
-                client_pp_request(pp_io_request.as_ref().unwrap().request())
-            }
-
-            Some(Client::Request(request)) => {
-                sans_resp = sans.handle(sans_resp, &request).await;
-                client_response(sans_resp.response())
-            }
-
-            None => break,
+        let next = match step {
+            Step::Sans => match client_response(sans_resp.response()) {
+                Client::Tlv(response) => {
+                    io_handle(&tlv_io, &mut tlv_io_request, &response, Step::Tlv)
+                }
+                Client::Pp(response) => io_handle(&pp_io, &mut pp_io_request, &response, Step::Pp),
+                _ => None,
+            },
+
+            Step::Tlv => match client_tlv_request(tlv_io_request.as_ref().unwrap().request()) {
+                Client::Pp(response) => io_handle(&pp_io, &mut pp_io_request, &response, Step::Pp),
+                Client::Request(request) => {
+                    sans_resp = sans.handle(sans_resp, &request).await;
+                    Some(Step::Sans)
+                }
+                _ => None,
+            },
+
+            Step::Pp => match client_pp_request(pp_io_request.as_ref().unwrap().request()) {
+                Client::Tlv(response) => {
+                    io_handle(&tlv_io, &mut tlv_io_request, &response, Step::Tlv)
+                }
+                Client::Request(request) => {
+                    sans_resp = sans.handle(sans_resp, &request).await;
+                    Some(Step::Sans)
+                }
+                _ => None,
+            },
+        };
+        let Some(next) = next else {
+            break;
         };
+        step = next;
     }
 }
 
@@ -139,8 +194,8 @@ enum Server<'a> {
     Request(ServerRequest<'a>),
 }
 
-fn server_tlv_request<'a>(request: Option<&TlvServerRequest<'a>>) -> Option<Server<'a>> {
-    request.map(|request| match request {
+fn server_tlv_request<'a>(request: &TlvServerRequest<'a>) -> Server<'a> {
+    match request {
         TlvServerRequest::WritePayload { payload } => {
             Server::Request(ServerRequest::WritePayload { payload })
         }
@@ -150,11 +205,12 @@ fn server_tlv_request<'a>(request: Option<&TlvServerRequest<'a>>) -> Option<Serv
             _ => Server::Request(ServerRequest::Error),
         },
         TlvServerRequest::ReadPayload => Server::Request(ServerRequest::ReadPayload),
-    })
+        TlvServerRequest::Error { .. } => Server::Request(ServerRequest::Error),
+    }
 }
 
-fn server_pp_request<'a>(request: Option<&PpServerRequest<'a>>) -> Option<Server<'a>> {
-    request.map(|request| match request {
+fn server_pp_request<'a>(request: &PpServerRequest<'a>) -> Server<'a> {
+    match request {
         PpServerRequest::WriteMessage { payload } => Server::Tlv(TlvServerResponse::Write {
             tag: 0,
             val: payload,
@@ -163,22 +219,24 @@ fn server_pp_request<'a>(request: Option<&PpServerRequest<'a>>) -> Option<Server
             duration: *duration,
         }),
         PpServerRequest::Read => Server::Tlv(TlvServerResponse::ReadPayload { payload: &[] }),
-    })
+    }
 }
 
-fn server_response<'a>(response: Option<&ServerResponse<'a>>) -> Option<Server<'a>> {
-    response.map(|response| match response {
+fn server_response<'a>(response: &ServerResponse<'a>) -> Server<'a> {
+    match response {
         ServerResponse::ReadPayload { payload } => {
             Server::Tlv(TlvServerResponse::ReadPayload { payload })
         }
-    })
+    }
 }
 
-pub async fn run_server<'a>(sans: Sans<ServerRequest<'a>, ServerResponse<'a>>) {
-    let (pp_sans, pp_io) = asansio::new();
+pub async fn run_server(mut sans: Sans<'_, ServerProto>) {
+    let mut pp_session = Session::<PpServerProto>::new();
+    let (pp_sans, pp_io) = pp_session.split();
     let pp_task = pin!(pingpong_proto::run_server(pp_sans));
 
-    let (tlv_sans, tlv_io) = asansio::new();
+    let mut tlv_session = Session::<TlvServerProto>::new();
+    let (tlv_sans, tlv_io) = tlv_session.split();
     let tlv_task = pin!(tlv_proto::run_server(tlv_sans));
 
     let mut pp_io_request = pp_io.start(pp_task);
@@ -191,31 +249,40 @@ pub async fn run_server<'a>(sans: Sans<ServerRequest<'a>, ServerResponse<'a>>) {
     };
 
     let mut sans_resp = sans.start(&ServerRequest::ReadPayload).await;
-    let mut server = server_response(sans_resp.response());
+    let mut step = Step::Sans;
     loop {
-        server = match server {
-            Some(Server::Tlv(response)) => {
-                tlv_io_request = tlv_io.handle(tlv_io_request.take().unwrap(), &response);
-                if tlv_io_request.is_none() {
-                    return;
-                };
-                server_tlv_request(tlv_io_request.as_ref().unwrap().request())
-            }
-
-            Some(Server::Pp(response)) => {
-                pp_io_request = pp_io.handle(pp_io_request.take().unwrap(), &response);
-                if pp_io_request.is_none() {
-                    return;
-                };
-                server_pp_request(pp_io_request.as_ref().unwrap().request())
-            }
-
-            Some(Server::Request(request)) => {
-                sans_resp = sans.handle(sans_resp, &request).await;
-                server_response(sans_resp.response())
-            }
-
-            None => break,
+        let next = match step {
+            Step::Sans => match server_response(sans_resp.response()) {
+                Server::Tlv(response) => {
+                    io_handle(&tlv_io, &mut tlv_io_request, &response, Step::Tlv)
+                }
+                Server::Pp(response) => io_handle(&pp_io, &mut pp_io_request, &response, Step::Pp),
+                _ => None,
+            },
+
+            Step::Tlv => match server_tlv_request(tlv_io_request.as_ref().unwrap().request()) {
+                Server::Pp(response) => io_handle(&pp_io, &mut pp_io_request, &response, Step::Pp),
+                Server::Request(request) => {
+                    sans_resp = sans.handle(sans_resp, &request).await;
+                    Some(Step::Sans)
+                }
+                _ => None,
+            },
+
+            Step::Pp => match server_pp_request(pp_io_request.as_ref().unwrap().request()) {
+                Server::Tlv(response) => {
+                    io_handle(&tlv_io, &mut tlv_io_request, &response, Step::Tlv)
+                }
+                Server::Request(request) => {
+                    sans_resp = sans.handle(sans_resp, &request).await;
+                    Some(Step::Sans)
+                }
+                _ => None,
+            },
+        };
+        let Some(next) = next else {
+            break;
         };
+        step = next;
     }
 }
@@ -1,10 +1,16 @@
 use asansio::Sans;
-use std::collections::VecDeque;
+use asansio::codec::Decoder;
+use asansio::codec::Encoder;
+use asansio::codec::tlv::Element;
+use asansio::codec::tlv::Tlv;
+use asansio::codec::tlv::TlvError;
+use asansio::codec::tlv::Width;
 
 pub enum ClientRequest<'a> {
     ReadPayload,
     WritePayload { payload: &'a [u8] },
     Read { tag: u8, val: &'a [u8] },
+    Error { err: TlvError },
 }
 
 pub enum ClientResponse<'a> {
@@ -16,6 +22,7 @@ pub enum ServerRequest<'a> {
     ReadPayload,
     WritePayload { payload: &'a [u8] },
     Read { tag: u8, val: &'a [u8] },
+    Error { err: TlvError },
 }
 
 pub enum ServerResponse<'a> {
@@ -23,110 +30,120 @@ pub enum ServerResponse<'a> {
     Write { tag: u8, val: &'a [u8] },
 }
 
+pub struct ClientProto;
+
+asansio::message! {
+    ClientProto {
+        type Request<'r> = ClientRequest<'r>;
+        type Response<'r> = ClientResponse<'r>;
+    }
+}
+
+pub struct ServerProto;
+
+asansio::message! {
+    ServerProto {
+        type Request<'r> = ServerRequest<'r>;
+        type Response<'r> = ServerResponse<'r>;
+    }
+}
+
 struct Cache {
+    codec: Tlv,
     write: Vec<u8>,
-    read: VecDeque<u8>,
+    read: Vec<u8>,
     read_consumed: usize,
 }
 
 impl Cache {
     fn new() -> Self {
         Self {
+            codec: Tlv::new().tag(Width::U8).length(Width::U8),
             write: Vec::new(),
-            read: VecDeque::new(),
+            read: Vec::new(),
             read_consumed: 0,
         }
     }
 
-    fn read_packet(&mut self, payload: &[u8]) -> Option<&[u8]> {
+    fn read_packet(&mut self, payload: &[u8]) -> Option<Element<'_>> {
         self.read.drain(..self.read_consumed);
         self.read_consumed = 0;
 
-        payload.iter().for_each(|byte| self.read.push_back(*byte));
+        self.read.extend_from_slice(payload);
 
-        if self.read.len() < 2 {
-            return None;
-        }
-
-        let len = self.read[1] as usize;
-        if self.read.len() < len + 2 {
-            return None;
-        }
-
-        self.read_consumed = len + 2;
-        Some(&self.read.make_contiguous()[..self.read_consumed])
+        // Single byte tags and lengths are never malformed
+        let len = self.codec.frame_len(&self.read).unwrap()?;
+        self.read_consumed = len;
+        Some(self.codec.decode(&self.read[..len]).unwrap())
     }
 
-    fn write_packet(&mut self, tag: u8, val: &[u8]) -> &[u8] {
-        self.write.clear();
-        self.write.push(tag);
-        self.write.push(val.len() as u8);
-        self.write.extend_from_slice(val);
-        &self.write[0..val.len() + 2]
+    fn write_packet(&mut self, tag: u8, val: &[u8]) -> Result<&[u8], TlvError> {
+        let element = Element {
+            tag: tag.into(),
+            value: val,
+        };
+        let len = self.codec.encoded_len(&element)?;
+        self.write.resize(len, 0);
+        self.codec.encode(&element, &mut self.write);
+        Ok(&self.write)
     }
 }
 
 fn client_read_payload<'a>(cache: &'a mut Cache, payload: &[u8]) -> ClientRequest<'a> {
-    if let Some(buf) = cache.read_packet(payload) {
-        ClientRequest::Read {
-            tag: buf[0],
-            val: &buf[2..],
-        }
-    } else {
-        ClientRequest::ReadPayload
+    match cache.read_packet(payload) {
+        Some(element) => ClientRequest::Read {
+            tag: element.tag as u8,
+            val: element.value,
+        },
+        None => ClientRequest::ReadPayload,
     }
 }
 
 fn client_write<'a>(cache: &'a mut Cache, tag: u8, val: &[u8]) -> ClientRequest<'a> {
-    ClientRequest::WritePayload {
-        payload: cache.write_packet(tag, val),
+    match cache.write_packet(tag, val) {
+        Ok(payload) => ClientRequest::WritePayload { payload },
+        Err(err) => ClientRequest::Error { err },
     }
 }
 
-pub async fn run_client<'a>(sans: Sans<ClientRequest<'a>, ClientResponse<'a>>) {
+pub async fn run_client(mut sans: Sans<'_, ClientProto>) {
     let mut cache = Cache::new();
 
     let mut sans_resp = sans.start(&ClientRequest::ReadPayload).await;
     loop {
         let request = match sans_resp.response() {
-            Some(ClientResponse::ReadPayload { payload }) => {
-                client_read_payload(&mut cache, payload)
-            }
-            Some(ClientResponse::Write { tag, val }) => client_write(&mut cache, *tag, val),
-            None => break,
+            ClientResponse::ReadPayload { payload } => client_read_payload(&mut cache, payload),
+            ClientResponse::Write { tag, val } => client_write(&mut cache, *tag, val),
         };
         sans_resp = sans.handle(sans_resp, &request).await;
     }
 }
 
 fn server_read_payload<'a>(cache: &'a mut Cache, payload: &[u8]) -> ServerRequest<'a> {
-    if let Some(buf) = cache.read_packet(payload) {
-        ServerRequest::Read {
-            tag: buf[0],
-            val: &buf[2..],
-        }
-    } else {
-        ServerRequest::ReadPayload
+    match cache.read_packet(payload) {
+        Some(element) => ServerRequest::Read {
+            tag: element.tag as u8,
+            val: element.value,
+        },
+        None => ServerRequest::ReadPayload,
     }
 }
 
 fn server_write<'a>(cache: &'a mut Cache, tag: u8, val: &[u8]) -> ServerRequest<'a> {
-    ServerRequest::WritePayload {
-        payload: cache.write_packet(tag, val),
+    match cache.write_packet(tag, val) {
+        Ok(payload) => ServerRequest::WritePayload { payload },
+        Err(err) => ServerRequest::Error { err },
     }
 }
 
-pub async fn run_server<'a>(sans: Sans<ServerRequest<'a>, ServerResponse<'a>>) {
+pub async fn run_server(mut sans: Sans<'_, ServerProto>) {
     let mut cache = Cache::new();
 
     let mut sans_resp = sans.start(&ServerRequest::ReadPayload).await;
     loop {
         let request = match sans_resp.response() {
-            Some(ServerResponse::ReadPayload { payload }) => {
-                server_read_payload(&mut cache, payload)
-            }
-            Some(ServerResponse::Write { tag, val }) => server_write(&mut cache, *tag, val),
-            None => break,
+            ServerResponse::ReadPayload { payload } => server_read_payload(&mut cache, payload),
+            ServerResponse::Write { tag, val } => server_write(&mut cache, *tag, val),
         };
         sans_resp = sans.handle(sans_resp, &request).await;
     }
@@ -0,0 +1,277 @@
+//! Step budget and livelock detection of sessions.
+//!
+//! A buggy Sans task could exchange messages with the driver forever, e.g. reading an empty
+//! payload again and again. The [Budget] replaces the [Io](crate::Io) in the driver of the
+//! session and turns such a runaway session into the terminal [Error]: the session is limited to
+//! the maximum number of steps and to the maximum number of consecutive steps without progress,
+//! which is defined by the user predicate of the exchanged Request and Response. A panic of the
+//! Sans task is returned as the error too, so a server could disconnect the peer in both cases.
+//!
+//! ```
+//! # use asansio::Drive;
+//! # use asansio::Session;
+//! # use asansio::budget::Budget;
+//! # use asansio::budget::Error;
+//! # use std::pin::pin;
+//! #
+//! struct Read;
+//! struct Data<'a>(&'a [u8]);
+//!
+//! struct Proto;
+//!
+//! asansio::message! {
+//!     Proto {
+//!         type Request<'r> = Read;
+//!         type Response<'r> = Data<'r>;
+//!     }
+//! }
+//!
+//! let mut session = Session::<Proto>::new();
+//! let (mut sans, io) = session.split();
+//! let mut io = Budget::new(io).idle(3, |_: &Read, data: &Data| !data.0.is_empty());
+//!
+//! // Waits for data forever
+//! let task = pin!(async {
+//!     let mut response = sans.start(&Read).await;
+//!     loop {
+//!         response = sans.handle(response, &Read).await;
+//!     }
+//! });
+//!
+//! let mut handler = io.try_start(task).unwrap();
+//! let err = loop {
+//!     match io.try_handle(handler.unwrap(), &Data(&[])) {
+//!         Ok(next) => handler = next,
+//!         Err(err) => break err,
+//!     }
+//! };
+//! assert!(matches!(err, Error::Livelock(3)));
+//! ```
+
+use crate::Drive;
+use crate::IoRequest;
+use crate::Message;
+use core::convert::Infallible;
+use core::fmt;
+use core::pin::Pin;
+
+/// The terminal error of the session guarded by the [Budget], wrapping the error `E` of the
+/// [Drive].
+#[derive(Debug)]
+pub enum Error<E = crate::Error> {
+    /// The session exceeded the maximum number of steps.
+    Steps(usize),
+    /// The session exceeded the maximum number of consecutive steps without progress.
+    Livelock(usize),
+    /// The wrapped driver returned the error, e.g. the Sans task panicked.
+    Task(E),
+}
+
+impl<E: fmt::Display> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Steps(max) => write!(f, "session exceeded the budget of {max} steps"),
+            Self::Livelock(max) => write!(f, "session made no progress for {max} steps"),
+            Self::Task(err) => err.fmt(f),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for Error<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Task(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// The predicate of [Budget::new], every exchange makes progress
+type Always<M> =
+    for<'a, 'b> fn(&<M as Message>::Request<'a>, &<M as Message>::Response<'b>) -> bool;
+
+/// The Io part which limits steps of the session.
+///
+/// It wraps the [Io](crate::Io) or another [Drive], so it could replace it in the driver of the
+/// session. A step is a single Response passed to the Sans task.
+pub struct Budget<D: Drive, P = Always<<D as Drive>::Message>> {
+    io: D,
+    steps: Option<usize>,
+    idle: Option<usize>,
+    progress: P,
+    taken: usize,
+    idle_taken: usize,
+}
+
+impl<D: Drive> Budget<D> {
+    /// Creates the unlimited budget for the Io part.
+    pub fn new(io: D) -> Self {
+        Self {
+            io,
+            steps: None,
+            idle: None,
+            progress: |_, _| true,
+            taken: 0,
+            idle_taken: 0,
+        }
+    }
+}
+
+impl<D: Drive, P> Budget<D, P>
+where
+    P: FnMut(&<D::Message as Message>::Request<'_>, &<D::Message as Message>::Response<'_>) -> bool,
+{
+    /// Sets the maximum number of steps of the session.
+    pub fn steps(mut self, max: usize) -> Self {
+        self.steps = Some(max);
+        self
+    }
+
+    /// Sets the maximum number of consecutive steps without progress. The predicate returns
+    /// true if the exchange of the Request and the Response made progress.
+    pub fn idle<Q>(self, max: usize, progress: Q) -> Budget<D, Q>
+    where
+        Q: FnMut(
+            &<D::Message as Message>::Request<'_>,
+            &<D::Message as Message>::Response<'_>,
+        ) -> bool,
+    {
+        Budget {
+            io: self.io,
+            steps: self.steps,
+            idle: Some(max),
+            progress,
+            taken: self.taken,
+            idle_taken: self.idle_taken,
+        }
+    }
+
+    /// The number of steps taken by the session.
+    pub fn taken(&self) -> usize {
+        self.taken
+    }
+
+    /// Returns true if the session exceeded the budget.
+    pub fn is_exceeded(&self) -> bool {
+        self.error::<Infallible>().is_some()
+    }
+
+    /// Returns the Io part, consuming the budget.
+    pub fn into_io(self) -> D {
+        self.io
+    }
+
+    /// Takes the step with the exchange of the Request and the Response
+    fn step(
+        &mut self,
+        request: &<D::Message as Message>::Request<'_>,
+        response: &<D::Message as Message>::Response<'_>,
+    ) {
+        self.taken += 1;
+        let progress = (self.progress)(request, response);
+        self.idle_taken = if progress { 0 } else { self.idle_taken + 1 };
+    }
+
+    /// Returns the error of the exceeded budget
+    fn error<E>(&self) -> Option<Error<E>> {
+        match (self.steps, self.idle) {
+            (Some(max), _) if self.taken > max => Some(Error::Steps(max)),
+            (_, Some(max)) if self.idle_taken > max => Some(Error::Livelock(max)),
+            _ => None,
+        }
+    }
+
+    /// Panics if the session exceeded the budget
+    fn check(&self) {
+        if let Some(err) = self.error::<Infallible>() {
+            panic!("{err}");
+        }
+    }
+}
+
+impl<D: Drive, P> Drive for Budget<D, P>
+where
+    P: FnMut(&<D::Message as Message>::Request<'_>, &<D::Message as Message>::Response<'_>) -> bool,
+{
+    type Message = D::Message;
+    type Error = Error<D::Error>;
+
+    /// The same as [Drive::start].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the session exceeded the budget or the Sans task panics.
+    fn start<'a, Task>(
+        &mut self,
+        task: Pin<&'a mut Task>,
+    ) -> Option<IoRequest<'a, D::Message, Task>>
+    where
+        Self: 'a,
+        Task: Future<Output = ()> + ?Sized,
+    {
+        self.check();
+        self.io.start(task)
+    }
+
+    /// The same as [Drive::handle], but the Sans task is not polled when the session exceeds
+    /// the budget with this step.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the session exceeds the budget or the Sans task panics.
+    fn handle<'a, Task>(
+        &mut self,
+        handler: IoRequest<'a, D::Message, Task>,
+        response: &<D::Message as Message>::Response<'_>,
+    ) -> Option<IoRequest<'a, D::Message, Task>>
+    where
+        Task: Future<Output = ()> + ?Sized,
+    {
+        self.check();
+        self.step(handler.request(), response);
+        self.check();
+        self.io.handle(handler, response)
+    }
+
+    /// The same as [Drive::try_start].
+    fn try_start<'a, Task>(
+        &mut self,
+        task: Pin<&'a mut Task>,
+    ) -> Result<Option<IoRequest<'a, D::Message, Task>>, Self::Error>
+    where
+        Self: 'a,
+        Task: Future<Output = ()> + ?Sized,
+    {
+        if let Some(err) = self.error() {
+            return Err(err);
+        }
+        self.io.try_start(task).map_err(Error::Task)
+    }
+
+    /// The same as [Drive::try_handle], but the Sans task is not polled when the session exceeds
+    /// the budget with this step - the error is returned instead and the session is terminated.
+    fn try_handle<'a, Task>(
+        &mut self,
+        handler: IoRequest<'a, D::Message, Task>,
+        response: &<D::Message as Message>::Response<'_>,
+    ) -> Result<Option<IoRequest<'a, D::Message, Task>>, Self::Error>
+    where
+        Task: Future<Output = ()> + ?Sized,
+    {
+        if let Some(err) = self.error() {
+            return Err(err);
+        }
+        self.step(handler.request(), response);
+        if let Some(err) = self.error() {
+            return Err(err);
+        }
+        self.io.try_handle(handler, response).map_err(Error::Task)
+    }
+
+    fn task_error(err: &Self::Error) -> Option<&crate::Error> {
+        match err {
+            Error::Task(err) => D::task_error(err),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,441 @@
+//! Framing codecs for byte streams.
+//!
+//! A byte stream delivers data in arbitrary chunks, so a protocol has to reassemble its frames:
+//! a frame could be split between many reads and a single read could contain many frames. The
+//! [Decoder] finds complete frames in the buffered data and decodes them, the [Encoder] writes
+//! frames. The [Framed] helper of the Sans task does the reassembly for any codec on top of the
+//! [Stream] vocabulary: it awaits Responses to [StreamRequest::Read] until the next complete frame
+//! is buffered and writes encoded frames with [StreamRequest::Write].
+//!
+//! Buffers are provided by the caller, so codecs don't allocate and work also in `no_std`
+//! deployments. The read buffer limits the size of the frame: every [StreamRequest::Read] asks
+//! for at most the free space after the incomplete frame, so the Io part has to honour its max.
+//!
+//! ```
+//! # use asansio::Sans;
+//! # use asansio::codec::Decoder;
+//! # use asansio::codec::Encoder;
+//! # use asansio::codec::Framed;
+//! # use asansio::net::Stream;
+//! #
+//! /// Frames terminated by the zero byte
+//! struct Zero;
+//!
+//! impl Decoder for Zero {
+//!     type Frame<'a> = &'a [u8];
+//!     type Error = ();
+//!
+//!     fn frame_len(&mut self, buf: &[u8]) -> Result<Option<usize>, ()> {
+//!         Ok(buf.iter().position(|byte| *byte == 0).map(|idx| idx + 1))
+//!     }
+//!
+//!     fn decode<'a>(&mut self, frame: &'a [u8]) -> Result<&'a [u8], ()> {
+//!         Ok(&frame[..frame.len() - 1])
+//!     }
+//! }
+//!
+//! impl Encoder<[u8]> for Zero {
+//!     type Error = ();
+//!
+//!     fn encoded_len(&mut self, item: &[u8]) -> Result<usize, ()> {
+//!         Ok(item.len() + 1)
+//!     }
+//!
+//!     fn encode(&mut self, item: &[u8], buf: &mut [u8]) {
+//!         buf[..item.len()].copy_from_slice(item);
+//!         buf[item.len()] = 0;
+//!     }
+//! }
+//!
+//! /// Echoes frames until the stream is closed
+//! async fn echo(mut sans: Sans<'_, Stream>) {
+//!     let (mut read_buf, mut write_buf) = ([0; 64], [0; 64]);
+//!     let mut framed = Framed::new(&mut sans, &mut read_buf, &mut write_buf);
+//!     let mut frame = [0; 64];
+//!     while let Some(data) = framed.read(&mut Zero).await.unwrap() {
+//!         let frame = &mut frame[..data.len()];
+//!         frame.copy_from_slice(data);
+//!         framed.write(&mut Zero, frame).await.unwrap();
+//!     }
+//! }
+//! ```
+
+pub mod length;
+pub mod lines;
+pub mod tlv;
+
+use crate::Sans;
+use crate::SansResponse;
+use crate::net::Stream;
+use crate::net::StreamRequest;
+use crate::net::StreamResponse;
+use core::convert::Infallible;
+use core::fmt;
+use core::mem;
+#[cfg(feature = "coverage")]
+use core::panic::Location;
+use core::time::Duration;
+
+/// Finds and decodes frames in the buffered data of the stream.
+pub trait Decoder {
+    /// The decoded frame borrowing the buffer.
+    type Frame<'a>;
+
+    /// The error of the malformed frame.
+    type Error;
+
+    /// Returns the length of the complete frame at the start of the buffer or `None` if more data
+    /// is needed. The next call gets the same data with more data appended, unless the decoder
+    /// is [reset](Decoder::reset) or the frame is decoded.
+    fn frame_len(&mut self, buf: &[u8]) -> Result<Option<usize>, Self::Error>;
+
+    /// Forgets the state of the incomplete frame, before the decoder is used with other data.
+    /// Does nothing by default.
+    fn reset(&mut self) {}
+
+    /// Decodes the complete frame of the length returned by [Decoder::frame_len].
+    fn decode<'a>(&mut self, frame: &'a [u8]) -> Result<Self::Frame<'a>, Self::Error>;
+}
+
+/// Encodes items as frames of the stream.
+pub trait Encoder<Item: ?Sized> {
+    /// The error of the item, which could not be encoded.
+    type Error;
+
+    /// Returns the length of the encoded item.
+    fn encoded_len(&mut self, item: &Item) -> Result<usize, Self::Error>;
+
+    /// Encodes the item into the buffer of the length returned by [Encoder::encoded_len].
+    fn encode(&mut self, item: &Item, buf: &mut [u8]);
+}
+
+/// The error of reading or writing frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error<E> {
+    /// The error of the codec.
+    Codec(E),
+    /// The frame does not fit into the buffer.
+    Overflow,
+    /// The stream was closed in the middle of the frame.
+    Closed,
+    /// No data was read before the timeout.
+    Timeout,
+    /// The Io part sent the Response, which does not answer the Request.
+    Unexpected,
+}
+
+impl<E: fmt::Display> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Codec(err) => err.fmt(f),
+            Self::Overflow => write!(f, "frame does not fit into the buffer"),
+            Self::Closed => write!(f, "stream closed in the middle of the frame"),
+            Self::Timeout => write!(f, "no data read before the timeout"),
+            Self::Unexpected => write!(f, "unexpected Response of the stream"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug + fmt::Display> std::error::Error for Error<E> {}
+
+/// The error of reading the stream without any codec, e.g. by [Framed::read_until].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamError {
+    /// The data does not fit into the buffer.
+    Overflow,
+    /// The stream was closed in the middle of the data.
+    Closed,
+    /// No data was read before the timeout.
+    Timeout,
+    /// The Io part sent the Response, which does not answer the Request.
+    Unexpected,
+}
+
+impl<E> From<StreamError> for Error<E> {
+    fn from(err: StreamError) -> Self {
+        match err {
+            StreamError::Overflow => Self::Overflow,
+            StreamError::Closed => Self::Closed,
+            StreamError::Timeout => Self::Timeout,
+            StreamError::Unexpected => Self::Unexpected,
+        }
+    }
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Error::<Infallible>::from(*self).fmt(f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for StreamError {}
+
+/// The chunk of the data read by [Framed::read_until].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chunk<'a> {
+    /// The part of the data, the delimiter was not read yet.
+    Part(&'a [u8]),
+    /// The rest of the data before the delimiter.
+    Last(&'a [u8]),
+}
+
+/// Reads and writes frames of the [Stream] from the Sans task.
+///
+/// It sends all Requests of the Sans task - the first one with [Sans::start] and next ones with
+/// [Sans::handle] - so the Sans task uses it instead of the [Sans] for the stream. The reads ask
+/// for at most the free space of the read buffer after the incomplete frame, a frame longer than
+/// the buffer returns [Error::Overflow] and the data longer than the max returns
+/// [Error::Unexpected].
+///
+/// With the `coverage` feature its Requests are recorded at the calls of its methods in the Sans
+/// task.
+pub struct Framed<'f, 's> {
+    sans: &'f mut Sans<'s, Stream>,
+    response: Option<SansResponse<'s, Stream>>,
+    timeout: Option<Duration>,
+    read: &'f mut [u8],
+    start: usize,
+    end: usize,
+    consumed: usize,
+    partial: bool,
+    write: &'f mut [u8],
+    #[cfg(feature = "coverage")]
+    location: &'static Location<'static>,
+}
+
+impl<'f, 's> Framed<'f, 's> {
+    /// Creates the helper with buffers for reading and writing frames.
+    #[cfg_attr(feature = "coverage", track_caller)]
+    pub fn new(sans: &'f mut Sans<'s, Stream>, read: &'f mut [u8], write: &'f mut [u8]) -> Self {
+        Self {
+            sans,
+            response: None,
+            timeout: None,
+            read,
+            start: 0,
+            end: 0,
+            consumed: 0,
+            partial: false,
+            write,
+            #[cfg(feature = "coverage")]
+            location: Location::caller(),
+        }
+    }
+
+    /// Sets the timeout of every read of the stream.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Retrieve the data read from the stream, which is not consumed by frames yet.
+    pub fn buffered(&self) -> &[u8] {
+        &self.read[self.start + self.consumed..self.end]
+    }
+
+    /// Awaits the next complete frame. Returns `None` if the stream was closed between frames.
+    // It is not async to capture the location of the caller with the coverage feature
+    #[cfg_attr(feature = "coverage", track_caller)]
+    #[cfg_attr(not(feature = "coverage"), allow(clippy::manual_async_fn))]
+    pub fn read<D: Decoder>(
+        &mut self,
+        decoder: &mut D,
+    ) -> impl Future<Output = Result<Option<D::Frame<'_>>, Error<D::Error>>> {
+        #[cfg(feature = "coverage")]
+        self.locate(Location::caller());
+        async move {
+            self.start += mem::take(&mut self.consumed);
+            // The decoder could have been used with other data since the last read
+            decoder.reset();
+            let len = loop {
+                let buf = &self.read[self.start..self.end];
+                if let Some(len) = decoder.frame_len(buf).map_err(Error::Codec)? {
+                    break len;
+                }
+                if !self.fill().await? {
+                    return Ok(None);
+                }
+            };
+            self.consumed = len;
+            let frame = &self.read[self.start..self.start + len];
+            decoder.decode(frame).map(Some).map_err(Error::Codec)
+        }
+    }
+
+    /// Awaits the data until the delimiter, which is not limited by the read buffer.
+    ///
+    /// It returns [Chunk::Part] of the data as soon as it could not be the part of the delimiter
+    /// and [Chunk::Last] with the rest of the data when the delimiter is read. The delimiter is
+    /// consumed, but not returned. Returns `None` if the stream was closed between chunks of
+    /// different delimited data. The [StreamError] converts into the [Error] of any codec.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the delimiter is empty.
+    #[cfg_attr(feature = "coverage", track_caller)]
+    pub fn read_until(
+        &mut self,
+        delimiter: &[u8],
+    ) -> impl Future<Output = Result<Option<Chunk<'_>>, StreamError>> {
+        assert!(!delimiter.is_empty(), "the delimiter is empty");
+        #[cfg(feature = "coverage")]
+        self.locate(Location::caller());
+        async move {
+            self.start += mem::take(&mut self.consumed);
+            loop {
+                let buf = &self.read[self.start..self.end];
+                if let Some(idx) = find(buf, delimiter) {
+                    self.consumed = idx + delimiter.len();
+                    self.partial = false;
+                    let data = &self.read[self.start..self.start + idx];
+                    return Ok(Some(Chunk::Last(data)));
+                }
+                let len = buf.len().saturating_sub(delimiter.len() - 1);
+                if len > 0 {
+                    self.consumed = len;
+                    self.partial = true;
+                    let data = &self.read[self.start..self.start + len];
+                    return Ok(Some(Chunk::Part(data)));
+                }
+                if !self.fill().await? {
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    /// Encodes the item and writes the frame to the stream.
+    // It is not async to capture the location of the caller with the coverage feature
+    #[cfg_attr(feature = "coverage", track_caller)]
+    #[cfg_attr(not(feature = "coverage"), allow(clippy::manual_async_fn))]
+    pub fn write<E: Encoder<Item>, Item: ?Sized>(
+        &mut self,
+        encoder: &mut E,
+        item: &Item,
+    ) -> impl Future<Output = Result<(), Error<E::Error>>> {
+        #[cfg(feature = "coverage")]
+        self.locate(Location::caller());
+        async move {
+            let len = encoder.encoded_len(item).map_err(Error::Codec)?;
+            let Some(buf) = self.write.get_mut(..len) else {
+                return Err(Error::Overflow);
+            };
+            encoder.encode(item, buf);
+            let request = StreamRequest::Write { data: buf };
+            match exchange(
+                self.sans,
+                &mut self.response,
+                &request,
+                #[cfg(feature = "coverage")]
+                self.location,
+            )
+            .await
+            {
+                StreamResponse::Written => Ok(()),
+                _ => Err(Error::Unexpected),
+            }
+        }
+    }
+
+    /// Sends other Request of the stream, e.g. [StreamRequest::Sleep], and awaits its Response.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the Request is [StreamRequest::Read], its data would bypass the read buffer.
+    #[cfg_attr(feature = "coverage", track_caller)]
+    pub fn request(
+        &mut self,
+        request: &StreamRequest<'_>,
+    ) -> impl Future<Output = StreamResponse<'_>> {
+        assert!(
+            !matches!(request, StreamRequest::Read { .. }),
+            "the stream is read by Framed::read"
+        );
+        #[cfg(feature = "coverage")]
+        self.locate(Location::caller());
+        async move {
+            exchange(
+                self.sans,
+                &mut self.response,
+                request,
+                #[cfg(feature = "coverage")]
+                self.location,
+            )
+            .await
+        }
+    }
+
+    /// Stores the location of the caller, the await point of the next Request
+    #[cfg(feature = "coverage")]
+    fn locate(&mut self, location: &'static Location<'static>) {
+        self.location = location;
+    }
+
+    /// Reads more data after the buffered one, returns false if the stream was closed between
+    /// frames
+    async fn fill(&mut self) -> Result<bool, StreamError> {
+        self.read.copy_within(self.start..self.end, 0);
+        self.end -= self.start;
+        self.start = 0;
+        let max = self.read.len() - self.end;
+        if max == 0 {
+            return Err(StreamError::Overflow);
+        }
+        let request = StreamRequest::Read {
+            max,
+            timeout: self.timeout,
+        };
+        match exchange(
+            self.sans,
+            &mut self.response,
+            &request,
+            #[cfg(feature = "coverage")]
+            self.location,
+        )
+        .await
+        {
+            StreamResponse::Data { data } => {
+                let Some(free) = self.read.get_mut(self.end..self.end + data.len()) else {
+                    return Err(StreamError::Unexpected);
+                };
+                free.copy_from_slice(data);
+                self.end += data.len();
+                Ok(true)
+            }
+            StreamResponse::Closed if self.end == 0 && !self.partial => Ok(false),
+            StreamResponse::Closed => Err(StreamError::Closed),
+            StreamResponse::Timeout => Err(StreamError::Timeout),
+            _ => Err(StreamError::Unexpected),
+        }
+    }
+}
+
+/// Sends the Request with the Sans and stores its Response
+async fn exchange<'r, 's>(
+    sans: &mut Sans<'s, Stream>,
+    response: &'r mut Option<SansResponse<'s, Stream>>,
+    request: &StreamRequest<'_>,
+    #[cfg(feature = "coverage")] location: &'static Location<'static>,
+) -> StreamResponse<'r> {
+    let handle = match response.take() {
+        Some(previous) => {
+            drop(previous);
+            sans.send(request)
+        }
+        None => sans.start(request),
+    };
+    // The await point is the caller of the Framed, not this function
+    #[cfg(feature = "coverage")]
+    let handle = handle.at(location);
+    let next = handle.await;
+    *response.insert(next).response()
+}
+
+/// Returns the position of the first occurrence of the non-empty needle
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
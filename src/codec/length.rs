@@ -0,0 +1,284 @@
+//! Length-delimited frames.
+//!
+//! Every frame is the payload prefixed with its length. The [LengthDelimited] codec supports
+//! fixed width prefixes of one, two and four bytes in both byte orders and the variable width
+//! LEB128 prefix. The maximum length of the payload is checked for both decoded and encoded
+//! frames, so a malicious peer could not make the reader wait for a huge frame. The prefix could
+//! also count other bytes than the payload, e.g. itself, which is described by the adjustment
+//! added to the prefix to get the length of the payload.
+//!
+//! ```
+//! # use asansio::codec::Decoder;
+//! # use asansio::codec::Encoder;
+//! # use asansio::codec::length::Endian;
+//! # use asansio::codec::length::LengthDelimited;
+//! # use asansio::codec::length::Prefix;
+//! #
+//! // The big endian prefix of two bytes counts also itself
+//! let mut codec = LengthDelimited::new()
+//!     .prefix(Prefix::U16)
+//!     .endian(Endian::Big)
+//!     .adjustment(-2);
+//!
+//! let mut buf = [0; 5];
+//! assert_eq!(codec.encoded_len(b"abc"), Ok(5));
+//! codec.encode(b"abc", &mut buf);
+//! assert_eq!(buf, *b"\x00\x05abc");
+//!
+//! assert_eq!(codec.frame_len(&buf[..4]), Ok(None));
+//! assert_eq!(codec.frame_len(&buf), Ok(Some(5)));
+//! assert_eq!(codec.decode(&buf), Ok(&b"abc"[..]));
+//! ```
+
+use crate::codec::Decoder;
+use crate::codec::Encoder;
+use core::fmt;
+
+/// The width of the length prefix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Prefix {
+    /// The single byte.
+    U8,
+    /// Two bytes.
+    U16,
+    /// Four bytes.
+    U32,
+    /// LEB128 of at most ten bytes, seven bits in every byte, the least significant first.
+    Varint,
+}
+
+/// The byte order of fixed width prefixes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endian {
+    /// The most significant byte first.
+    Big,
+    /// The least significant byte first.
+    Little,
+}
+
+/// The error of the length-delimited frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LengthError {
+    /// The payload is longer than the maximum.
+    TooLong {
+        /// The length of the payload.
+        len: usize,
+        /// The maximum length of the payload.
+        max: usize,
+    },
+    /// The prefix is malformed or the adjusted length is negative. The payload shorter than the
+    /// positive adjustment could not be encoded too.
+    Invalid,
+}
+
+impl fmt::Display for LengthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLong { len, max } => {
+                write!(f, "frame of {len} bytes exceeds the maximum of {max} bytes")
+            }
+            Self::Invalid => write!(f, "invalid length prefix"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LengthError {}
+
+/// The maximum number of bytes of the varint prefix
+const VARINT_LEN: usize = 10;
+
+/// The codec of length-delimited frames.
+///
+/// It decodes frames as payloads and encodes byte slices. The default prefix is the big endian
+/// [Prefix::U32] without the adjustment and the maximum length of the payload is 8 MiB.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LengthDelimited {
+    prefix: Prefix,
+    endian: Endian,
+    max: usize,
+    adjustment: isize,
+}
+
+impl LengthDelimited {
+    /// Creates the codec with default settings.
+    pub const fn new() -> Self {
+        Self {
+            prefix: Prefix::U32,
+            endian: Endian::Big,
+            max: 8 * 1024 * 1024,
+            adjustment: 0,
+        }
+    }
+
+    /// Sets the width of the prefix.
+    pub const fn prefix(mut self, prefix: Prefix) -> Self {
+        self.prefix = prefix;
+        self
+    }
+
+    /// Sets the byte order of the fixed width prefix.
+    pub const fn endian(mut self, endian: Endian) -> Self {
+        self.endian = endian;
+        self
+    }
+
+    /// Sets the maximum length of the payload.
+    pub const fn max_frame_len(mut self, max: usize) -> Self {
+        self.max = max;
+        self
+    }
+
+    /// Sets the adjustment added to the prefix to get the length of the payload, e.g. the
+    /// negative width of the prefix if it counts itself.
+    pub const fn adjustment(mut self, adjustment: isize) -> Self {
+        self.adjustment = adjustment;
+        self
+    }
+
+    /// The maximum length of the payload, which is limited also by the prefix
+    fn limit(&self) -> usize {
+        let prefix_max = match self.prefix {
+            Prefix::U8 => u8::MAX as i128,
+            Prefix::U16 => u16::MAX as i128,
+            Prefix::U32 => u32::MAX as i128,
+            Prefix::Varint => u64::MAX as i128,
+        };
+        let limit = (prefix_max + self.adjustment as i128).max(0);
+        usize::try_from(limit).unwrap_or(usize::MAX).min(self.max)
+    }
+
+    /// Returns the width of the prefix and the value of the prefix if it is complete
+    fn read_prefix(&self, buf: &[u8]) -> Result<Option<(usize, u64)>, LengthError> {
+        let width = match self.prefix {
+            Prefix::U8 => 1,
+            Prefix::U16 => 2,
+            Prefix::U32 => 4,
+            Prefix::Varint => return read_varint(buf),
+        };
+        let Some(bytes) = buf.get(..width) else {
+            return Ok(None);
+        };
+        let value = match self.endian {
+            Endian::Big => bytes
+                .iter()
+                .fold(0, |value, byte| value << 8 | *byte as u64),
+            Endian::Little => bytes
+                .iter()
+                .rev()
+                .fold(0, |value, byte| value << 8 | *byte as u64),
+        };
+        Ok(Some((width, value)))
+    }
+
+    /// Returns the width of the prefix and the length of the payload if the prefix is complete
+    fn header(&self, buf: &[u8]) -> Result<Option<(usize, usize)>, LengthError> {
+        let Some((width, value)) = self.read_prefix(buf)? else {
+            return Ok(None);
+        };
+        let len = usize::try_from(value as i128 + self.adjustment as i128)
+            .map_err(|_| LengthError::Invalid)?;
+        if len > self.max {
+            return Err(LengthError::TooLong { len, max: self.max });
+        }
+        Ok(Some((width, len)))
+    }
+
+    /// The value of the prefix of the payload checked by the limit
+    fn value(&self, len: usize) -> u64 {
+        (len as i128 - self.adjustment as i128) as u64
+    }
+}
+
+impl Default for LengthDelimited {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn read_varint(buf: &[u8]) -> Result<Option<(usize, u64)>, LengthError> {
+    let mut value = 0_u64;
+    for (idx, byte) in buf.iter().take(VARINT_LEN).enumerate() {
+        let bits = (*byte & 0x7f) as u64;
+        if idx == VARINT_LEN - 1 && bits > 1 {
+            return Err(LengthError::Invalid);
+        }
+        value |= bits << (7 * idx);
+        if byte & 0x80 == 0 {
+            return Ok(Some((idx + 1, value)));
+        }
+    }
+    if buf.len() >= VARINT_LEN {
+        return Err(LengthError::Invalid);
+    }
+    Ok(None)
+}
+
+fn varint_len(mut value: u64) -> usize {
+    let mut len = 1;
+    while value >= 0x80 {
+        value >>= 7;
+        len += 1;
+    }
+    len
+}
+
+impl Decoder for LengthDelimited {
+    type Frame<'a> = &'a [u8];
+    type Error = LengthError;
+
+    fn frame_len(&mut self, buf: &[u8]) -> Result<Option<usize>, LengthError> {
+        Ok(self
+            .header(buf)?
+            .map(|(width, len)| width.checked_add(len).ok_or(LengthError::Invalid))
+            .transpose()?
+            .filter(|len| *len <= buf.len()))
+    }
+
+    fn decode<'a>(&mut self, frame: &'a [u8]) -> Result<&'a [u8], LengthError> {
+        let (width, len) = self.header(frame)?.ok_or(LengthError::Invalid)?;
+        let end = width.checked_add(len).ok_or(LengthError::Invalid)?;
+        frame.get(width..end).ok_or(LengthError::Invalid)
+    }
+}
+
+impl Encoder<[u8]> for LengthDelimited {
+    type Error = LengthError;
+
+    fn encoded_len(&mut self, item: &[u8]) -> Result<usize, LengthError> {
+        let len = item.len();
+        if (len as i128) < self.adjustment as i128 {
+            return Err(LengthError::Invalid);
+        }
+        let max = self.limit();
+        if len > max {
+            return Err(LengthError::TooLong { len, max });
+        }
+        let width = match self.prefix {
+            Prefix::U8 => 1,
+            Prefix::U16 => 2,
+            Prefix::U32 => 4,
+            Prefix::Varint => varint_len(self.value(len)),
+        };
+        Ok(width + len)
+    }
+
+    fn encode(&mut self, item: &[u8], buf: &mut [u8]) {
+        let value = self.value(item.len());
+        let width = buf.len() - item.len();
+        let (prefix, payload) = buf.split_at_mut(width);
+        match (self.prefix, self.endian) {
+            (Prefix::Varint, _) => {
+                let mut value = value;
+                for byte in prefix.iter_mut() {
+                    *byte = (value & 0x7f) as u8 | 0x80;
+                    value >>= 7;
+                }
+                prefix[width - 1] &= 0x7f;
+            }
+            (_, Endian::Big) => prefix.copy_from_slice(&value.to_be_bytes()[8 - width..]),
+            (_, Endian::Little) => prefix.copy_from_slice(&value.to_le_bytes()[..width]),
+        }
+        payload.copy_from_slice(item);
+    }
+}
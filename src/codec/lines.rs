@@ -0,0 +1,242 @@
+//! Line-based and delimited frames.
+//!
+//! Text protocols like SMTP or the inline commands of Redis terminate every frame with the line
+//! ending. The [Lines] codec decodes lines without their endings and encodes byte slices and
+//! strings with the ending appended. By default it reads lines terminated by both CRLF and LF and
+//! writes CRLF, other [Delimiter]s are strict. The maximum length of the line is checked for both
+//! decoded and encoded lines, and already for the incomplete line in the buffer, so a peer could
+//! not make the reader wait for an endless line.
+//!
+//! The data not limited by the read buffer, e.g. the message of the SMTP `DATA` command, is read
+//! in chunks with [Framed::read_until](crate::codec::Framed::read_until).
+//!
+//! ```
+//! # use asansio::codec::Decoder;
+//! # use asansio::codec::Encoder;
+//! # use asansio::codec::lines::Delimiter;
+//! # use asansio::codec::lines::LineError;
+//! # use asansio::codec::lines::Lines;
+//! #
+//! let mut codec = Lines::new().max_line_len(8);
+//!
+//! let buf = b"HELO a\r\nQUIT\nNO";
+//! assert_eq!(codec.frame_len(buf), Ok(Some(8)));
+//! assert_eq!(codec.decode(&buf[..8]), Ok(&b"HELO a"[..]));
+//! assert_eq!(codec.frame_len(&buf[8..]), Ok(Some(5)));
+//! assert_eq!(codec.decode(&buf[8..13]), Ok(&b"QUIT"[..]));
+//! assert_eq!(codec.frame_len(&buf[13..]), Ok(None));
+//!
+//! let mut line = [0; 6];
+//! assert_eq!(codec.encoded_len("QUIT"), Ok(6));
+//! codec.encode("QUIT", &mut line);
+//! assert_eq!(line, *b"QUIT\r\n");
+//!
+//! let mut codec = Lines::new().delimiter(Delimiter::Bytes(b"||"));
+//! assert_eq!(codec.encoded_len("a||b"), Err(LineError::Delimiter));
+//! ```
+
+use crate::codec::Decoder;
+use crate::codec::Encoder;
+use crate::codec::find;
+use core::fmt;
+
+/// The delimiter terminating lines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Delimiter<'d> {
+    /// Lines are terminated by LF.
+    Lf,
+    /// Lines are terminated by CRLF.
+    CrLf,
+    /// Lines are terminated by LF, the CR before it is not the part of the line. Lines are written
+    /// with CRLF.
+    Any,
+    /// Lines are terminated by the non-empty sequence of bytes.
+    Bytes(&'d [u8]),
+}
+
+impl<'d> Delimiter<'d> {
+    /// The delimiter written after lines
+    fn bytes(self) -> &'d [u8] {
+        match self {
+            Self::Lf => b"\n",
+            Self::CrLf | Self::Any => b"\r\n",
+            Self::Bytes(bytes) => bytes,
+        }
+    }
+
+    /// The delimiter searched for in lines
+    fn needle(self) -> &'d [u8] {
+        match self {
+            Self::Any => b"\n",
+            delimiter => delimiter.bytes(),
+        }
+    }
+}
+
+/// The error of the line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineError {
+    /// The line is longer than the maximum.
+    TooLong {
+        /// The length of the line or of its part in the buffer.
+        len: usize,
+        /// The maximum length of the line.
+        max: usize,
+    },
+    /// The encoded line contains the delimiter.
+    Delimiter,
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLong { len, max } => {
+                write!(f, "line of {len} bytes exceeds the maximum of {max} bytes")
+            }
+            Self::Delimiter => write!(f, "line contains the delimiter"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LineError {}
+
+/// The codec of lines.
+///
+/// It decodes lines without the delimiter and encodes byte slices and strings. The default
+/// delimiter is [Delimiter::Any] and the maximum length of the line is 8 KiB. The codec remembers
+/// how much of the incomplete line was searched for the delimiter, so it is not searched again
+/// after every read, and forgets it when it is [reset](Decoder::reset).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Lines<'d> {
+    delimiter: Delimiter<'d>,
+    max: usize,
+    searched: usize,
+}
+
+impl<'d> Lines<'d> {
+    /// Creates the codec with default settings.
+    pub const fn new() -> Self {
+        Self {
+            delimiter: Delimiter::Any,
+            max: 8 * 1024,
+            searched: 0,
+        }
+    }
+
+    /// Sets the delimiter of lines.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [Delimiter::Bytes] is empty.
+    pub const fn delimiter(mut self, delimiter: Delimiter<'d>) -> Self {
+        if let Delimiter::Bytes(bytes) = delimiter {
+            assert!(!bytes.is_empty(), "the delimiter is empty");
+        }
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Sets the maximum length of the line without the delimiter.
+    pub const fn max_line_len(mut self, max: usize) -> Self {
+        self.max = max;
+        self
+    }
+
+    /// Returns the length of the line and of the frame if the frame is complete
+    fn split(&mut self, buf: &[u8]) -> Option<(usize, usize)> {
+        let needle = self.delimiter.needle();
+        let from = self.searched.min(buf.len());
+        let Some(idx) = find(&buf[from..], needle).map(|idx| from + idx) else {
+            self.searched = buf.len().saturating_sub(needle.len() - 1);
+            return None;
+        };
+        self.searched = 0;
+        let line = match self.delimiter {
+            Delimiter::Any if idx > 0 && buf[idx - 1] == b'\r' => idx - 1,
+            _ => idx,
+        };
+        Some((line, idx + needle.len()))
+    }
+
+    /// Checks the length of the line
+    fn check(&self, len: usize) -> Result<(), LineError> {
+        if len > self.max {
+            return Err(LineError::TooLong { len, max: self.max });
+        }
+        Ok(())
+    }
+}
+
+impl Default for Lines<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for Lines<'_> {
+    type Frame<'a> = &'a [u8];
+    type Error = LineError;
+
+    fn frame_len(&mut self, buf: &[u8]) -> Result<Option<usize>, LineError> {
+        match self.split(buf) {
+            Some((line, len)) => self.check(line).map(|_| Some(len)),
+            None => {
+                // The end of the buffer could be the part of the delimiter
+                let partial = self.delimiter.bytes().len() - 1;
+                let line = buf.len().saturating_sub(partial);
+                self.check(line).inspect_err(|_| self.searched = 0)?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.searched = 0;
+    }
+
+    fn decode<'a>(&mut self, frame: &'a [u8]) -> Result<&'a [u8], LineError> {
+        self.reset();
+        let (line, _) = self.split(frame).ok_or(LineError::Delimiter)?;
+        self.check(line)?;
+        Ok(&frame[..line])
+    }
+}
+
+impl Encoder<[u8]> for Lines<'_> {
+    type Error = LineError;
+
+    fn encoded_len(&mut self, item: &[u8]) -> Result<usize, LineError> {
+        self.check(item.len())?;
+        let delimiter = self.delimiter.needle();
+        if find(item, delimiter).is_some() {
+            return Err(LineError::Delimiter);
+        }
+        // The end of the line and the start of the delimiter must not form the delimiter
+        let len = delimiter.len();
+        if (1..len)
+            .any(|k| item.ends_with(&delimiter[..k]) && delimiter[k..] == delimiter[..len - k])
+        {
+            return Err(LineError::Delimiter);
+        }
+        Ok(item.len() + self.delimiter.bytes().len())
+    }
+
+    fn encode(&mut self, item: &[u8], buf: &mut [u8]) {
+        let (line, delimiter) = buf.split_at_mut(item.len());
+        line.copy_from_slice(item);
+        delimiter.copy_from_slice(self.delimiter.bytes());
+    }
+}
+
+impl Encoder<str> for Lines<'_> {
+    type Error = LineError;
+
+    fn encoded_len(&mut self, item: &str) -> Result<usize, LineError> {
+        self.encoded_len(item.as_bytes())
+    }
+
+    fn encode(&mut self, item: &str, buf: &mut [u8]) {
+        self.encode(item.as_bytes(), buf)
+    }
+}
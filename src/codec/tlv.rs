@@ -0,0 +1,537 @@
+//! Tag-length-value frames and their routing to sub-protocols.
+//!
+//! Every frame is the [Element] of the tag, the length and the value. The [Tlv] codec supports
+//! tags and lengths of fixed widths in both byte orders and BER encoded ones, where the tag has
+//! one to four bytes and the length uses the short or the long definite form. The value of the
+//! constructed element is the sequence of nested elements, which are iterated with
+//! [Tlv::elements]. The maximum length of the value is checked for both decoded and encoded
+//! elements.
+//!
+//! The [Registry] routes elements read from the [Framed] stream to sub-protocol Sans tasks by
+//! their tags. Every sub-protocol task speaks the [Routed] vocabulary: it awaits elements of its
+//! tags and writes elements to the stream, so it does not depend on the framing nor on other
+//! sub-protocols. It replaces the manual dispatch of tags to sessions of sub-protocols.
+//!
+//! ```
+//! # use asansio::codec::Decoder;
+//! # use asansio::codec::Encoder;
+//! # use asansio::codec::tlv::Element;
+//! # use asansio::codec::tlv::Tlv;
+//! #
+//! let mut codec = Tlv::new();
+//!
+//! // The constructed element with two nested elements
+//! let mut value = [0; 8];
+//! codec.encode(&Element { tag: 0x5a, value: b"12" }, &mut value[..4]);
+//! codec.encode(&Element { tag: 0x9f02, value: b"3" }, &mut value[4..8]);
+//! assert_eq!(codec.encoded_len(&Element { tag: 0x70, value: &value }), Ok(10));
+//! let mut buf = [0; 10];
+//! codec.encode(&Element { tag: 0x70, value: &value }, &mut buf);
+//! assert_eq!(buf, *b"\x70\x08\x5a\x0212\x9f\x02\x013");
+//!
+//! assert_eq!(codec.frame_len(&buf), Ok(Some(10)));
+//! let element = codec.decode(&buf).unwrap();
+//! assert!(codec.is_constructed(element.tag));
+//! let nested: Result<Vec<_>, _> = codec.elements(element.value).collect();
+//! assert_eq!(
+//!     nested.unwrap(),
+//!     [Element { tag: 0x5a, value: b"12" }, Element { tag: 0x9f02, value: b"3" }]
+//! );
+//! ```
+
+use crate::Io;
+use crate::IoRequest;
+use crate::codec::Decoder;
+use crate::codec::Encoder;
+use crate::codec::Error;
+use crate::codec::Framed;
+use crate::codec::length::Endian;
+use crate::net::StreamRequest;
+use crate::net::StreamResponse;
+use core::fmt;
+use core::pin::Pin;
+
+/// The width of the tag or of the length.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Width {
+    /// The single byte.
+    U8,
+    /// Two bytes.
+    U16,
+    /// Four bytes.
+    U32,
+    /// The BER encoding of one to four bytes of the tag or up to five bytes of the length.
+    Ber,
+}
+
+impl Width {
+    /// The number of bytes of the fixed width
+    fn fixed(self) -> Option<usize> {
+        match self {
+            Self::U8 => Some(1),
+            Self::U16 => Some(2),
+            Self::U32 => Some(4),
+            Self::Ber => None,
+        }
+    }
+}
+
+/// The element of the tag and the value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Element<'a> {
+    /// The tag, BER tags are stored as their big endian bytes, e.g. `0x9f02`.
+    pub tag: u32,
+    /// The value, which is the sequence of nested elements for the constructed element.
+    pub value: &'a [u8],
+}
+
+/// The error of the TLV element.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TlvError {
+    /// The value is longer than the maximum.
+    TooLong {
+        /// The length of the value.
+        len: usize,
+        /// The maximum length of the value.
+        max: usize,
+    },
+    /// The tag or the length is malformed or does not fit into its width.
+    Invalid,
+    /// No handler of the [Registry] waits for the element of the tag.
+    Unrouted(u32),
+    /// The handler of the [Registry] sent [StreamRequest::Read], which would bypass the framing.
+    StreamRead,
+}
+
+impl fmt::Display for TlvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLong { len, max } => {
+                write!(f, "value of {len} bytes exceeds the maximum of {max} bytes")
+            }
+            Self::Invalid => write!(f, "invalid tag or length"),
+            Self::Unrouted(tag) => write!(f, "no handler waits for the tag {tag:#x}"),
+            Self::StreamRead => write!(f, "stream read bypasses the framing"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TlvError {}
+
+/// The codec of TLV elements.
+///
+/// It decodes and encodes [Element]s. The default tag and length are [Width::Ber], the byte order
+/// of fixed widths is big endian and the maximum length of the value is 8 MiB. BER tags with the
+/// constructed bit are constructed by default, other tags are primitive.
+#[derive(Clone, Copy, Debug)]
+pub struct Tlv {
+    tag: Width,
+    length: Width,
+    endian: Endian,
+    max: usize,
+    constructed: Option<fn(u32) -> bool>,
+}
+
+impl Tlv {
+    /// Creates the codec with default settings.
+    pub const fn new() -> Self {
+        Self {
+            tag: Width::Ber,
+            length: Width::Ber,
+            endian: Endian::Big,
+            max: 8 * 1024 * 1024,
+            constructed: None,
+        }
+    }
+
+    /// Sets the width of the tag.
+    pub const fn tag(mut self, tag: Width) -> Self {
+        self.tag = tag;
+        self
+    }
+
+    /// Sets the width of the length.
+    pub const fn length(mut self, length: Width) -> Self {
+        self.length = length;
+        self
+    }
+
+    /// Sets the byte order of the fixed width tag and length.
+    pub const fn endian(mut self, endian: Endian) -> Self {
+        self.endian = endian;
+        self
+    }
+
+    /// Sets the maximum length of the value.
+    pub const fn max_value_len(mut self, max: usize) -> Self {
+        self.max = max;
+        self
+    }
+
+    /// Sets the predicate of constructed tags.
+    pub const fn constructed(mut self, constructed: fn(u32) -> bool) -> Self {
+        self.constructed = Some(constructed);
+        self
+    }
+
+    /// Returns true if the value of the tag is the sequence of nested elements.
+    pub fn is_constructed(&self, tag: u32) -> bool {
+        match (self.constructed, self.tag) {
+            (Some(constructed), _) => constructed(tag),
+            (None, Width::Ber) => (tag >> (8 * (significant(tag as u64) - 1))) & 0x20 != 0,
+            (None, _) => false,
+        }
+    }
+
+    /// Iterates over the nested elements of the constructed value.
+    pub fn elements<'a>(&self, value: &'a [u8]) -> Elements<'a> {
+        Elements {
+            codec: *self,
+            buf: value,
+        }
+    }
+
+    /// Returns the tag, the length of the header and the length of the value if the header is
+    /// complete
+    fn header(&self, buf: &[u8]) -> Result<Option<(u32, usize, usize)>, TlvError> {
+        let tag = match self.tag.fixed() {
+            Some(width) => read_fixed(buf, width, self.endian).map(|tag| (tag as u32, width)),
+            None => read_ber_tag(buf)?,
+        };
+        let Some((tag, tag_len)) = tag else {
+            return Ok(None);
+        };
+        let buf = &buf[tag_len..];
+        let length = match self.length.fixed() {
+            Some(width) => read_fixed(buf, width, self.endian).map(|len| (len, width)),
+            None => read_ber_length(buf)?,
+        };
+        let Some((len, length_len)) = length else {
+            return Ok(None);
+        };
+        let len = usize::try_from(len).map_err(|_| TlvError::Invalid)?;
+        if len > self.max {
+            return Err(TlvError::TooLong { len, max: self.max });
+        }
+        Ok(Some((tag, tag_len + length_len, len)))
+    }
+
+    /// Returns the width of the tag
+    fn tag_len(&self, tag: u32) -> Result<usize, TlvError> {
+        match self.tag.fixed() {
+            Some(width) if width < 4 && tag >> (8 * width) != 0 => Err(TlvError::Invalid),
+            Some(width) => Ok(width),
+            None => {
+                let width = significant(tag as u64);
+                match read_ber_tag(&tag.to_be_bytes()[4 - width..]) {
+                    Ok(Some((_, len))) if len == width => Ok(width),
+                    _ => Err(TlvError::Invalid),
+                }
+            }
+        }
+    }
+
+    /// Returns the width of the length
+    fn length_len(&self, len: usize) -> Result<usize, TlvError> {
+        let (width, prefix_max) = match self.length.fixed() {
+            Some(width) => (width, u64::MAX >> (64 - 8 * width)),
+            None if len < 0x80 => (1, u32::MAX as u64),
+            None => (1 + significant(len as u64), u32::MAX as u64),
+        };
+        let max = usize::try_from(prefix_max)
+            .unwrap_or(usize::MAX)
+            .min(self.max);
+        if len > max {
+            return Err(TlvError::TooLong { len, max });
+        }
+        Ok(width)
+    }
+}
+
+impl Default for Tlv {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads the fixed width number
+fn read_fixed(buf: &[u8], width: usize, endian: Endian) -> Option<u64> {
+    let bytes = buf.get(..width)?;
+    Some(match endian {
+        Endian::Big => bytes
+            .iter()
+            .fold(0, |value, byte| value << 8 | *byte as u64),
+        Endian::Little => bytes
+            .iter()
+            .rev()
+            .fold(0, |value, byte| value << 8 | *byte as u64),
+    })
+}
+
+/// Writes the fixed width number
+fn write_fixed(value: u64, buf: &mut [u8], endian: Endian) {
+    let width = buf.len();
+    match endian {
+        Endian::Big => buf.copy_from_slice(&value.to_be_bytes()[8 - width..]),
+        Endian::Little => buf.copy_from_slice(&value.to_le_bytes()[..width]),
+    }
+}
+
+/// Reads the BER tag of at most four bytes
+fn read_ber_tag(buf: &[u8]) -> Result<Option<(u32, usize)>, TlvError> {
+    let Some(first) = buf.first() else {
+        return Ok(None);
+    };
+    if first & 0x1f != 0x1f {
+        return Ok(Some((*first as u32, 1)));
+    }
+    let mut tag = *first as u32;
+    for (idx, byte) in buf.iter().enumerate().skip(1) {
+        if idx == 4 {
+            return Err(TlvError::Invalid);
+        }
+        tag = tag << 8 | *byte as u32;
+        if byte & 0x80 == 0 {
+            return Ok(Some((tag, idx + 1)));
+        }
+    }
+    if buf.len() >= 4 {
+        return Err(TlvError::Invalid);
+    }
+    Ok(None)
+}
+
+/// Reads the BER definite length of at most four bytes after the first one
+fn read_ber_length(buf: &[u8]) -> Result<Option<(u64, usize)>, TlvError> {
+    let Some(first) = buf.first() else {
+        return Ok(None);
+    };
+    if first & 0x80 == 0 {
+        return Ok(Some((*first as u64, 1)));
+    }
+    let width = (first & 0x7f) as usize;
+    if width == 0 || width > 4 {
+        return Err(TlvError::Invalid);
+    }
+    Ok(read_fixed(&buf[1..], width, Endian::Big).map(|len| (len, 1 + width)))
+}
+
+/// The number of significant bytes of the number
+fn significant(value: u64) -> usize {
+    (8 - value.leading_zeros() as usize / 8).max(1)
+}
+
+/// The iterator of nested elements returned by [Tlv::elements].
+#[derive(Clone, Debug)]
+pub struct Elements<'a> {
+    codec: Tlv,
+    buf: &'a [u8],
+}
+
+impl<'a> Iterator for Elements<'a> {
+    type Item = Result<Element<'a>, TlvError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.is_empty() {
+            return None;
+        }
+        let element = match self.codec.frame_len(self.buf) {
+            Ok(Some(len)) => {
+                let (frame, rest) = self.buf.split_at(len);
+                self.buf = rest;
+                return Some(self.codec.decode(frame));
+            }
+            Ok(None) => Err(TlvError::Invalid),
+            Err(err) => Err(err),
+        };
+        self.buf = &[];
+        Some(element)
+    }
+}
+
+impl Decoder for Tlv {
+    type Frame<'a> = Element<'a>;
+    type Error = TlvError;
+
+    fn frame_len(&mut self, buf: &[u8]) -> Result<Option<usize>, TlvError> {
+        Ok(self
+            .header(buf)?
+            .map(|(_, header, len)| header + len)
+            .filter(|len| *len <= buf.len()))
+    }
+
+    fn decode<'a>(&mut self, frame: &'a [u8]) -> Result<Element<'a>, TlvError> {
+        let (tag, header, len) = self.header(frame)?.ok_or(TlvError::Invalid)?;
+        let value = frame.get(header..header + len).ok_or(TlvError::Invalid)?;
+        Ok(Element { tag, value })
+    }
+}
+
+impl Encoder<Element<'_>> for Tlv {
+    type Error = TlvError;
+
+    fn encoded_len(&mut self, item: &Element<'_>) -> Result<usize, TlvError> {
+        Ok(self.tag_len(item.tag)? + self.length_len(item.value.len())? + item.value.len())
+    }
+
+    fn encode(&mut self, item: &Element<'_>, buf: &mut [u8]) {
+        let len = item.value.len();
+        let (header, value) = buf.split_at_mut(buf.len() - len);
+        let (tag, length) = header.split_at_mut(match self.tag.fixed() {
+            Some(width) => width,
+            None => significant(item.tag as u64),
+        });
+        match self.tag {
+            Width::Ber => tag.copy_from_slice(&item.tag.to_be_bytes()[4 - tag.len()..]),
+            _ => write_fixed(item.tag as u64, tag, self.endian),
+        }
+        match self.length {
+            Width::Ber if len < 0x80 => length[0] = len as u8,
+            Width::Ber => {
+                length[0] = 0x80 | (length.len() - 1) as u8;
+                write_fixed(len as u64, &mut length[1..], Endian::Big);
+            }
+            _ => write_fixed(len as u64, length, self.endian),
+        }
+        value.copy_from_slice(item.value);
+    }
+}
+
+/// The vocabulary of sub-protocol tasks routed by the [Registry].
+pub struct Routed;
+
+/// The Request of the sub-protocol task.
+pub enum RoutedRequest<'a> {
+    /// Awaits the next element of tags of the task.
+    Read,
+    /// Writes the element to the stream.
+    Write(Element<'a>),
+    /// Sends other Request of the stream, e.g. [StreamRequest::Sleep]. The stream is read only
+    /// by [RoutedRequest::Read].
+    Stream(StreamRequest<'a>),
+}
+
+/// The Response to the sub-protocol task.
+pub enum RoutedResponse<'a> {
+    /// The element read for [RoutedRequest::Read].
+    Element(Element<'a>),
+    /// The element was written for [RoutedRequest::Write].
+    Written,
+    /// The Response of the stream for [RoutedRequest::Stream].
+    Stream(StreamResponse<'a>),
+}
+
+crate::message! {
+    Routed {
+        type Request<'r> = RoutedRequest<'r>;
+        type Response<'r> = RoutedResponse<'r>;
+    }
+}
+
+/// The handler of elements of the [Registry].
+pub trait Handler {
+    /// Returns true if elements of the tag are routed to the handler.
+    fn routes(&self, tag: u32) -> bool;
+
+    /// Retrieve the current Request of the handler or `None` if it finished.
+    fn request(&self) -> Option<&RoutedRequest<'_>>;
+
+    /// Handles the Response to the current Request.
+    fn handle(&mut self, response: &RoutedResponse<'_>);
+}
+
+/// The [Handler] running the sub-protocol task in its own session.
+pub struct Route<'a, 's, Task: Future<Output = ()>> {
+    tags: &'a [u32],
+    io: Io<'s, Routed>,
+    handler: Option<IoRequest<'a, Routed, Task>>,
+}
+
+impl<'a, 's: 'a, Task: Future<Output = ()>> Route<'a, 's, Task> {
+    /// Starts the sub-protocol task, which handles elements of the tags.
+    pub fn new(tags: &'a [u32], io: Io<'s, Routed>, task: Pin<&'a mut Task>) -> Self {
+        let handler = io.start(task);
+        Self { tags, io, handler }
+    }
+}
+
+impl<Task: Future<Output = ()>> Handler for Route<'_, '_, Task> {
+    fn routes(&self, tag: u32) -> bool {
+        self.tags.contains(&tag)
+    }
+
+    fn request(&self) -> Option<&RoutedRequest<'_>> {
+        self.handler.as_ref().map(|handler| handler.request())
+    }
+
+    fn handle(&mut self, response: &RoutedResponse<'_>) {
+        if let Some(handler) = self.handler.take() {
+            self.handler = self.io.handle(handler, response);
+        }
+    }
+}
+
+/// Routes elements of the stream to handlers by their tags.
+///
+/// Handlers are provided by the caller, so the registry does not allocate. The element is routed
+/// to the first handler of its tag, which waits for [RoutedRequest::Read]. Other Requests of
+/// handlers are served before the next element is read.
+pub struct Registry<'r, 'h> {
+    handlers: &'r mut [&'h mut dyn Handler],
+}
+
+impl<'r, 'h> Registry<'r, 'h> {
+    /// Creates the registry of handlers.
+    pub fn new(handlers: &'r mut [&'h mut dyn Handler]) -> Self {
+        Self { handlers }
+    }
+
+    /// Routes elements of the stream until it is closed. Returns [TlvError::Unrouted] if no
+    /// handler waits for the element and [TlvError::StreamRead] if the handler tries to read the
+    /// stream with [RoutedRequest::Stream].
+    pub async fn run(
+        &mut self,
+        framed: &mut Framed<'_, '_>,
+        codec: &mut Tlv,
+    ) -> Result<(), Error<TlvError>> {
+        for handler in self.handlers.iter_mut() {
+            serve(&mut **handler, framed, codec).await?;
+        }
+        while let Some(element) = framed.read(codec).await? {
+            let Some(handler) = self.handlers.iter_mut().find(|handler| {
+                handler.routes(element.tag)
+                    && matches!(handler.request(), Some(RoutedRequest::Read))
+            }) else {
+                return Err(Error::Codec(TlvError::Unrouted(element.tag)));
+            };
+            handler.handle(&RoutedResponse::Element(element));
+            serve(&mut **handler, framed, codec).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Serves Requests of the handler until it waits for the element or finishes
+async fn serve(
+    handler: &mut dyn Handler,
+    framed: &mut Framed<'_, '_>,
+    codec: &mut Tlv,
+) -> Result<(), Error<TlvError>> {
+    loop {
+        match handler.request() {
+            None | Some(RoutedRequest::Read) => return Ok(()),
+            Some(RoutedRequest::Write(element)) => {
+                framed.write(codec, element).await?;
+                handler.handle(&RoutedResponse::Written);
+            }
+            Some(RoutedRequest::Stream(StreamRequest::Read { .. })) => {
+                return Err(Error::Codec(TlvError::StreamRead));
+            }
+            Some(RoutedRequest::Stream(request)) => {
+                let response = framed.request(request).await;
+                handler.handle(&RoutedResponse::Stream(response));
+            }
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! Await-point coverage of Sans tasks.
+//!
+//! States of the Sans task are generated by the compiler, so they are identified by await
+//! points - the locations of `Sans::start` and `Sans::handle` calls. With the `coverage` feature
+//! these calls capture their location by `#[track_caller]`, [IoRequest::location] returns it and
+//! every Request sent by any Sans task of the process is counted in the global [report]. The
+//! report of a test run lists await points hit by test drivers and how often, so await points of
+//! the protocol missing in the report are untested states.
+//!
+//! Drivers with own bookkeeping could collect the [Coverage] from [IoRequest::location] too.
+//!
+//! ```
+//! # use asansio::Session;
+//! # use asansio::coverage;
+//! # use std::pin::pin;
+//! #
+//! # struct Request<'a>(&'a [u8]);
+//! # struct Response<'a>(&'a [u8]);
+//! #
+//! # struct Proto;
+//! #
+//! # asansio::message! {
+//! #     Proto {
+//! #         type Request<'r> = Request<'r>;
+//! #         type Response<'r> = Response<'r>;
+//! #     }
+//! # }
+//! #
+//! let mut session = Session::<Proto>::new();
+//! let (mut sans, io) = session.split();
+//! let task = pin!(async {
+//!     sans.start(&Request(b"hello")).await;
+//! });
+//! let handler = io.start(task).unwrap();
+//! // The location of the `sans.start` call above
+//! let location = handler.location();
+//! assert_eq!(coverage::report().count(location), 1);
+//! ```
+//!
+//! [IoRequest::location]: crate::IoRequest::location
+
+use core::fmt;
+use core::panic::Location;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+/// Numbers of Requests sent from await points.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Coverage {
+    hits: BTreeMap<Location<'static>, usize>,
+}
+
+impl Coverage {
+    /// Creates the empty coverage.
+    pub const fn new() -> Self {
+        Self {
+            hits: BTreeMap::new(),
+        }
+    }
+
+    /// Counts the Request sent from the await point.
+    pub fn record(&mut self, location: &Location<'static>) {
+        *self.hits.entry(*location).or_default() += 1;
+    }
+
+    /// Adds counts of the other coverage.
+    pub fn merge(&mut self, other: &Coverage) {
+        for (location, count) in &other.hits {
+            *self.hits.entry(*location).or_default() += count;
+        }
+    }
+
+    /// Returns the number of Requests sent from the await point.
+    pub fn count(&self, location: &Location<'static>) -> usize {
+        self.hits.get(location).copied().unwrap_or(0)
+    }
+
+    /// Iterates over hit await points with their counts, ordered by the file, the line and the
+    /// column.
+    pub fn iter(&self) -> impl Iterator<Item = (&Location<'static>, usize)> {
+        self.hits.iter().map(|(location, count)| (location, *count))
+    }
+
+    /// Retrieve the coverage of await points in files with the path ending with the suffix.
+    pub fn file(&self, suffix: &str) -> Coverage {
+        Self {
+            hits: self
+                .hits
+                .iter()
+                .filter(|(location, _)| location.file().ends_with(suffix))
+                .map(|(location, count)| (*location, *count))
+                .collect(),
+        }
+    }
+
+    /// Returns true if no await point was hit.
+    pub fn is_empty(&self) -> bool {
+        self.hits.is_empty()
+    }
+}
+
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (location, count) in &self.hits {
+            writeln!(f, "{location} {count}")?;
+        }
+        Ok(())
+    }
+}
+
+static GLOBAL: Mutex<Coverage> = Mutex::new(Coverage::new());
+
+/// Counts the Request in the global coverage
+pub(crate) fn record(location: &Location<'static>) {
+    GLOBAL
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .record(location);
+}
+
+/// Retrieve the global coverage of all Sans tasks of the process.
+pub fn report() -> Coverage {
+    GLOBAL
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// Clears the global coverage.
+pub fn reset() {
+    *GLOBAL.lock().unwrap_or_else(PoisonError::into_inner) = Coverage::new();
+}
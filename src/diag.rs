@@ -0,0 +1,39 @@
+//! Diagnostic records of sessions for the `log` and `defmt` features.
+//!
+//! Every poll of the Sans task emits a debug record with the step number of the session and
+//! whether the task finished. A misuse of the session detected by the crate emits an error record
+//! before the panic, so it is visible also on targets, which abort on panics.
+
+/// Asserts the rule of the session, like `assert!`, and records the violation
+macro_rules! check {
+    ($cond:expr, $msg:literal $(,)?) => {
+        if !$cond {
+            #[cfg(feature = "log")]
+            log::error!($msg);
+            #[cfg(feature = "defmt")]
+            defmt::error!($msg);
+            panic!($msg);
+        }
+    };
+}
+
+/// Records the finished poll of the Sans task
+macro_rules! step {
+    ($step:expr, $finished:expr) => {
+        #[cfg(feature = "log")]
+        log::debug!("step {}, finished: {}", $step, $finished);
+        #[cfg(feature = "defmt")]
+        defmt::debug!("step {=usize}, finished: {=bool}", $step, $finished);
+    };
+}
+
+/// Records the panic of the Sans task
+#[cfg(feature = "std")]
+macro_rules! panicked {
+    () => {
+        #[cfg(feature = "log")]
+        log::error!("Sans task panicked, the session is poisoned");
+        #[cfg(feature = "defmt")]
+        defmt::error!("Sans task panicked, the session is poisoned");
+    };
+}
@@ -0,0 +1,175 @@
+//! Sequence diagrams of recorded sessions.
+//!
+//! The [Diagram] renders transcripts recorded by the [Recorder](crate::transcript::Recorder) or
+//! by the [Recorded](crate::loopback::Recorded) translation of the loopback connector as the
+//! Mermaid or PlantUML sequence diagram. Every session has two participants - the Sans task and
+//! its Io part. Requests are arrows from the Sans task to the Io, Responses are dashed arrows
+//! back and the end of the task is the note. Labels are records of the formatting [Hook], e.g.
+//! the [DebugHook]. Entries of many sessions are ordered by their
+//! [order](crate::transcript::Entry::order) of recording.
+//!
+//! ```
+//! # use asansio::Drive;
+//! # use asansio::Session;
+//! # use asansio::diagram::Diagram;
+//! # use asansio::transcript::DebugHook;
+//! # use asansio::transcript::Recorder;
+//! # use std::pin::pin;
+//! #
+//! # #[derive(Debug)]
+//! # struct Request<'a>(&'a str);
+//! # #[derive(Debug)]
+//! # struct Response<'a>(&'a str);
+//! #
+//! # struct Proto;
+//! #
+//! # asansio::message! {
+//! #     Proto {
+//! #         type Request<'r> = Request<'r>;
+//! #         type Response<'r> = Response<'r>;
+//! #     }
+//! # }
+//! #
+//! let mut session = Session::<Proto>::new();
+//! let (mut sans, io) = session.split();
+//! let mut recorder = Recorder::new(io, DebugHook);
+//!
+//! let mut task = pin!(async {
+//!     sans.start(&Request("hello")).await;
+//! });
+//! let handler = recorder.start(task.as_mut()).unwrap();
+//! recorder.handle(handler, &Response("world"));
+//!
+//! let diagram = Diagram::new().session("Client", "Io", recorder.transcript());
+//! assert_eq!(
+//!     diagram.mermaid(),
+//!     "sequenceDiagram\n\
+//!      \x20   participant P0 as Client\n\
+//!      \x20   participant P1 as Io\n\
+//!      \x20   P0->>P1: Request(\"hello\")\n\
+//!      \x20   P1-->>P0: Response(\"world\")\n\
+//!      \x20   Note over P0: finished\n"
+//! );
+//! ```
+//!
+//! [Hook]: crate::transcript::Hook
+//! [DebugHook]: crate::transcript::DebugHook
+
+use crate::transcript::Event;
+use crate::transcript::Transcript;
+use core::fmt::Display;
+use core::fmt::Write;
+use std::format;
+use std::string::String;
+use std::string::ToString;
+use std::vec::Vec;
+
+/// The single item of the diagram
+enum Item {
+    Request(String),
+    Response(String),
+    Note(String),
+}
+
+/// The item with its participants
+struct Step {
+    order: u64,
+    sans: usize,
+    io: usize,
+    item: Item,
+}
+
+/// The sequence diagram of sessions.
+#[derive(Default)]
+pub struct Diagram {
+    participants: Vec<String>,
+    steps: Vec<Step>,
+}
+
+impl Diagram {
+    /// Creates the empty diagram.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the session with names of the Sans task and its Io. Participants with the same name
+    /// are shared between sessions.
+    pub fn session<Req: Display, Resp: Display>(
+        mut self,
+        sans: &str,
+        io: &str,
+        transcript: &Transcript<Req, Resp>,
+    ) -> Self {
+        let sans = self.participant(sans);
+        let io = self.participant(io);
+        self.steps
+            .extend(transcript.entries().iter().map(|entry| Step {
+                order: entry.order,
+                sans,
+                io,
+                item: match &entry.event {
+                    Event::Request(request) => Item::Request(request.to_string()),
+                    Event::Response(response) => Item::Response(response.to_string()),
+                    Event::Finished => Item::Note("finished".to_string()),
+                    Event::Panicked(Some(msg)) => Item::Note(format!("panicked: {msg}")),
+                    Event::Panicked(None) => Item::Note("panicked".to_string()),
+                },
+            }));
+        self.steps.sort_by_key(|step| step.order);
+        self
+    }
+
+    fn participant(&mut self, name: &str) -> usize {
+        match self.participants.iter().position(|known| known == name) {
+            Some(idx) => idx,
+            None => {
+                self.participants.push(name.to_string());
+                self.participants.len() - 1
+            }
+        }
+    }
+
+    /// Renders the Mermaid sequence diagram.
+    pub fn mermaid(&self) -> String {
+        // Mermaid breaks lines on semicolons and uses # for entities
+        let escape = |label: &str| {
+            label
+                .replace('#', "#35;")
+                .replace(';', "#59;")
+                .replace('\n', "<br>")
+        };
+        let mut out = String::from("sequenceDiagram\n");
+        for (idx, name) in self.participants.iter().enumerate() {
+            writeln!(out, "    participant P{idx} as {}", escape(name)).unwrap();
+        }
+        for Step { sans, io, item, .. } in &self.steps {
+            match item {
+                Item::Request(label) => writeln!(out, "    P{sans}->>P{io}: {}", escape(label)),
+                Item::Response(label) => writeln!(out, "    P{io}-->>P{sans}: {}", escape(label)),
+                Item::Note(label) => writeln!(out, "    Note over P{sans}: {}", escape(label)),
+            }
+            .unwrap();
+        }
+        out
+    }
+
+    /// Renders the PlantUML sequence diagram.
+    pub fn plantuml(&self) -> String {
+        let escape = |label: &str| label.replace('\n', "\\n");
+        let mut out = String::from("@startuml\n");
+        for (idx, name) in self.participants.iter().enumerate() {
+            let name = escape(name).replace('"', "'");
+            writeln!(out, "participant \"{name}\" as P{idx}").unwrap();
+        }
+        for Step { sans, io, item, .. } in &self.steps {
+            match item {
+                Item::Request(label) => writeln!(out, "P{sans} -> P{io}: {}", escape(label)),
+                Item::Response(label) => writeln!(out, "P{io} --> P{sans}: {}", escape(label)),
+                Item::Note(label) => writeln!(out, "note over P{sans}: {}", escape(label)),
+            }
+            .unwrap();
+        }
+        out.push_str("@enduml\n");
+        out
+    }
+}
@@ -0,0 +1,123 @@
+//! Differential testing of two Sans tasks.
+//!
+//! The [run] function drives two implementations of the same protocol in lockstep, e.g. the old
+//! one and its rewrite. Both tasks get the same Responses from the [Respond] implementation and
+//! their Requests are compared by the user function after every step. The first difference is
+//! reported as the [Mismatch] with Requests recorded by the [Hook]. Tasks match also when both
+//! finish or both panic with the same message.
+//!
+//! The comparison could cover only the common subset of both protocols by the choice of
+//! Responses and the comparison function.
+
+use crate::Error;
+use crate::Io;
+use crate::IoRequest;
+use crate::Message;
+use crate::transcript::Event;
+use crate::transcript::Hook;
+use core::fmt;
+use core::fmt::Debug;
+use core::pin::Pin;
+use std::string::String;
+
+/// Responds to Requests of both Sans tasks.
+pub trait Respond<M: Message> {
+    /// Responds to the Request of the left task, the right one gets the same Response. `None`
+    /// finishes the comparison.
+    fn respond<'a>(&'a mut self, request: &M::Request<'_>) -> Option<M::Response<'a>>;
+}
+
+/// The first difference of Sans tasks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatch<Req, Resp> {
+    /// The number of Responses sent to both tasks before the difference.
+    pub step: usize,
+    /// The Request or the end of the left task.
+    pub left: Event<Req, Resp>,
+    /// The Request or the end of the right task.
+    pub right: Event<Req, Resp>,
+}
+
+impl<Req: Debug, Resp: Debug> fmt::Display for Mismatch<Req, Resp> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "tasks differ after {} responses: left {:?}, right {:?}",
+            self.step, self.left, self.right
+        )
+    }
+}
+
+impl<Req: Debug, Resp: Debug> std::error::Error for Mismatch<Req, Resp> {}
+
+/// Drives both Sans tasks in lockstep until they finish, differ or the [Respond] implementation
+/// finishes. Returns the number of Responses sent to both tasks.
+pub fn run<'ls, 'rs, 'l, 'r, M, H, LeftTask, RightTask>(
+    left_io: &Io<'ls, M>,
+    left_task: Pin<&'l mut LeftTask>,
+    right_io: &Io<'rs, M>,
+    right_task: Pin<&'r mut RightTask>,
+    respond: &mut impl Respond<M>,
+    mut same: impl FnMut(&M::Request<'_>, &M::Request<'_>) -> bool,
+    hook: &mut H,
+) -> Result<usize, Mismatch<H::Request, H::Response>>
+where
+    'ls: 'l,
+    'rs: 'r,
+    M: Message,
+    H: Hook<M>,
+    LeftTask: Future<Output = ()> + ?Sized,
+    RightTask: Future<Output = ()> + ?Sized,
+{
+    let mut left = left_io.try_start(left_task);
+    let mut right = right_io.try_start(right_task);
+    let mut step = 0;
+    loop {
+        let (left_handler, right_handler) = match (left, right) {
+            (Ok(Some(left)), Ok(Some(right))) => (left, right),
+            (Ok(None), Ok(None)) => return Ok(step),
+            (Err(left), Err(right)) if left.message() == right.message() => return Ok(step),
+            (left, right) => {
+                return Err(Mismatch {
+                    step,
+                    left: event(hook, left),
+                    right: event(hook, right),
+                });
+            }
+        };
+
+        let left_request = left_handler.request();
+        let right_request = right_handler.request();
+        if !same(left_request, right_request) {
+            return Err(Mismatch {
+                step,
+                left: Event::Request(hook.request(left_request)),
+                right: Event::Request(hook.request(right_request)),
+            });
+        }
+
+        let Some(response) = respond.respond(left_request) else {
+            return Ok(step);
+        };
+        left = left_io.try_handle(left_handler, &response);
+        right = right_io.try_handle(right_handler, &response);
+        step += 1;
+    }
+}
+
+/// Records the Request or the end of the task
+fn event<M, H, Task>(
+    hook: &mut H,
+    result: Result<Option<IoRequest<'_, M, Task>>, Error>,
+) -> Event<H::Request, H::Response>
+where
+    M: Message,
+    H: Hook<M>,
+    Task: Future<Output = ()> + ?Sized,
+{
+    match result {
+        Ok(Some(handler)) => Event::Request(hook.request(handler.request())),
+        Ok(None) => Event::Finished,
+        Err(err) => Event::Panicked(err.message().map(String::from)),
+    }
+}
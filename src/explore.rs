@@ -0,0 +1,242 @@
+//! Bounded exhaustive exploration of Sans tasks.
+//!
+//! The Sans task is a pure function of the sequence of Responses, so all its reachable
+//! behaviours are explored by running the task from scratch with every sequence of Responses
+//! from the small alphabet. The [Explorer] runs sequences breadth-first up to the depth bound
+//! and checks invariants on every Request. The first failing sequence is the minimal
+//! [Counterexample] - there is no shorter one and it is the first one in the order of the
+//! alphabet.
+//!
+//! The task is created for every run by the factory, which boxes the task of the [Sans] part:
+//!
+//! ```
+//! # use asansio::Sans;
+//! # use asansio::explore::Explorer;
+//! #
+//! enum Request {
+//!     Value(u8),
+//! }
+//! enum Response {
+//!     Inc,
+//!     Reset,
+//! }
+//!
+//! struct Proto;
+//!
+//! asansio::message! {
+//!     Proto {
+//!         type Request<'r> = Request;
+//!         type Response<'r> = Response;
+//!     }
+//! }
+//!
+//! async fn counter(mut sans: Sans<'_, Proto>) {
+//!     let mut value = 0;
+//!     let mut response = sans.start(&Request::Value(value)).await;
+//!     loop {
+//!         match response.response() {
+//!             Response::Inc => value += 1,
+//!             Response::Reset => value = 0,
+//!         }
+//!         response = sans.handle(response, &Request::Value(value)).await;
+//!     }
+//! }
+//!
+//! let counterexample = Explorer::new(vec![Response::Reset, Response::Inc])
+//!     .depth(5)
+//!     .invariant("value below 3", |Request::Value(value)| *value < 3)
+//!     .run(|sans| Box::pin(counter(sans)))
+//!     .unwrap_err();
+//! assert_eq!(counterexample.responses, [1, 1, 1]);
+//! ```
+
+use crate::Message;
+use crate::Sans;
+use crate::Session;
+use core::fmt;
+use core::pin::Pin;
+use std::boxed::Box;
+use std::collections::VecDeque;
+use std::string::String;
+use std::vec::Vec;
+
+type Invariant<'e, M> = Box<dyn FnMut(&<M as Message>::Request<'_>) -> bool + 'e>;
+type Describe<'e, M> = Box<dyn Fn(&<M as Message>::Request<'_>) -> String + 'e>;
+
+/// The boxed Sans task created by the factory for every run.
+pub type Task<'s> = Pin<Box<dyn Future<Output = ()> + 's>>;
+
+/// The bounded exhaustive explorer of the Sans task.
+pub struct Explorer<'e, M: Message> {
+    alphabet: Vec<M::Response<'static>>,
+    depth: usize,
+    invariants: Vec<(String, Invariant<'e, M>)>,
+    describe: Option<Describe<'e, M>>,
+}
+
+/// The violation found by the [Explorer].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// The invariant with the name does not hold for the last Request.
+    Invariant(String),
+    /// The Sans task panicked with the message, if any.
+    Panicked(Option<String>),
+}
+
+/// The minimal sequence of Responses leading to the [Violation].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Counterexample {
+    /// Indices of Responses in the alphabet.
+    pub responses: Vec<usize>,
+    /// Descriptions of Requests before every Response and the last Request, if the explorer
+    /// describes Requests.
+    pub requests: Vec<String>,
+    /// The found violation.
+    pub violation: Violation,
+}
+
+impl fmt::Display for Counterexample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.violation {
+            Violation::Invariant(name) => write!(f, "invariant `{name}` violated")?,
+            Violation::Panicked(Some(msg)) => write!(f, "Sans task panicked: {msg}")?,
+            Violation::Panicked(None) => write!(f, "Sans task panicked")?,
+        }
+        write!(f, " after responses {:?}", self.responses)?;
+        for (idx, request) in self.requests.iter().enumerate() {
+            write!(f, "\n> {request}")?;
+            if let Some(response) = self.responses.get(idx) {
+                write!(f, "\n< {response}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Counterexample {}
+
+/// The summary of the exploration without violations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Report {
+    /// The number of runs of the Sans task.
+    pub runs: usize,
+    /// The number of sequences after which the Sans task finished.
+    pub finished: usize,
+    /// The number of sequences cut by the depth bound with the Sans task still waiting.
+    pub bounded: usize,
+}
+
+/// The end of the single run
+enum Run {
+    Pending,
+    Finished,
+}
+
+impl<'e, M: Message> Explorer<'e, M> {
+    /// Creates the explorer with the alphabet of Responses. The default depth is 4.
+    pub fn new(alphabet: Vec<M::Response<'static>>) -> Self {
+        Self {
+            alphabet,
+            depth: 4,
+            invariants: Vec::new(),
+            describe: None,
+        }
+    }
+
+    /// Sets the maximal number of Responses in the sequence.
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Adds the invariant checked on every Request.
+    pub fn invariant(
+        mut self,
+        name: impl Into<String>,
+        invariant: impl FnMut(&M::Request<'_>) -> bool + 'e,
+    ) -> Self {
+        self.invariants.push((name.into(), Box::new(invariant)));
+        self
+    }
+
+    /// Describes Requests in the counterexample with the function, e.g. their `Debug` output.
+    pub fn describe(mut self, describe: impl Fn(&M::Request<'_>) -> String + 'e) -> Self {
+        self.describe = Some(Box::new(describe));
+        self
+    }
+
+    /// Explores the Sans task created by the factory for every run.
+    pub fn run(
+        mut self,
+        mut factory: impl for<'s> FnMut(Sans<'s, M>) -> Task<'s>,
+    ) -> Result<Report, Counterexample> {
+        let mut report = Report {
+            runs: 0,
+            finished: 0,
+            bounded: 0,
+        };
+        let mut queue = VecDeque::from([Vec::new()]);
+        while let Some(responses) = queue.pop_front() {
+            report.runs += 1;
+            match self.run_once(&mut factory, &responses)? {
+                Run::Finished => report.finished += 1,
+                Run::Pending if responses.len() == self.depth => report.bounded += 1,
+                Run::Pending => queue.extend((0..self.alphabet.len()).map(|idx| {
+                    let mut next = responses.clone();
+                    next.push(idx);
+                    next
+                })),
+            }
+        }
+        Ok(report)
+    }
+
+    /// Runs the Sans task with the sequence of Responses, checking only the last Request, as
+    /// the previous ones were checked by shorter sequences.
+    fn run_once(
+        &mut self,
+        factory: &mut impl for<'s> FnMut(Sans<'s, M>) -> Task<'s>,
+        responses: &[usize],
+    ) -> Result<Run, Counterexample> {
+        let mut session = Session::<M>::new();
+        let (sans, io) = session.split();
+        let mut task = factory(sans);
+
+        let mut requests = Vec::new();
+        let mut result = io.try_start(task.as_mut());
+        for &idx in responses {
+            let handler = match result {
+                Ok(Some(handler)) => handler,
+                _ => unreachable!("the sequence is extended only for the waiting Sans task"),
+            };
+            if let Some(describe) = &self.describe {
+                requests.push(describe(handler.request()));
+            }
+            result = io.try_handle(handler, &self.alphabet[idx]);
+        }
+
+        let violation = match result {
+            Ok(None) => return Ok(Run::Finished),
+            Ok(Some(handler)) => {
+                let request = handler.request();
+                if let Some(describe) = &self.describe {
+                    requests.push(describe(request));
+                }
+                let failed = self
+                    .invariants
+                    .iter_mut()
+                    .find_map(|(name, invariant)| (!invariant(request)).then(|| name.clone()));
+                match failed {
+                    Some(name) => Violation::Invariant(name),
+                    None => return Ok(Run::Pending),
+                }
+            }
+            Err(err) => Violation::Panicked(err.message().map(String::from)),
+        };
+        Err(Counterexample {
+            responses: responses.to_vec(),
+            requests,
+            violation,
+        })
+    }
+}
@@ -0,0 +1,143 @@
+//! Fuzzing of Sans tasks.
+//!
+//! The [Fuzzer] interprets raw fuzzer bytes as random choices of the [Strategy] from the
+//! [prop](crate::prop) module and drives the Sans task with generated Responses through the
+//! [Io](crate::Io). Crashes are reported by panics, none of them is caught, so they propagate out
+//! of [Fuzzer::run] to the fuzzer:
+//!
+//! - the panic of the Sans task, including `unreachable!` and failed assertions, is propagated
+//!   with its original payload,
+//! - the failed property of the strategy panics with its message,
+//! - too many Responses in a row without consuming the input are the infinite request loop.
+//!
+//! The run ends without the crash when the Sans task finishes, the strategy finishes the case or
+//! it draws past the end of the input. The same strategy could be used for property tests and the
+//! fuzzer, e.g. the `cargo fuzz` target is:
+//!
+//! ```ignore
+//! fuzz_target!(|data: &[u8]| {
+//!     Fuzzer::new().run(data, |sans| Box::pin(run_client(sans)), Chunks::default());
+//! });
+//! ```
+//!
+//! The example of the Sans task accepting only even bytes:
+//!
+//! ```
+//! # use asansio::Sans;
+//! # use asansio::fuzz::Fuzzer;
+//! # use asansio::net::Stream;
+//! # use asansio::net::StreamRequest;
+//! # use asansio::net::StreamResponse;
+//! # use asansio::prop::Source;
+//! # use asansio::prop::Strategy;
+//! # use std::panic;
+//! #
+//! async fn even(mut sans: Sans<'_, Stream>) {
+//!     let read = StreamRequest::Read {
+//!         max: 4,
+//!         timeout: None,
+//!     };
+//!     let mut response = sans.start(&read).await;
+//!     while let StreamResponse::Data { data } = response.response() {
+//!         assert!(data.iter().all(|byte| byte % 2 == 0), "odd byte");
+//!         response = sans.handle(response, &read).await;
+//!     }
+//! }
+//!
+//! #[derive(Default)]
+//! struct Chunks(Vec<u8>);
+//!
+//! impl Strategy<Stream> for Chunks {
+//!     fn respond<'a>(
+//!         &'a mut self,
+//!         source: &mut Source,
+//!         _: &StreamRequest<'_>,
+//!     ) -> Result<Option<StreamResponse<'a>>, String> {
+//!         self.0 = source.bytes(1..=4);
+//!         Ok(Some(StreamResponse::Data { data: &self.0 }))
+//!     }
+//! }
+//!
+//! let fuzz = |data: &[u8]| Fuzzer::new().run(data, |sans| Box::pin(even(sans)), Chunks::default());
+//! fuzz(&[1, 2, 4]);
+//! assert!(panic::catch_unwind(|| fuzz(&[1, 2, 3])).is_err());
+//! ```
+
+use crate::Message;
+use crate::Sans;
+use crate::Session;
+use crate::explore::Task;
+use crate::prop::Origin;
+use crate::prop::Source;
+use crate::prop::Strategy;
+use std::vec::Vec;
+
+/// The fuzz target helper.
+#[derive(Clone, Copy, Debug)]
+pub struct Fuzzer {
+    idle: usize,
+}
+
+impl Default for Fuzzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fuzzer {
+    /// Creates the fuzzer. The default limit of Responses without consuming the input is 1000.
+    pub fn new() -> Self {
+        Self { idle: 1000 }
+    }
+
+    /// Sets the maximal number of Responses in a row without consuming the input, more of them
+    /// are the infinite request loop.
+    pub fn idle(mut self, idle: usize) -> Self {
+        self.idle = idle;
+        self
+    }
+
+    /// Runs the Sans task created by the factory with Responses of the strategy generated from
+    /// the fuzzer input. Panics on crashes.
+    pub fn run<M: Message, S: Strategy<M>>(
+        &self,
+        data: &[u8],
+        factory: impl for<'s> FnOnce(Sans<'s, M>) -> Task<'s>,
+        mut strategy: S,
+    ) {
+        let mut source = Source::new(
+            Vec::new(),
+            Origin::Input {
+                data: data.to_vec(),
+                offset: 0,
+            },
+        );
+        let mut session = Session::<M>::new();
+        let (sans, io) = session.split();
+        let mut task = factory(sans);
+
+        let mut idle = 0;
+        let mut handler = io.start(task.as_mut());
+        while let Some(request) = handler {
+            let consumed = source.consumed();
+            let response = match strategy.respond(&mut source, request.request()) {
+                Ok(Some(response)) => response,
+                Ok(None) => return,
+                Err(msg) => panic!("property failed: {msg}"),
+            };
+            if source.is_exhausted() {
+                return;
+            }
+            idle = if source.consumed() == consumed {
+                idle + 1
+            } else {
+                0
+            };
+            assert!(
+                idle <= self.idle,
+                "infinite request loop: {idle} Responses without consuming the input"
+            );
+            handler = io.handle(request, &response);
+        }
+    }
+}
@@ -11,7 +11,32 @@
 //! This crate could be used also for non network protocol cases, everywhere there is a need for
 //! creating a state machine.
 //!
-//! This is `no_std` crate and it doesn't allocate on the heap.
+//! This is `no_std` crate and it doesn't allocate on the heap. The [net] module defines the
+//! standard stream and datagram vocabularies of messages for network protocols and the [codec]
+//! module reassembles frames of byte streams in Sans tasks. The optional
+//! `std` feature adds the panic isolation of Sans tasks and the tools for debugging sessions:
+//!
+//! - `transcript` - recording of the Request/Response exchanges,
+//! - `replay` - deterministic replay of recorded transcripts against a Sans task,
+//! - `mock` - scripted mock of the Io part for unit-testing protocols,
+//! - `loopback` - in-memory connector for running a client Sans task against a server one,
+//! - `sim` - deterministic network simulator for the [net] vocabularies,
+//! - `explore` - bounded exhaustive exploration of Sans tasks with invariants,
+//! - `prop` - property-based testing of Sans tasks with shrinking,
+//! - `fuzz` - fuzz target helper driving Sans tasks with raw fuzzer input,
+//! - `diff` - differential testing of two Sans tasks in lockstep,
+//! - `diagram` - Mermaid and PlantUML sequence diagrams of recorded sessions,
+//! - `metrics` - per-session metrics of exchanges and time spent in the Sans and Io parts,
+//! - `budget` - step budget and livelock detection of sessions.
+//!
+//! The optional `coverage` feature, which requires `std`, captures the await point of every
+//! Request and accumulates the report of hit await points in the `coverage` module.
+//!
+//! The optional `log` and `defmt` features emit compact records of every step of the session
+//! and of detected misuses, also in `no_std` deployments.
+//!
+//! The optional `tracing` feature opens the `tracing` span for every step of the session and
+//! records Requests and Responses formatted by the hook from the `trace` module.
 //!
 //! ## Usage
 //!
@@ -19,41 +44,54 @@
 //!
 //! ```
 //! # use asansio::Sans;
+//! # use asansio::Session;
 //! # use std::pin::pin;
 //! #
 //! struct Request<'a>(&'a [u8]);
 //! struct Response<'a>(&'a [u8]);
 //!
-//! async fn sans_task<'a>(sans: Sans<Request<'a>, Response<'a>>) {
+//! struct Proto;
+//!
+//! asansio::message! {
+//!     Proto {
+//!         type Request<'r> = Request<'r>;
+//!         type Response<'r> = Response<'r>;
+//!     }
+//! }
+//!
+//! async fn sans_task(mut sans: Sans<'_, Proto>) {
 //!     let mut request_buf = [1u8; 10];
 //!     let response = sans.start(&Request(&request_buf)).await;
-//!     assert_eq!(response.response().unwrap().0, [2; 20]);
+//!     assert_eq!(response.response().0, [2; 20]);
 //!
 //!     request_buf.fill(3);
 //!     let response = sans.handle(response, &Request(&request_buf)).await;
-//!     assert_eq!(response.response().unwrap().0, [4; 20]);
+//!     assert_eq!(response.response().0, [4; 20]);
 //! }
 //!
-//! let (sans, io) = asansio::new();
+//! let mut session = Session::<Proto>::new();
+//! let (sans, io) = session.split();
 //!
 //! let task = pin!(sans_task(sans));
 //!
 //! let request = io.start(task).unwrap();
-//! assert_eq!(request.request().unwrap().0, [1; 10]);
+//! assert_eq!(request.request().0, [1; 10]);
 //!
-//! let mut response_buf = [2; 20];
+//! let response_buf = [2; 20];
 //! let request = io.handle(request, &Response(&response_buf)).unwrap();
-//! assert_eq!(request.request().unwrap().0, [3; 10]);
+//! assert_eq!(request.request().0, [3; 10]);
 //!
-//! response_buf.fill(4);
+//! let response_buf = [4; 20];
 //! assert!(io.handle(request, &Response(&response_buf)).is_none());
 //! ```
 //!
 //! This crate divides a problem into two parts. The first `Sans` takes care of the state machine
 //! independent of the I/O and the second `Io` is responsible with I/O communication.  There are
-//! two types to manage them: the [Io] and the [Sans], which are constructed by the [new] function.
-//! These two parts communicate using `Request` and `Respond` types, which are defined by the user
-//! (for real scenarios they could be `enums`).
+//! two types to manage them: the [Io] and the [Sans], which are constructed from the [Session] by
+//! the [Session::split] function.
+//! These two parts communicate using `Request` and `Response` types, which are defined by the user
+//! (for real scenarios they could be `enums`) and grouped together by the [Message] trait, which
+//! is implemented by the [message!] macro.
 //!
 //! `Sans` starts communicating with `Io` using [Sans::start] and providing the initial `Request`;
 //! it returns the [SansResponse] from the `Io`.  `Io` starts sans task by using [Io::start] which
@@ -62,212 +100,1046 @@
 //!
 //! See also more [examples](https://github.com/ewienik/asansio/tree/master/examples).
 //!
+//! ## Lifetimes
+//!
+//! The [Message] trait defines `Request` and `Response` as generic associated types with their own
+//! lifetime. Every exchange borrows its own `Request` and `Response` - they only need to live
+//! until the next [Sans::handle] or [Io::handle] call, not for the whole session. This means both
+//! parts could use short-lived buffers for each message.
+//!
+//! The borrow checker enforces these limits: a reference returned by [IoRequest::request] or
+//! [SansResponse::response] (or any payload borrowed from it) cannot be held across the next
+//! [Io::handle], [Sans::handle] or [Sans::start], a `Request` cannot borrow the `Response` it
+//! answers, a `Request` buffer cannot be modified while the Sans task waits for the `Response`
+//! and an [IoRequest] cannot outlive the pinned Sans task. The `tests/compile_fail` suite checks
+//! that such misuses are rejected.
+//!
+//! ## Threads
+//!
+//! The [Session] and the [Sans] are `Send` and `Sync`. The [Io] is `Send`, but it is not `Sync`,
+//! as the steps of the session are not synchronized between threads. Other types follow their
+//! messages - [SansHandle] and [IoRequest] hold a shared reference to the `Request`, so they are
+//! `Send` when the `Request` is `Sync`; [SansResponse] holds a shared reference to the `Response`,
+//! so with the `std` feature it is `Send` when the `Response` is `Sync`. It is not `Sync` and it
+//! panics when it is used by other thread than the one polling the Sans task, so the `Response` is
+//! not borrowed while the [Io] finishes the poll.
+//!
+//! With the `std` feature a Sans task with such messages is `Send`, so the whole session (the
+//! [Session], the pinned Sans task and the [IoRequest]) could be moved between threads in the
+//! `Send` future, e.g. spawned on the multithreaded `tokio` runtime.
+//!
 //! ## Safety
 //!
 //! The crate uses `unsafe` parts for preparing a proper `async/await` infrastructure. Safety is
 //! guaranteed by consuming the latest [IoRequest] and [SansResponse] - these handlers store
 //! `Request` and `Response` objects and their lifetime is limited to the adjecent calls.
+//!
+//! The rules checked by the crate are:
+//!
+//! - The [Session] outlives the [Sans] and the [Io] and there is only one pair of them for the
+//!   session - checked by the borrow checker.
+//! - The `Request` is valid until the next [Io::handle] - the [IoRequest] borrows the Sans task
+//!   mutably, so the [SansHandle] with the `Request` is not polled nor dropped in the meantime.
+//! - The [SansHandle] is not leaked while the Sans task is pending - the [Io] polls the Sans
+//!   task again without the `Response` and panics if the [SansHandle] does not publish the same
+//!   `Request` again.
+//! - The `Response` is valid during the single poll of the Sans task - the [Session] counts
+//!   steps and [SansResponse::response] panics when it is used after the next step.
+//! - The [SansResponse] is not held during the next exchange - the [SansHandle] panics when it
+//!   sends the `Request` while the previous [SansResponse] is not dropped.
+//! - The [SansHandle] is polled only by the [Io] of its own [Session] - the waker passed by the
+//!   [Io] identifies the session and its clones are not accepted, so a Sans task polled by other
+//!   executors or by a different session panics.
+//! - The Sans task waits only for the [SansHandle] - the [Io] panics if the task is pending
+//!   without a `Request`.
+//! - The [SansResponse] is not used concurrently with the [Io] finishing the poll - it is `Send`
+//!   only with the `std` feature, which checks that it is used by the thread polling the Sans
+//!   task.
+//!
+//! The `tests/soundness.rs` suite exercises these rules and it is designed to be run also with
+//! Miri: `cargo +nightly miri test --features std --test soundness`.
+//!
+//! ## Panics
+//!
+//! A panic in the Sans task unwinds through [Io::start] or [Io::handle] into the Io part. With
+//! the `std` feature `Io::try_start` and `Io::try_handle` catch the panic instead, mark the
+//! session as poisoned and return `Error::Panicked`. The poisoned session is terminal - next
+//! calls return `Error::Poisoned` and [Io::start] or [Io::handle] panic, so a malformed peer
+//! could be disconnected without taking down the whole server.
+//!
+//! ## States
+//!
+//! States of the Sans task are generated by the compiler and they have no names. The Sans task
+//! could label its current phase by [Sans::state], e.g. `sans.state("handshake")`, and the Io
+//! part retrieves the label of the pending session by [IoRequest::state], so a stuck session
+//! could be reported with its phase. Changes of the label are reported to the [Tracer] set by
+//! [Session::with_tracer].
 
 #![no_std]
 
+#[cfg(feature = "std")]
+extern crate std;
+
+#[macro_use]
+mod diag;
+
+#[cfg(feature = "std")]
+pub mod budget;
+pub mod codec;
+#[cfg(feature = "coverage")]
+pub mod coverage;
+#[cfg(feature = "std")]
+pub mod diagram;
+#[cfg(feature = "std")]
+pub mod diff;
+#[cfg(feature = "std")]
+pub mod explore;
+#[cfg(feature = "std")]
+pub mod fuzz;
+#[cfg(feature = "std")]
+pub mod loopback;
+#[cfg(feature = "std")]
+pub mod metrics;
+#[cfg(feature = "std")]
+pub mod mock;
+pub mod net;
+#[cfg(feature = "std")]
+pub mod prop;
+#[cfg(feature = "std")]
+pub mod replay;
+#[cfg(feature = "std")]
+pub mod sim;
+#[cfg(feature = "tracing")]
+pub mod trace;
+#[cfg(feature = "std")]
+pub mod transcript;
+
+#[cfg(feature = "std")]
+use core::any::Any;
+#[cfg(feature = "std")]
+use core::fmt;
 use core::marker::PhantomData;
+#[cfg(feature = "coverage")]
+use core::panic::Location;
 use core::pin::Pin;
 use core::ptr;
+use core::slice;
+use core::str;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicPtr;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
 use core::task::Context;
 use core::task::Poll;
 use core::task::RawWaker;
 use core::task::RawWakerVTable;
 use core::task::Waker;
+#[cfg(feature = "std")]
+use std::boxed::Box;
+#[cfg(feature = "std")]
+use std::panic::AssertUnwindSafe;
+
+/// The family of messages exchanged between Sans and Io.
+///
+/// `Request` is sent from the Sans part to the Io part, `Response` is sent back from the Io part
+/// to the Sans part. Both are borrowed only for a single exchange, so they are generic over
+/// their own lifetime.
+///
+/// The `shorten_*` functions prove to the compiler that the messages are covariant over their
+/// lifetime. They should be implemented as an identity function, which compiles only for
+/// covariant types. The [message!] macro implements the trait with such functions.
+pub trait Message {
+    /// The message from the Sans part to the Io part.
+    type Request<'r>;
+
+    /// The message from the Io part to the Sans part.
+    type Response<'r>;
+
+    /// Shortens the lifetime of the Request. Implement it as `request`.
+    fn shorten_request<'a: 'b, 'b>(request: &'b Self::Request<'a>) -> &'b Self::Request<'b>;
 
-/// Store transmission message from(Tx) or to(Rx) Sans
-#[derive(Default)]
-enum Channel<Request, Response> {
-    Tx(*const Request),
-    Rx(*const Response),
-    #[default]
-    None,
+    /// Shortens the lifetime of the Response. Implement it as `response`.
+    fn shorten_response<'a: 'b, 'b>(response: &'b Self::Response<'a>) -> &'b Self::Response<'b>;
 }
 
-impl<Request, Response> Channel<Request, Response> {
-    fn tx(request: &Request) -> Self {
-        Self::Tx(request as *const Request)
+/// Implements [Message] for the family of messages.
+///
+/// The `shorten_*` functions are implemented as the identity function, so the implementation
+/// compiles only for messages covariant over their lifetime.
+///
+/// ```
+/// struct Request<'a>(&'a [u8]);
+/// struct Response<'a>(&'a [u8]);
+///
+/// struct Proto;
+///
+/// asansio::message! {
+///     Proto {
+///         type Request<'r> = Request<'r>;
+///         type Response<'r> = Response<'r>;
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! message {
+    (
+        $message:ty {
+            type Request<$request_lifetime:lifetime> = $request:ty;
+            type Response<$response_lifetime:lifetime> = $response:ty;
+        }
+    ) => {
+        impl $crate::Message for $message {
+            type Request<$request_lifetime> = $request;
+            type Response<$response_lifetime> = $response;
+
+            fn shorten_request<'a: 'b, 'b>(
+                request: &'b Self::Request<'a>,
+            ) -> &'b Self::Request<'b> {
+                request
+            }
+
+            fn shorten_response<'a: 'b, 'b>(
+                response: &'b Self::Response<'a>,
+            ) -> &'b Self::Response<'b> {
+                response
+            }
+        }
+    };
+}
+
+/// The observer of named states of Sans tasks.
+///
+/// The tracer is set for the session by [Session::with_tracer] and it is called by [Sans::state]
+/// from the Sans task, so it is shared between threads like the [Sans].
+pub trait Tracer: Sync {
+    /// The Sans task entered the state `to` from the state `from` during the poll number `step`.
+    fn transition(&self, step: usize, from: Option<&'static str>, to: &'static str);
+}
+
+/// The state shared by the Sans and the Io parts of a single session.
+///
+/// The session must outlive both parts, which are created by [Session::split]. It stores the
+/// Request and the Response in transfer and the step counter, which is increased after every
+/// poll of the Sans task. Every [SansResponse] remembers its step, so using it after the next
+/// [Io::handle] call is detected.
+pub struct Session<M> {
+    step: AtomicUsize,
+    request: AtomicPtr<()>,
+    response: AtomicPtr<()>,
+    outstanding: AtomicBool,
+    #[cfg(feature = "std")]
+    poller: AtomicUsize,
+    #[cfg(feature = "std")]
+    poisoned: AtomicBool,
+    #[cfg(feature = "coverage")]
+    location: AtomicPtr<Location<'static>>,
+    state: AtomicPtr<u8>,
+    state_len: AtomicUsize,
+    state_lock: AtomicBool,
+    tracer: Option<&'static dyn Tracer>,
+    _message: PhantomData<fn() -> M>,
+}
+
+impl<M: Message> Session<M> {
+    /// Creates a new session.
+    pub const fn new() -> Self {
+        Self {
+            step: AtomicUsize::new(0),
+            request: AtomicPtr::new(ptr::null_mut()),
+            response: AtomicPtr::new(ptr::null_mut()),
+            outstanding: AtomicBool::new(false),
+            #[cfg(feature = "std")]
+            poller: AtomicUsize::new(0),
+            #[cfg(feature = "std")]
+            poisoned: AtomicBool::new(false),
+            #[cfg(feature = "coverage")]
+            location: AtomicPtr::new(ptr::null_mut()),
+            state: AtomicPtr::new(ptr::null_mut()),
+            state_len: AtomicUsize::new(0),
+            state_lock: AtomicBool::new(false),
+            tracer: None,
+            _message: PhantomData,
+        }
+    }
+
+    /// Sets the tracer of state transitions of the Sans task.
+    pub const fn with_tracer(mut self, tracer: &'static dyn Tracer) -> Self {
+        self.tracer = Some(tracer);
+        self
+    }
+
+    /// Creates a two parts: Sans and Io for the session. The session is borrowed mutably, so
+    /// there is only one Sans and one Io for it at the time.
+    pub fn split(&mut self) -> (Sans<'_, M>, Io<'_, M>) {
+        (
+            Sans { session: self },
+            Io {
+                session: self,
+                #[cfg(feature = "tracing")]
+                hook: None,
+                _not_sync: PhantomData,
+            },
+        )
+    }
+
+    fn step(&self) -> usize {
+        self.step.load(Ordering::Relaxed)
+    }
+
+    fn request(&self) -> *const M::Request<'static> {
+        self.request.load(Ordering::Relaxed).cast_const().cast()
+    }
+
+    fn set_request(&self, request: *const M::Request<'static>) {
+        self.request
+            .store(request.cast_mut().cast(), Ordering::Relaxed);
     }
 
-    fn rx(response: &Response) -> Self {
-        Self::Rx(response as *const Response)
+    fn response(&self) -> *const M::Response<'static> {
+        self.response.load(Ordering::Relaxed).cast_const().cast()
+    }
+
+    fn set_response(&self, response: *const M::Response<'static>) {
+        self.response
+            .store(response.cast_mut().cast(), Ordering::Relaxed);
+    }
+
+    fn is_outstanding(&self) -> bool {
+        self.outstanding.load(Ordering::Relaxed)
+    }
+
+    fn set_outstanding(&self, outstanding: bool) {
+        self.outstanding.store(outstanding, Ordering::Relaxed);
+    }
+
+    /// Stores the thread polling the Sans task, zero between polls
+    #[cfg(feature = "std")]
+    fn set_poller(&self, poller: usize) {
+        self.poller.store(poller, Ordering::Relaxed);
+    }
+
+    #[cfg(feature = "std")]
+    fn is_poller(&self) -> bool {
+        self.poller.load(Ordering::Relaxed) == current_thread()
+    }
+
+    #[cfg(feature = "std")]
+    fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Relaxed)
+    }
+
+    #[cfg(feature = "coverage")]
+    fn location(&self) -> Option<&'static Location<'static>> {
+        // It is safe as only static locations are stored
+        unsafe { self.location.load(Ordering::Relaxed).as_ref() }
+    }
+
+    #[cfg(feature = "coverage")]
+    fn set_location(&self, location: &'static Location<'static>) {
+        self.location
+            .store(ptr::from_ref(location).cast_mut(), Ordering::Relaxed);
+    }
+
+    fn state(&self) -> Option<&'static str> {
+        let _lock = StateLock::new(&self.state_lock);
+        self.load_state()
+    }
+
+    /// Stores the state and returns the previous one
+    fn set_state(&self, state: &'static str) -> Option<&'static str> {
+        let _lock = StateLock::new(&self.state_lock);
+        let previous = self.load_state();
+        self.state
+            .store(state.as_ptr().cast_mut(), Ordering::Relaxed);
+        self.state_len.store(state.len(), Ordering::Relaxed);
+        previous
+    }
+
+    fn load_state(&self) -> Option<&'static str> {
+        let state = self.state.load(Ordering::Relaxed);
+        // It is safe as the pointer and the length are stored together under the lock and only
+        // static strings are stored
+        (!state.is_null()).then(|| unsafe {
+            str::from_utf8_unchecked(slice::from_raw_parts(
+                state,
+                self.state_len.load(Ordering::Relaxed),
+            ))
+        })
+    }
+}
+
+impl<M: Message> Default for Session<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Guards the state of the session, as the Sans is Sync and the state is not a single atomic
+struct StateLock<'a>(&'a AtomicBool);
+
+impl<'a> StateLock<'a> {
+    fn new(lock: &'a AtomicBool) -> Self {
+        while lock
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+        Self(lock)
+    }
+}
+
+impl Drop for StateLock<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+/// Finishes a single poll of the Sans task, also when the task panics
+struct PollGuard<'a, M: Message>(&'a Session<M>);
+
+impl<M: Message> Drop for PollGuard<'_, M> {
+    fn drop(&mut self) {
+        // The Io is not Sync, so the step is never increased concurrently
+        self.0
+            .step
+            .store(self.0.step().wrapping_add(1), Ordering::Relaxed);
+        self.0.set_response(ptr::null());
+        #[cfg(feature = "std")]
+        self.0.set_poller(0);
     }
 }
 
 /// The Future helper for handling data between Io and Sans
-pub struct SansHandle<'a, Request, Response> {
-    request: Option<&'a Request>,
-    _response: PhantomData<Response>,
+///
+/// It publishes the `Request` on every poll while it waits for the `Response`, so the [Io] accepts
+/// the `Request` only if the Sans task still awaits it.
+pub struct SansHandle<'s, 'a, M: Message> {
+    session: &'s Session<M>,
+    request: &'a M::Request<'a>,
+    sent: bool,
+    #[cfg(feature = "coverage")]
+    location: &'static Location<'static>,
 }
 
-impl<'a, Request: Unpin, Response: Unpin> Future for SansHandle<'a, Request, Response> {
-    type Output = SansResponse<Response>;
+impl<'s, 'a, M: Message> SansHandle<'s, 'a, M> {
+    fn request_ptr(&self) -> *const M::Request<'static> {
+        ptr::from_ref(self.request).cast()
+    }
 
-    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    /// Reports the Request at the location of the helper caller instead of the helper itself
+    #[cfg(feature = "coverage")]
+    pub(crate) fn at(mut self, location: &'static Location<'static>) -> Self {
+        self.location = location;
+        self
+    }
+}
+
+impl<'s, 'a, M: Message> Future for SansHandle<'s, 'a, M> {
+    type Output = SansResponse<'s, M>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
         let waker = cx.waker();
-        assert!(ptr::eq(waker.vtable(), &WAKER_VTABLE));
-
-        // It is safe as waker is build befor each future handle call and the Channel
-        // is valid between await points.
-        let ch = unsafe { &mut *(waker.data() as *mut Channel<Request, Response>) };
-
-        if let Some(request) = self.request.take() {
-            *ch = Channel::tx(request);
-            Poll::Pending
-        } else {
-            match ch {
-                Channel::Rx(response) => Poll::Ready(SansResponse {
-                    response: *response,
-                }),
-                Channel::Tx(_) => Poll::Pending,
-                Channel::None => unreachable!(),
+        check!(
+            ptr::eq(waker.vtable(), &WAKER_VTABLE) && !waker.data().is_null(),
+            "Sans task polled outside of the Io"
+        );
+        check!(
+            ptr::eq(waker.data(), ptr::from_ref(this.session).cast()),
+            "Sans task polled by the Io of a different session"
+        );
+        let session = this.session;
+
+        // The waker is not a clone, so it is used during a poll of the Io and the Response in the
+        // session is valid.
+        if this.sent && !session.response().is_null() {
+            let response = session.response();
+            session.set_response(ptr::null());
+            if session.request() == this.request_ptr() {
+                session.set_request(ptr::null());
             }
+            this.sent = false;
+            session.set_outstanding(true);
+            return Poll::Ready(SansResponse {
+                session,
+                step: session.step(),
+                response,
+            });
+        }
+
+        check!(
+            this.sent || !session.is_outstanding(),
+            "Request sent while the previous SansResponse is held"
+        );
+        session.set_request(this.request_ptr());
+        #[cfg(feature = "coverage")]
+        session.set_location(this.location);
+        this.sent = true;
+        Poll::Pending
+    }
+}
+
+impl<M: Message> Drop for SansHandle<'_, '_, M> {
+    fn drop(&mut self) {
+        // The Request could not be used by the Io after the SansHandle is dropped
+        if self.sent && self.session.request() == self.request_ptr() {
+            self.session.set_request(ptr::null());
         }
     }
 }
 
 /// Manages the Sans part
-pub struct Sans<Request, Response> {
-    _request: PhantomData<Request>,
-    _response: PhantomData<Response>,
+pub struct Sans<'s, M> {
+    session: &'s Session<M>,
 }
 
 /// The holder of the Response from the Io to Sans
-pub struct SansResponse<Response> {
-    response: *const Response,
+pub struct SansResponse<'s, M: Message> {
+    session: &'s Session<M>,
+    step: usize,
+    response: *const M::Response<'static>,
 }
 
-// It is safe as its lifetime is between two awaits in the Sans part
-unsafe impl<Response> Send for SansResponse<Response> {}
+// It is safe as the SansResponse is a shared reference to the Response and the session, and the
+// Response is retrieved only by the thread polling the Sans task, so not concurrently with the Io
+// finishing the poll
+#[cfg(feature = "std")]
+unsafe impl<M: Message> Send for SansResponse<'_, M> where for<'r> M::Response<'r>: Sync {}
 
-impl<Request, Response> Sans<Request, Response> {
+impl<'s, M: Message> Sans<'s, M> {
     /// Initial request from the Sans part.
-    pub fn start<'a>(&self, request: &'a Request) -> SansHandle<'a, Request, Response> {
-        SansHandle {
-            request: Some(request),
-            _response: PhantomData,
-        }
+    ///
+    /// The Sans is borrowed mutably, so the [SansResponse] returned by [Sans::handle] could not
+    /// be held by the Sans task during the next start.
+    ///
+    /// # Panics
+    ///
+    /// The returned [SansHandle] panics if it is polled while the [SansResponse] of the previous
+    /// Request is held.
+    #[cfg_attr(feature = "coverage", track_caller)]
+    pub fn start<'a>(&mut self, request: &'a M::Request<'a>) -> SansHandle<'s, 'a, M> {
+        self.send(request)
     }
 
     /// Next requests from the Sans part. It must receive SansResponse from the previous await call
     /// as the Response is not longer valid.
-    pub fn handle<'a>(
-        &self,
-        _response: SansResponse<Response>,
-        request: &'a Request,
-    ) -> SansHandle<'a, Request, Response> {
+    #[cfg_attr(feature = "coverage", track_caller)]
+    pub fn handle<'b, 'a>(
+        &'b self,
+        response: SansResponse<'_, M>,
+        request: &'a M::Request<'a>,
+    ) -> SansHandle<'b, 'a, M> {
+        drop(response);
+        self.send(request)
+    }
+
+    /// Creates the SansHandle not bound to the borrow of the Sans
+    #[cfg_attr(feature = "coverage", track_caller)]
+    pub(crate) fn send<'a>(&self, request: &'a M::Request<'a>) -> SansHandle<'s, 'a, M> {
         SansHandle {
-            request: Some(request),
-            _response: PhantomData,
+            session: self.session,
+            request,
+            sent: false,
+            #[cfg(feature = "coverage")]
+            location: Location::caller(),
+        }
+    }
+
+    /// Labels the current phase of the Sans task, e.g. `sans.state("auth")`. The label is
+    /// returned by [IoRequest::state] and a change of the label is reported to the [Tracer] of
+    /// the session.
+    pub fn state(&self, state: &'static str) {
+        let session = self.session;
+        let from = session.set_state(state);
+        if from == Some(state) {
+            return;
+        }
+        if let Some(tracer) = session.tracer {
+            tracer.transition(session.step(), from, state);
         }
     }
 }
 
-impl<Response> SansResponse<Response> {
+impl<M: Message> SansResponse<'_, M> {
     /// Retrieve a reference to the Response from the Io part.
-    pub fn response(&self) -> Option<&Response> {
-        if self.response.is_null() {
-            return None;
-        }
+    ///
+    /// # Panics
+    ///
+    /// Panics if the SansResponse is used after the next step of the session - the Response is
+    /// not longer valid then - or by other thread than the one polling the Sans task.
+    pub fn response(&self) -> &M::Response<'_> {
+        check!(
+            self.step == self.session.step(),
+            "SansResponse used after the next step of the session"
+        );
+        // The Io of other thread could finish the poll while the Response is borrowed
+        #[cfg(feature = "std")]
+        check!(
+            self.session.is_poller(),
+            "SansResponse used by other thread than the one polling the Sans task"
+        );
+
+        // It is safe as the step is the same, so the poll of the Io::handle with the Response is
+        // not finished yet.
+        M::shorten_response(unsafe { &*self.response })
+    }
+}
 
-        // It is save as SansResponse is used only between two adjacent await points
-        Some(unsafe { &*self.response })
+impl<M: Message> Drop for SansResponse<'_, M> {
+    fn drop(&mut self) {
+        self.session.set_outstanding(false);
     }
 }
 
 /// Manages the Io part
-pub struct Io<Request, Response> {
-    _request: PhantomData<Request>,
-    _response: PhantomData<Response>,
+pub struct Io<'s, M> {
+    session: &'s Session<M>,
+    #[cfg(feature = "tracing")]
+    hook: Option<&'s dyn trace::TraceHook<M>>,
+    _not_sync: PhantomData<core::cell::Cell<()>>,
 }
 
 /// The holder of the Request from the Sans to Io
-pub struct IoRequest<'a, Request, Task> {
-    request: Option<&'a Request>,
+pub struct IoRequest<'a, M: Message, Task: ?Sized> {
+    session: &'a Session<M>,
+    request: *const M::Request<'static>,
+    #[cfg(feature = "coverage")]
+    location: Option<&'static Location<'static>>,
     task: Pin<&'a mut Task>,
 }
 
-impl<Request, Response> Io<Request, Response> {
+// It is safe as the IoRequest is a shared reference to the Request and a mutable reference to the
+// Task
+unsafe impl<M: Message, Task: Send + ?Sized> Send for IoRequest<'_, M, Task> where
+    for<'r> M::Request<'r>: Sync
+{
+}
+
+// It is safe as the IoRequest is a shared reference to the Request and a mutable reference to the
+// Task
+unsafe impl<M: Message, Task: Sync + ?Sized> Sync for IoRequest<'_, M, Task> where
+    for<'r> M::Request<'r>: Sync
+{
+}
+
+impl<'s, M: Message> Io<'s, M> {
+    /// Sets the hook formatting Requests and Responses recorded in `tracing` events.
+    #[cfg(feature = "tracing")]
+    pub fn with_hook(mut self, hook: &'s dyn trace::TraceHook<M>) -> Self {
+        self.hook = Some(hook);
+        self
+    }
+
     /// Starts the Sans part defined as a Future Task. Returns on the first async Request from Sans
     /// or when the Task finishes.
-    pub fn start<'a, Task>(&self, task: Pin<&'a mut Task>) -> Option<IoRequest<'a, Request, Task>>
+    pub fn start<'a, Task>(&self, task: Pin<&'a mut Task>) -> Option<IoRequest<'a, M, Task>>
     where
-        Task: Future<Output = ()>,
+        's: 'a,
+        Task: Future<Output = ()> + ?Sized,
     {
         let mut handler = IoRequest {
-            request: None,
+            session: self.session,
+            request: ptr::null(),
+            #[cfg(feature = "coverage")]
+            location: None,
             task,
         };
-        handler.run_async(Channel::<Request, Response>::None);
-        handler.request.map(|_| handler)
+        handler
+            .run_async(
+                ptr::null(),
+                #[cfg(feature = "tracing")]
+                self.hook,
+            )
+            .then_some(handler)
     }
 
     /// Next polling of the Future Task of the Sans part. It must receive IoRequest from the
     /// previous await call as the Response is not longer valid. Returns on the Request from Sans
     /// or when the Task finishes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the IoRequest was returned by the Io of a different session or the Sans task
+    /// panics.
     pub fn handle<'a, Task>(
         &self,
-        mut handler: IoRequest<'a, Request, Task>,
-        response: &Response,
-    ) -> Option<IoRequest<'a, Request, Task>>
+        mut handler: IoRequest<'a, M, Task>,
+        response: &M::Response<'_>,
+    ) -> Option<IoRequest<'a, M, Task>>
+    where
+        Task: Future<Output = ()> + ?Sized,
+    {
+        check!(
+            ptr::eq(handler.session, self.session),
+            "IoRequest handled by the Io of a different session"
+        );
+        handler
+            .run_async(
+                ptr::from_ref(response).cast(),
+                #[cfg(feature = "tracing")]
+                self.hook,
+            )
+            .then_some(handler)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'s, M: Message> Io<'s, M> {
+    /// The same as [Io::start], but a panic of the Sans task is returned as [Error::Panicked] and
+    /// the session is poisoned.
+    pub fn try_start<'a, Task>(
+        &self,
+        task: Pin<&'a mut Task>,
+    ) -> Result<Option<IoRequest<'a, M, Task>>, Error>
+    where
+        's: 'a,
+        Task: Future<Output = ()> + ?Sized,
+    {
+        let mut handler = IoRequest {
+            session: self.session,
+            request: ptr::null(),
+            #[cfg(feature = "coverage")]
+            location: None,
+            task,
+        };
+        Ok(handler
+            .try_run_async(
+                ptr::null(),
+                #[cfg(feature = "tracing")]
+                self.hook,
+            )?
+            .then_some(handler))
+    }
+
+    /// The same as [Io::handle], but a panic of the Sans task is returned as [Error::Panicked]
+    /// and the session is poisoned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the IoRequest was returned by the Io of a different session.
+    pub fn try_handle<'a, Task>(
+        &self,
+        mut handler: IoRequest<'a, M, Task>,
+        response: &M::Response<'_>,
+    ) -> Result<Option<IoRequest<'a, M, Task>>, Error>
+    where
+        Task: Future<Output = ()> + ?Sized,
+    {
+        check!(
+            ptr::eq(handler.session, self.session),
+            "IoRequest handled by the Io of a different session"
+        );
+        Ok(handler
+            .try_run_async(
+                ptr::from_ref(response).cast(),
+                #[cfg(feature = "tracing")]
+                self.hook,
+            )?
+            .then_some(handler))
+    }
+
+    /// Returns true if the Sans task of the session panicked.
+    pub fn is_poisoned(&self) -> bool {
+        self.session.is_poisoned()
+    }
+}
+
+/// The driver of the Sans task of the session.
+///
+/// It is implemented by the [Io] and by the wrappers of another driver added by the `std`
+/// feature, e.g. `metrics::Meter`, `budget::Budget` or `transcript::Recorder`, so the wrappers
+/// could be stacked on top of each other.
+pub trait Drive {
+    /// The family of messages exchanged with the Sans task.
+    type Message: Message;
+
+    /// The terminal error of the session.
+    #[cfg(feature = "std")]
+    type Error;
+
+    /// The same as [Io::start].
+    fn start<'a, Task>(
+        &mut self,
+        task: Pin<&'a mut Task>,
+    ) -> Option<IoRequest<'a, Self::Message, Task>>
+    where
+        Self: 'a,
+        Task: Future<Output = ()> + ?Sized;
+
+    /// The same as [Io::handle].
+    fn handle<'a, Task>(
+        &mut self,
+        handler: IoRequest<'a, Self::Message, Task>,
+        response: &<Self::Message as Message>::Response<'_>,
+    ) -> Option<IoRequest<'a, Self::Message, Task>>
+    where
+        Task: Future<Output = ()> + ?Sized;
+
+    /// The same as [Io::try_start], but the error is the terminal error of the driver.
+    #[cfg(feature = "std")]
+    fn try_start<'a, Task>(
+        &mut self,
+        task: Pin<&'a mut Task>,
+    ) -> Result<Option<IoRequest<'a, Self::Message, Task>>, Self::Error>
+    where
+        Self: 'a,
+        Task: Future<Output = ()> + ?Sized;
+
+    /// The same as [Io::try_handle], but the error is the terminal error of the driver.
+    #[cfg(feature = "std")]
+    fn try_handle<'a, Task>(
+        &mut self,
+        handler: IoRequest<'a, Self::Message, Task>,
+        response: &<Self::Message as Message>::Response<'_>,
+    ) -> Result<Option<IoRequest<'a, Self::Message, Task>>, Self::Error>
+    where
+        Task: Future<Output = ()> + ?Sized;
+
+    /// Retrieve the error of the Sans task, if it caused the terminal error of the driver.
+    #[cfg(feature = "std")]
+    fn task_error(err: &Self::Error) -> Option<&Error>;
+}
+
+impl<M: Message> Drive for Io<'_, M> {
+    type Message = M;
+
+    #[cfg(feature = "std")]
+    type Error = Error;
+
+    fn start<'a, Task>(&mut self, task: Pin<&'a mut Task>) -> Option<IoRequest<'a, M, Task>>
+    where
+        Self: 'a,
+        Task: Future<Output = ()> + ?Sized,
+    {
+        Io::start(self, task)
+    }
+
+    fn handle<'a, Task>(
+        &mut self,
+        handler: IoRequest<'a, M, Task>,
+        response: &M::Response<'_>,
+    ) -> Option<IoRequest<'a, M, Task>>
+    where
+        Task: Future<Output = ()> + ?Sized,
+    {
+        Io::handle(self, handler, response)
+    }
+
+    #[cfg(feature = "std")]
+    fn try_start<'a, Task>(
+        &mut self,
+        task: Pin<&'a mut Task>,
+    ) -> Result<Option<IoRequest<'a, M, Task>>, Error>
     where
-        Task: Future<Output = ()>,
+        Self: 'a,
+        Task: Future<Output = ()> + ?Sized,
     {
-        handler.run_async(Channel::rx(response));
-        handler.request.map(move |_| handler)
+        Io::try_start(self, task)
+    }
+
+    #[cfg(feature = "std")]
+    fn try_handle<'a, Task>(
+        &mut self,
+        handler: IoRequest<'a, M, Task>,
+        response: &M::Response<'_>,
+    ) -> Result<Option<IoRequest<'a, M, Task>>, Error>
+    where
+        Task: Future<Output = ()> + ?Sized,
+    {
+        Io::try_handle(self, handler, response)
+    }
+
+    #[cfg(feature = "std")]
+    fn task_error(err: &Error) -> Option<&Error> {
+        Some(err)
+    }
+}
+
+/// The terminal error of the session returned by [Io::try_start] and [Io::try_handle].
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum Error {
+    /// The Sans task panicked with the payload.
+    Panicked(Box<dyn Any + Send + 'static>),
+    /// The Sans task panicked before, the session is not usable anymore.
+    Poisoned,
+}
+
+#[cfg(feature = "std")]
+impl Error {
+    /// Retrieve the message of the panic, if the payload is a string.
+    pub fn message(&self) -> Option<&str> {
+        let Self::Panicked(payload) = self else {
+            return None;
+        };
+        payload.downcast_ref::<&str>().copied().or_else(|| {
+            payload
+                .downcast_ref::<std::string::String>()
+                .map(|msg| msg.as_str())
+        })
     }
 }
 
-impl<'a, Request, Task> IoRequest<'a, Request, Task>
+#[cfg(feature = "std")]
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Panicked(_) => match self.message() {
+                Some(msg) => write!(f, "Sans task panicked: {msg}"),
+                None => write!(f, "Sans task panicked"),
+            },
+            Self::Poisoned => write!(f, "Sans task panicked before, the session is poisoned"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+impl<'a, M: Message, Task> IoRequest<'a, M, Task>
 where
-    Task: Future<Output = ()>,
+    Task: Future<Output = ()> + ?Sized,
 {
     /// Retrieve a reference to the Request from the Sans part.
-    pub fn request(&self) -> Option<&Request> {
-        self.request
+    pub fn request(&self) -> &M::Request<'_> {
+        // It is safe as the SansHandle with the Request is pinned inside the Task, which is
+        // borrowed by the IoRequest, so it is not polled, dropped nor leaked until the next
+        // Io::handle.
+        M::shorten_request(unsafe { &*self.request })
     }
 
-    fn run_async<Response>(&mut self, ch: Channel<Request, Response>) {
-        // It is safe as now there is no valid Request waiting (IoRequest was consumed)
-        let waker = unsafe { Waker::new(&ch as *const _ as *const (), &WAKER_VTABLE) };
+    /// Retrieve the label of the current state of the Sans task set by [Sans::state].
+    pub fn state(&self) -> Option<&'static str> {
+        self.session.state()
+    }
+
+    /// Retrieve the location of the `Sans::start` or `Sans::handle` call which sent the Request.
+    #[cfg(feature = "coverage")]
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+            .expect("the location is set together with the Request")
+    }
 
+    /// Polls the Task with the Response. Returns true if the Task waits for the next Response.
+    fn run_async(
+        &mut self,
+        response: *const M::Response<'static>,
+        #[cfg(feature = "tracing")] hook: Option<&dyn trace::TraceHook<M>>,
+    ) -> bool {
+        let session = self.session;
+        #[cfg(feature = "std")]
+        check!(!session.is_poisoned(), "Sans task polled after it panicked");
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!(
+            "asansio",
+            session = ptr::from_ref(session).addr(),
+            step = session.step(),
+            state = session.state(),
+        )
+        .entered();
+        #[cfg(feature = "tracing")]
+        if let Some(hook) = hook.filter(|_| !response.is_null()) {
+            // It is safe as the Response is borrowed by the Io::handle call
+            let response = M::shorten_response(unsafe { &*response });
+            tracing::debug!(response = ?trace::Response(hook, response));
+        }
+        session.set_request(ptr::null());
+        session.set_response(response);
+        #[cfg(feature = "std")]
+        session.set_poller(current_thread());
+        let guard = PollGuard(session);
+
+        // It is safe as the waker data is the session, which outlives the IoRequest
+        let waker = unsafe { Waker::new(ptr::from_ref(session).cast(), &WAKER_VTABLE) };
         let mut cx = Context::from_waker(&waker);
-        self.request = match self.task.as_mut().poll(&mut cx) {
-            Poll::Ready(_) => None,
+        let mut poll = self.task.as_mut().poll(&mut cx);
+        self.request = session.request();
+        if poll.is_pending() && !self.request.is_null() {
+            // The SansHandle could be leaked after it sent the Request, so the Task is polled
+            // again without the Response and the Request is accepted only if its SansHandle
+            // still awaits it and publishes it again
+            session.set_request(ptr::null());
+            session.set_response(ptr::null());
+            poll = self.task.as_mut().poll(&mut cx);
+            check!(
+                poll.is_pending() && session.request() == self.request,
+                "SansHandle leaked while the Sans task is pending"
+            );
+        }
+        drop(guard);
+        step!(session.step(), poll.is_ready());
+        #[cfg(feature = "tracing")]
+        span.record("state", session.state());
+        match poll {
+            Poll::Ready(()) => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("finished");
+                false
+            }
             Poll::Pending => {
-                let Channel::Tx(request) = ch else {
-                    unreachable!();
-                };
-
-                // It is safe as this will be the only one IoRequest and it will be consumed by the
-                // next handle call
-                Some(unsafe { &*request })
+                check!(
+                    !self.request.is_null(),
+                    "Sans task pending outside of Sans::start or Sans::handle"
+                );
+                #[cfg(feature = "coverage")]
+                {
+                    self.location = session.location();
+                    coverage::record(self.location());
+                }
+                #[cfg(feature = "tracing")]
+                if let Some(hook) = hook {
+                    let request = self.request();
+                    tracing::debug!(request = ?trace::Request(hook, request));
+                }
+                true
             }
         }
     }
+
+    /// Polls the Task with the Response and catches its panic, which poisons the session.
+    #[cfg(feature = "std")]
+    fn try_run_async(
+        &mut self,
+        response: *const M::Response<'static>,
+        #[cfg(feature = "tracing")] hook: Option<&dyn trace::TraceHook<M>>,
+    ) -> Result<bool, Error> {
+        if self.session.is_poisoned() {
+            return Err(Error::Poisoned);
+        }
+        // The Task is not polled after the panic, as the session is poisoned, so it is not
+        // observed in a broken state.
+        std::panic::catch_unwind(AssertUnwindSafe(|| {
+            self.run_async(
+                response,
+                #[cfg(feature = "tracing")]
+                hook,
+            )
+        }))
+        .map_err(|payload| {
+            panicked!();
+            self.session.poisoned.store(true, Ordering::Relaxed);
+            Error::Panicked(payload)
+        })
+    }
 }
 
-/// Creates a two parts: Sans and Io for the specified Request and Response.
-pub fn new<Request, Response>() -> (Sans<Request, Response>, Io<Request, Response>) {
-    (
-        Sans {
-            _request: PhantomData,
-            _response: PhantomData,
-        },
-        Io {
-            _request: PhantomData,
-            _response: PhantomData,
-        },
-    )
-}
-
-const WAKER_VTABLE: RawWakerVTable = RawWakerVTable::new(
-    |data| RawWaker::new(data, &WAKER_VTABLE),
-    |_| {},
-    |_| {},
-    |_| {},
-);
+/// The vtable of the waker built by the Io for a single poll. It is a static, so its address
+/// identifies wakers passed by the Io.
+static WAKER_VTABLE: RawWakerVTable = RawWakerVTable::new(clone_waker, noop, noop, noop);
+
+/// The vtable of the cloned wakers, which could outlive the poll and are not used by Sans.
+static CLONED_WAKER_VTABLE: RawWakerVTable = RawWakerVTable::new(clone_waker, noop, noop, noop);
+
+fn clone_waker(_: *const ()) -> RawWaker {
+    RawWaker::new(ptr::null(), &CLONED_WAKER_VTABLE)
+}
+
+fn noop(_: *const ()) {}
+
+/// Identifies the current thread by the address of its thread local
+#[cfg(feature = "std")]
+fn current_thread() -> usize {
+    std::thread_local!(static THREAD: u8 = const { 0 });
+    THREAD.with(|thread| ptr::from_ref(thread).addr())
+}
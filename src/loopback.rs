@@ -0,0 +1,269 @@
+//! In-memory loopback connector for running a client Sans task against a server Sans task.
+//!
+//! The [run] function drives both tasks in the same thread without any I/O. The sides are
+//! connected by the [Link] - a pair of byte pipes, so the data written by one side is read by
+//! the other one. Every side has its own [Translate] implementation, which turns Requests of the
+//! task into operations on the link and Responses for the task.
+//!
+//! The tasks are polled in turns, the client first, until none of them could progress. The
+//! conversation is deterministic, so it could be unit tested. Exchanges of the side are recorded
+//! by wrapping its translation into [Recorded].
+
+use crate::Io;
+use crate::IoRequest;
+use crate::Message;
+use crate::transcript::Event;
+use crate::transcript::Hook;
+use crate::transcript::Transcript;
+use core::marker::PhantomData;
+use core::pin::Pin;
+use std::collections::VecDeque;
+use std::vec::Vec;
+
+/// The result of the translation of the Request.
+pub enum Action<R> {
+    /// Responds to the Request.
+    Respond(R),
+    /// Waits for the data from the peer - the same Request is translated again in the next turn.
+    /// The translation should not write to the link before waiting.
+    Wait,
+    /// Stops the side, e.g. on the protocol error.
+    Stop,
+}
+
+/// Translates Requests of the Sans task into operations on the [Link].
+pub trait Translate<M: Message> {
+    /// Translates the Request. The Response could borrow the translation or the link.
+    fn translate<'a>(
+        &'a mut self,
+        request: &M::Request<'_>,
+        link: &'a mut Link<'_>,
+    ) -> Action<M::Response<'a>>;
+
+    /// Called when the Sans task finished. Does nothing by default.
+    fn finished(&mut self) {}
+}
+
+/// The [Translate] wrapper recording exchanges of the side into the [Transcript].
+///
+/// The Request is recorded together with its Response or when the translation stops the side,
+/// waiting is not recorded. The end of the Sans task is recorded as [Event::Finished].
+pub struct Recorded<M: Message, T, H: Hook<M>> {
+    translate: T,
+    hook: H,
+    transcript: Transcript<H::Request, H::Response>,
+    _message: PhantomData<fn() -> M>,
+}
+
+impl<M: Message, T: Translate<M>, H: Hook<M>> Recorded<M, T, H> {
+    /// Wraps the translation with the hook converting messages.
+    pub fn new(translate: T, hook: H) -> Self {
+        Self {
+            translate,
+            hook,
+            transcript: Transcript::new(),
+            _message: PhantomData,
+        }
+    }
+
+    /// Retrieve the wrapped translation.
+    pub fn inner(&self) -> &T {
+        &self.translate
+    }
+
+    /// Retrieve the transcript recorded so far.
+    pub fn transcript(&self) -> &Transcript<H::Request, H::Response> {
+        &self.transcript
+    }
+
+    /// Returns the wrapped translation and the recorded transcript.
+    pub fn into_parts(self) -> (T, Transcript<H::Request, H::Response>) {
+        (self.translate, self.transcript)
+    }
+}
+
+impl<M: Message, T: Translate<M>, H: Hook<M>> Translate<M> for Recorded<M, T, H> {
+    fn translate<'a>(
+        &'a mut self,
+        request: &M::Request<'_>,
+        link: &'a mut Link<'_>,
+    ) -> Action<M::Response<'a>> {
+        let action = self.translate.translate(request, link);
+        if !matches!(action, Action::Wait) {
+            let request = self.hook.request(request);
+            self.transcript.push(Event::Request(request));
+        }
+        if let Action::Respond(response) = &action {
+            let response = self.hook.response(response);
+            self.transcript.push(Event::Response(response));
+        }
+        action
+    }
+
+    fn finished(&mut self) {
+        self.translate.finished();
+        self.transcript.push(Event::Finished);
+    }
+}
+
+/// The end of the side of the conversation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum End {
+    /// The Sans task finished.
+    Finished,
+    /// The translation stopped the side.
+    Stopped,
+    /// The translation waits for the data, which will never arrive.
+    Blocked,
+}
+
+/// The ends of both sides of the conversation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Outcome {
+    /// The end of the client side.
+    pub client: End,
+    /// The end of the server side.
+    pub server: End,
+}
+
+/// The incoming pipe of the side.
+#[derive(Default)]
+struct Endpoint {
+    incoming: VecDeque<u8>,
+    read: Vec<u8>,
+    closed: bool,
+}
+
+/// The connection of the side with its peer.
+pub struct Link<'l> {
+    own: &'l mut Endpoint,
+    peer: &'l mut Endpoint,
+}
+
+impl Link<'_> {
+    /// Reads at most max bytes of the data written by the peer so far. Returns the empty slice if
+    /// there is no data.
+    pub fn read(&mut self, max: usize) -> &[u8] {
+        let len = self.own.incoming.len().min(max);
+        self.own.read.clear();
+        self.own.read.extend(self.own.incoming.drain(..len));
+        &self.own.read
+    }
+
+    /// The number of bytes written by the peer, which are not read yet.
+    pub fn available(&self) -> usize {
+        self.own.incoming.len()
+    }
+
+    /// Writes the data to the peer.
+    pub fn write(&mut self, data: &[u8]) {
+        self.peer.incoming.extend(data);
+    }
+
+    /// Returns true if the peer finished or stopped, so it will not write anymore.
+    pub fn is_closed(&self) -> bool {
+        self.own.closed
+    }
+}
+
+/// The state of the side.
+struct Side<'a, M: Message, Task> {
+    handler: Option<IoRequest<'a, M, Task>>,
+    end: Option<End>,
+}
+
+impl<'a, M: Message, Task: Future<Output = ()>> Side<'a, M, Task> {
+    fn new(handler: Option<IoRequest<'a, M, Task>>, translate: &mut impl Translate<M>) -> Self {
+        let end = handler.is_none().then_some(End::Finished);
+        if end.is_some() {
+            translate.finished();
+        }
+        Self { handler, end }
+    }
+
+    /// Handles the Request of the side. Returns true if the conversation progressed.
+    fn step(
+        &mut self,
+        io: &Io<'_, M>,
+        translate: &mut impl Translate<M>,
+        own: &mut Endpoint,
+        peer: &mut Endpoint,
+    ) -> bool {
+        let Some(handler) = self.handler.take() else {
+            return false;
+        };
+        let mut link = Link { own, peer };
+        let progress = match translate.translate(handler.request(), &mut link) {
+            Action::Respond(response) => {
+                self.handler = io.handle(handler, &response);
+                if self.handler.is_none() {
+                    self.end = Some(End::Finished);
+                }
+                true
+            }
+            Action::Wait => {
+                self.handler = Some(handler);
+                false
+            }
+            Action::Stop => {
+                self.end = Some(End::Stopped);
+                true
+            }
+        };
+        if self.end == Some(End::Finished) {
+            translate.finished();
+        }
+        // The peer will not receive anything from the finished or stopped side
+        peer.closed = self.end.is_some();
+        progress
+    }
+}
+
+/// Runs the client task against the server task until none of them could progress.
+pub fn run<'cs, 'ss, 'c, 's, C, S, ClientTask, ServerTask>(
+    client_io: &Io<'cs, C>,
+    client_task: Pin<&'c mut ClientTask>,
+    client_translate: &mut impl Translate<C>,
+    server_io: &Io<'ss, S>,
+    server_task: Pin<&'s mut ServerTask>,
+    server_translate: &mut impl Translate<S>,
+) -> Outcome
+where
+    'cs: 'c,
+    'ss: 's,
+    C: Message,
+    S: Message,
+    ClientTask: Future<Output = ()>,
+    ServerTask: Future<Output = ()>,
+{
+    let mut client_endpoint = Endpoint::default();
+    let mut server_endpoint = Endpoint::default();
+
+    let mut client = Side::new(client_io.start(client_task), client_translate);
+    server_endpoint.closed = client.handler.is_none();
+    let mut server = Side::new(server_io.start(server_task), server_translate);
+    client_endpoint.closed = server.handler.is_none();
+
+    loop {
+        let client_progress = client.step(
+            client_io,
+            client_translate,
+            &mut client_endpoint,
+            &mut server_endpoint,
+        );
+        let server_progress = server.step(
+            server_io,
+            server_translate,
+            &mut server_endpoint,
+            &mut client_endpoint,
+        );
+        if !client_progress && !server_progress {
+            break;
+        }
+    }
+
+    Outcome {
+        client: client.end.unwrap_or(End::Blocked),
+        server: server.end.unwrap_or(End::Blocked),
+    }
+}
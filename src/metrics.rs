@@ -0,0 +1,304 @@
+//! Per-session metrics of the Io part.
+//!
+//! The [Meter] replaces the [Io](crate::Io) in the driver of the session and measures every
+//! exchange: the time the Sans task spent in the poll which prepared the Request and the time the
+//! Io part spent until it passed the Response back, e.g. waiting for the I/O. Measurements are
+//! passed to the [Metrics] together with the kind of the Request, so a protocol spending its CPU
+//! in parsing could be told from a protocol waiting for its driver. The [Meter] is a [Drive]
+//! wrapping another [Drive], so it could be stacked with other wrappers of the Io part.
+//!
+//! [Stats] is the [Metrics] accumulating totals of the session and a histogram of Request kinds.
+//!
+//! ```
+//! # use asansio::Drive;
+//! # use asansio::Session;
+//! # use asansio::metrics::Meter;
+//! # use asansio::metrics::Stats;
+//! # use std::pin::pin;
+//! #
+//! enum Request {
+//!     Ping,
+//!     Bye,
+//! }
+//! struct Response;
+//!
+//! struct Proto;
+//!
+//! asansio::message! {
+//!     Proto {
+//!         type Request<'r> = Request;
+//!         type Response<'r> = Response;
+//!     }
+//! }
+//!
+//! let mut session = Session::<Proto>::new();
+//! let (mut sans, io) = session.split();
+//! let stats = Stats::new(|request: &Request| match request {
+//!     Request::Ping => "ping",
+//!     Request::Bye => "bye",
+//! });
+//! let mut meter = Meter::new(io, stats);
+//!
+//! let task = pin!(async {
+//!     let response = sans.start(&Request::Ping).await;
+//!     sans.handle(response, &Request::Bye).await;
+//! });
+//!
+//! let mut handler = meter.start(task);
+//! while let Some(request) = handler {
+//!     handler = meter.handle(request, &Response);
+//! }
+//!
+//! let stats = meter.into_metrics();
+//! assert_eq!(stats.total().exchanges, 2);
+//! assert_eq!(stats.kind("ping").unwrap().exchanges, 1);
+//! assert_eq!(stats.polls(), 3);
+//! ```
+
+use crate::Drive;
+use crate::IoRequest;
+use crate::Message;
+use core::pin::Pin;
+use core::time::Duration;
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+/// Receives measurements of the session from the [Meter].
+pub trait Metrics<M: Message> {
+    /// Returns the kind of the Request, e.g. the name of its variant.
+    fn classify(&mut self, request: &M::Request<'_>) -> &'static str;
+
+    /// Records the exchange of the Request of the kind: the time of the poll of the Sans task,
+    /// which prepared the Request, and the time until the Response was passed back.
+    fn exchange(&mut self, kind: &'static str, sans: Duration, io: Duration);
+
+    /// Records the time of the last poll of the Sans task, which finished or panicked.
+    fn finish(&mut self, sans: Duration);
+}
+
+/// Accumulated measurements of exchanges.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Totals {
+    /// The number of exchanges.
+    pub exchanges: usize,
+    /// The time spent in the Sans task.
+    pub sans: Duration,
+    /// The time spent in the Io part.
+    pub io: Duration,
+}
+
+/// The [Metrics] accumulating totals of the session and of every kind of Requests.
+#[derive(Clone, Debug)]
+pub struct Stats<K> {
+    classify: K,
+    polls: usize,
+    total: Totals,
+    kinds: BTreeMap<&'static str, Totals>,
+}
+
+impl<K> Stats<K> {
+    /// Creates empty stats with the function returning the kind of the Request.
+    pub fn new(classify: K) -> Self {
+        Self {
+            classify,
+            polls: 0,
+            total: Totals::default(),
+            kinds: BTreeMap::new(),
+        }
+    }
+
+    /// The number of polls of the Sans task. Every exchange ends with a single poll, so the
+    /// session has one more poll than exchanges when the Sans task finishes.
+    pub fn polls(&self) -> usize {
+        self.polls
+    }
+
+    /// Retrieve totals of all exchanges. The time of the Sans task includes its last poll.
+    pub fn total(&self) -> &Totals {
+        &self.total
+    }
+
+    /// Retrieve totals of exchanges of the kind.
+    pub fn kind(&self, kind: &str) -> Option<&Totals> {
+        self.kinds.get(kind)
+    }
+
+    /// Iterates over kinds of Requests with their totals, ordered by the kind.
+    pub fn kinds(&self) -> impl Iterator<Item = (&'static str, &Totals)> {
+        self.kinds.iter().map(|(kind, totals)| (*kind, totals))
+    }
+}
+
+impl<M: Message, K> Metrics<M> for Stats<K>
+where
+    K: FnMut(&M::Request<'_>) -> &'static str,
+{
+    fn classify(&mut self, request: &M::Request<'_>) -> &'static str {
+        (self.classify)(request)
+    }
+
+    fn exchange(&mut self, kind: &'static str, sans: Duration, io: Duration) {
+        self.polls += 1;
+        for totals in [&mut self.total, self.kinds.entry(kind).or_default()] {
+            totals.exchanges += 1;
+            totals.sans += sans;
+            totals.io += io;
+        }
+    }
+
+    fn finish(&mut self, sans: Duration) {
+        self.polls += 1;
+        self.total.sans += sans;
+    }
+}
+
+/// The Request waiting for the Response
+struct Pending {
+    kind: &'static str,
+    sans: Duration,
+    sent: Instant,
+}
+
+/// The Io part which measures exchanges of the session.
+///
+/// It wraps the [Io](crate::Io) or another [Drive], so it could replace it in the driver of the
+/// session.
+pub struct Meter<D: Drive, R: Metrics<D::Message>> {
+    io: D,
+    metrics: R,
+    pending: Option<Pending>,
+}
+
+impl<D: Drive, R: Metrics<D::Message>> Meter<D, R> {
+    /// Creates the meter for the Io part passing measurements to the metrics.
+    pub fn new(io: D, metrics: R) -> Self {
+        Self {
+            io,
+            metrics,
+            pending: None,
+        }
+    }
+
+    /// Retrieve the metrics.
+    pub fn metrics(&self) -> &R {
+        &self.metrics
+    }
+
+    /// Returns the metrics, consuming the meter.
+    pub fn into_metrics(self) -> R {
+        self.metrics
+    }
+
+    /// Returns the Io part and the metrics, consuming the meter.
+    pub fn into_parts(self) -> (D, R) {
+        (self.io, self.metrics)
+    }
+
+    fn measure_exchange(&mut self) {
+        if let Some(Pending { kind, sans, sent }) = self.pending.take() {
+            self.metrics.exchange(kind, sans, sent.elapsed());
+        }
+    }
+
+    fn measure_poll<Task: Future<Output = ()> + ?Sized>(
+        &mut self,
+        polled: Instant,
+        handler: Option<&IoRequest<D::Message, Task>>,
+    ) {
+        let sans = polled.elapsed();
+        match handler.map(IoRequest::request) {
+            Some(request) => {
+                let kind = self.metrics.classify(request);
+                self.pending = Some(Pending {
+                    kind,
+                    sans,
+                    sent: Instant::now(),
+                });
+            }
+            None => self.metrics.finish(sans),
+        }
+    }
+
+    fn measure_result<Task: Future<Output = ()> + ?Sized>(
+        &mut self,
+        polled: Instant,
+        handler: Result<&Option<IoRequest<D::Message, Task>>, &D::Error>,
+    ) {
+        match handler.map_err(D::task_error) {
+            Ok(handler) => self.measure_poll(polled, handler.as_ref()),
+            Err(Some(crate::Error::Panicked(_))) => self.metrics.finish(polled.elapsed()),
+            Err(_) => {}
+        }
+    }
+}
+
+impl<D: Drive, R: Metrics<D::Message>> Drive for Meter<D, R> {
+    type Message = D::Message;
+    type Error = D::Error;
+
+    /// The same as [Drive::start], the poll is measured.
+    fn start<'a, Task>(
+        &mut self,
+        task: Pin<&'a mut Task>,
+    ) -> Option<IoRequest<'a, D::Message, Task>>
+    where
+        Self: 'a,
+        Task: Future<Output = ()> + ?Sized,
+    {
+        let polled = Instant::now();
+        let handler = self.io.start(task);
+        self.measure_poll(polled, handler.as_ref());
+        handler
+    }
+
+    /// The same as [Drive::handle], the exchange and the next poll are measured.
+    fn handle<'a, Task>(
+        &mut self,
+        handler: IoRequest<'a, D::Message, Task>,
+        response: &<D::Message as Message>::Response<'_>,
+    ) -> Option<IoRequest<'a, D::Message, Task>>
+    where
+        Task: Future<Output = ()> + ?Sized,
+    {
+        self.measure_exchange();
+        let polled = Instant::now();
+        let handler = self.io.handle(handler, response);
+        self.measure_poll(polled, handler.as_ref());
+        handler
+    }
+
+    /// The same as [Drive::try_start], the poll is measured.
+    fn try_start<'a, Task>(
+        &mut self,
+        task: Pin<&'a mut Task>,
+    ) -> Result<Option<IoRequest<'a, D::Message, Task>>, D::Error>
+    where
+        Self: 'a,
+        Task: Future<Output = ()> + ?Sized,
+    {
+        let polled = Instant::now();
+        let handler = self.io.try_start(task);
+        self.measure_result(polled, handler.as_ref());
+        handler
+    }
+
+    /// The same as [Drive::try_handle], the exchange and the next poll are measured.
+    fn try_handle<'a, Task>(
+        &mut self,
+        handler: IoRequest<'a, D::Message, Task>,
+        response: &<D::Message as Message>::Response<'_>,
+    ) -> Result<Option<IoRequest<'a, D::Message, Task>>, D::Error>
+    where
+        Task: Future<Output = ()> + ?Sized,
+    {
+        self.measure_exchange();
+        let polled = Instant::now();
+        let handler = self.io.try_handle(handler, response);
+        self.measure_result(polled, handler.as_ref());
+        handler
+    }
+
+    fn task_error(err: &D::Error) -> Option<&crate::Error> {
+        D::task_error(err)
+    }
+}
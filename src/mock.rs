@@ -0,0 +1,337 @@
+//! Scripted mock of the Io part for unit-testing protocols.
+//!
+//! The [MockIo] is built as a script of steps: every step expects a Request matching the
+//! predicate and responds with the Response. Steps could be optional or grouped together in any
+//! order, and the script could expect the Sans task to finish after the last step. Running the
+//! script drives the Sans task and reports the first mismatch as the readable [Failure].
+//!
+//! Responses are stored in the script, so they are `M::Response<'static>`, e.g. borrowing
+//! `'static` buffers or owning their payloads.
+//!
+//! ```
+//! # use asansio::Sans;
+//! # use asansio::Session;
+//! # use asansio::mock::MockIo;
+//! # use std::pin::pin;
+//! #
+//! enum Request {
+//!     Ping,
+//!     Bye,
+//! }
+//! enum Response {
+//!     Pong,
+//! }
+//!
+//! struct Proto;
+//!
+//! asansio::message! {
+//!     Proto {
+//!         type Request<'r> = Request;
+//!         type Response<'r> = Response;
+//!     }
+//! }
+//!
+//! async fn sans_task(mut sans: Sans<'_, Proto>) {
+//!     let response = sans.start(&Request::Ping).await;
+//!     sans.handle(response, &Request::Bye).await;
+//! }
+//!
+//! let mut session = Session::<Proto>::new();
+//! let (sans, io) = session.split();
+//!
+//! MockIo::new()
+//!     .expect("ping", |request| matches!(request, Request::Ping))
+//!     .respond(Response::Pong)
+//!     .expect("bye", |request| matches!(request, Request::Bye))
+//!     .respond(Response::Pong)
+//!     .expect_end()
+//!     .run(&io, pin!(sans_task(sans)));
+//! ```
+
+use crate::Io;
+use crate::Message;
+use core::fmt;
+use core::pin::Pin;
+use std::boxed::Box;
+use std::format;
+use std::string::String;
+use std::vec;
+use std::vec::Vec;
+
+type Predicate<'m, M> = Box<dyn FnMut(&<M as Message>::Request<'_>) -> bool + 'm>;
+type Responder<'m, M> =
+    Box<dyn FnMut(&<M as Message>::Request<'_>) -> <M as Message>::Response<'static> + 'm>;
+type Describe<'m, M> = Box<dyn Fn(&<M as Message>::Request<'_>) -> String + 'm>;
+
+/// A single step of the script.
+struct Step<'m, M: Message> {
+    name: String,
+    predicate: Predicate<'m, M>,
+    responder: Responder<'m, M>,
+    optional: bool,
+}
+
+/// The scripted mock of the Io part.
+pub struct MockIo<'m, M: Message> {
+    groups: Vec<Vec<Step<'m, M>>>,
+    expect_end: bool,
+    describe: Option<Describe<'m, M>>,
+}
+
+/// The step of the [MockIo] waiting for its Response.
+pub struct Expectation<'m, M: Message> {
+    script: MockIo<'m, M>,
+    name: String,
+    predicate: Predicate<'m, M>,
+    optional: bool,
+}
+
+/// The first mismatch between the script and the Sans task.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Failure {
+    /// The Request does not match any of the expected steps.
+    UnexpectedRequest {
+        /// The number of the Request, starting from 1.
+        number: usize,
+        /// The description of the Request, if the script describes Requests.
+        request: Option<String>,
+        /// Names of the steps which could match.
+        expected: Vec<String>,
+    },
+    /// The Sans task finished before the mandatory steps.
+    UnexpectedEnd {
+        /// Names of the remaining mandatory steps.
+        expected: Vec<String>,
+    },
+    /// The Sans task sent the Request after the last step, but it was expected to finish.
+    NotFinished {
+        /// The number of the Request, starting from 1.
+        number: usize,
+        /// The description of the Request, if the script describes Requests.
+        request: Option<String>,
+    },
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let request = |f: &mut fmt::Formatter<'_>, number, request: &Option<String>| match request {
+            Some(request) => write!(f, "request #{number} {request}"),
+            None => write!(f, "request #{number}"),
+        };
+        match self {
+            Self::UnexpectedRequest {
+                number,
+                request: description,
+                expected,
+            } => {
+                request(f, number, description)?;
+                write!(f, " does not match any of expected steps: ")?;
+                write!(f, "{}", expected.join(", "))
+            }
+            Self::UnexpectedEnd { expected } => write!(
+                f,
+                "Sans task finished, but steps are still expected: {}",
+                expected.join(", ")
+            ),
+            Self::NotFinished {
+                number,
+                request: description,
+            } => {
+                request(f, number, description)?;
+                write!(
+                    f,
+                    " received after the last step, the Sans task should finish"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for Failure {}
+
+impl<'m, M: Message> MockIo<'m, M> {
+    /// Creates the empty script.
+    pub fn new() -> Self {
+        Self {
+            groups: Vec::new(),
+            expect_end: false,
+            describe: None,
+        }
+    }
+
+    /// Adds the step expecting the Request matching the predicate. The name of the step is used
+    /// in failures.
+    pub fn expect(
+        self,
+        name: impl Into<String>,
+        predicate: impl FnMut(&M::Request<'_>) -> bool + 'm,
+    ) -> Expectation<'m, M> {
+        Expectation {
+            script: self,
+            name: name.into(),
+            predicate: Box::new(predicate),
+            optional: false,
+        }
+    }
+
+    /// Adds steps built by the function, which could match Requests in any order.
+    pub fn any_order(mut self, steps: impl FnOnce(Self) -> Self) -> Self {
+        let group = steps(Self::new())
+            .groups
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        if !group.is_empty() {
+            self.groups.push(group);
+        }
+        self
+    }
+
+    /// Expects the Sans task to finish after the last step. Otherwise the script stops after the
+    /// last step and the Sans task is not polled anymore.
+    pub fn expect_end(mut self) -> Self {
+        self.expect_end = true;
+        self
+    }
+
+    /// Describes Requests in failures with the function, e.g. their `Debug` output.
+    pub fn describe(mut self, describe: impl Fn(&M::Request<'_>) -> String + 'm) -> Self {
+        self.describe = Some(Box::new(describe));
+        self
+    }
+
+    /// Drives the Sans task with the script.
+    ///
+    /// # Panics
+    ///
+    /// Panics with the [Failure] message if the Sans task does not follow the script.
+    #[track_caller]
+    pub fn run<'s, 'a, Task>(self, io: &Io<'s, M>, task: Pin<&'a mut Task>)
+    where
+        's: 'a,
+        Task: Future<Output = ()>,
+    {
+        if let Err(failure) = self.try_run(io, task) {
+            panic!("{failure}");
+        }
+    }
+
+    /// Drives the Sans task with the script and returns the first [Failure].
+    pub fn try_run<'s, 'a, Task>(
+        mut self,
+        io: &Io<'s, M>,
+        task: Pin<&'a mut Task>,
+    ) -> Result<(), Failure>
+    where
+        's: 'a,
+        Task: Future<Output = ()>,
+    {
+        self.groups.reverse();
+        let mut number = 0;
+        let mut handler = io.start(task);
+        while let Some(current) = handler {
+            number += 1;
+            let request = current.request();
+            let Some(mut step) = self.next_step(request) else {
+                let mandatory = self.groups.iter().flatten().any(|step| !step.optional);
+                if !mandatory && !self.expect_end {
+                    return Ok(());
+                }
+                return Err(self.mismatch(number, request));
+            };
+            let response = (step.responder)(request);
+            handler = io.handle(current, &response);
+        }
+
+        let expected = self
+            .groups
+            .iter()
+            .rev()
+            .flatten()
+            .filter(|step| !step.optional)
+            .map(|step| step.name.clone())
+            .collect::<Vec<_>>();
+        if expected.is_empty() {
+            Ok(())
+        } else {
+            Err(Failure::UnexpectedEnd { expected })
+        }
+    }
+
+    /// Finds the step matching the Request, skipping groups with only optional steps left.
+    fn next_step(&mut self, request: &M::Request<'_>) -> Option<Step<'m, M>> {
+        for idx in (0..self.groups.len()).rev() {
+            let group = &mut self.groups[idx];
+            if let Some(pos) = group.iter_mut().position(|step| (step.predicate)(request)) {
+                let step = group.remove(pos);
+                let len = if group.is_empty() { idx } else { idx + 1 };
+                self.groups.truncate(len);
+                return Some(step);
+            }
+            if group.iter().any(|step| !step.optional) {
+                return None;
+            }
+        }
+        None
+    }
+
+    fn mismatch(&self, number: usize, request: &M::Request<'_>) -> Failure {
+        let request = self.describe.as_ref().map(|describe| describe(request));
+        if self.groups.is_empty() {
+            return Failure::NotFinished { number, request };
+        }
+        let mut expected = Vec::new();
+        for group in self.groups.iter().rev() {
+            expected.extend(group.iter().map(|step| match step.optional {
+                true => format!("{} (optional)", step.name),
+                false => step.name.clone(),
+            }));
+            if group.iter().any(|step| !step.optional) {
+                break;
+            }
+        }
+        Failure::UnexpectedRequest {
+            number,
+            request,
+            expected,
+        }
+    }
+}
+
+impl<M: Message> Default for MockIo<'_, M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'m, M: Message> Expectation<'m, M> {
+    /// Marks the step as optional - the script continues if it does not match.
+    pub fn optional(mut self) -> Self {
+        self.optional = true;
+        self
+    }
+
+    /// Responds with the Response to the matching Request.
+    pub fn respond(self, response: M::Response<'static>) -> MockIo<'m, M>
+    where
+        M::Response<'static>: 'm,
+    {
+        let mut response = Some(response);
+        self.respond_with(move |_| response.take().unwrap())
+    }
+
+    /// Responds with the Response built by the function from the matching Request.
+    pub fn respond_with(
+        self,
+        responder: impl FnMut(&M::Request<'_>) -> M::Response<'static> + 'm,
+    ) -> MockIo<'m, M> {
+        let mut script = self.script;
+        script.groups.push(vec![Step {
+            name: self.name,
+            predicate: self.predicate,
+            responder: Box::new(responder),
+            optional: self.optional,
+        }]);
+        script
+    }
+}
@@ -0,0 +1,84 @@
+//! Standard vocabularies of messages for network protocols.
+//!
+//! Protocols written against these vocabularies could be driven by any Io part supporting them:
+//! real sockets, the [sim](crate::sim) simulator or user tests. The [Stream] is a reliable
+//! ordered byte stream like TCP and the [Datagram] is an unreliable message transport like UDP.
+//! Both could wait for the data with a timeout and sleep, so protocols could implement timers.
+use core::time::Duration;
+
+/// The Request of the [Stream] vocabulary.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamRequest<'a> {
+    /// Reads at most max bytes from the peer, waiting at most the timeout if it is set. The
+    /// Response is [StreamResponse::Data], [StreamResponse::Closed] or [StreamResponse::Timeout].
+    /// The max must not be zero.
+    Read {
+        max: usize,
+        timeout: Option<Duration>,
+    },
+    /// Writes the data to the peer. The Response is [StreamResponse::Written].
+    Write { data: &'a [u8] },
+    /// Sleeps for the duration. The Response is [StreamResponse::Woken].
+    Sleep { duration: Duration },
+}
+
+/// The Response of the [Stream] vocabulary.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamResponse<'a> {
+    /// The data read from the peer, never empty nor longer than the max of the Read. It could be
+    /// a part of the written data or many writes together.
+    Data { data: &'a [u8] },
+    /// The peer closed the stream and all its data was read.
+    Closed,
+    /// No data was read before the timeout.
+    Timeout,
+    /// The data was written.
+    Written,
+    /// The sleep finished.
+    Woken,
+}
+
+/// The reliable ordered byte stream vocabulary.
+pub struct Stream;
+
+crate::message! {
+    Stream {
+        type Request<'r> = StreamRequest<'r>;
+        type Response<'r> = StreamResponse<'r>;
+    }
+}
+
+/// The Request of the [Datagram] vocabulary.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DatagramRequest<'a> {
+    /// Receives the datagram from the peer, waiting at most the timeout if it is set. The
+    /// Response is [DatagramResponse::Datagram] or [DatagramResponse::Timeout].
+    Recv { timeout: Option<Duration> },
+    /// Sends the datagram to the peer. The Response is [DatagramResponse::Sent].
+    Send { data: &'a [u8] },
+    /// Sleeps for the duration. The Response is [DatagramResponse::Woken].
+    Sleep { duration: Duration },
+}
+
+/// The Response of the [Datagram] vocabulary.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DatagramResponse<'a> {
+    /// The datagram received from the peer.
+    Datagram { data: &'a [u8] },
+    /// No datagram was received before the timeout.
+    Timeout,
+    /// The datagram was sent, it could be still lost, duplicated or reordered.
+    Sent,
+    /// The sleep finished.
+    Woken,
+}
+
+/// The unreliable message transport vocabulary.
+pub struct Datagram;
+
+crate::message! {
+    Datagram {
+        type Request<'r> = DatagramRequest<'r>;
+        type Response<'r> = DatagramResponse<'r>;
+    }
+}
@@ -0,0 +1,467 @@
+//! Property-based testing of Sans tasks.
+//!
+//! The [Property] drives the Sans task through the [Io](crate::Io) with Responses generated by
+//! the user-provided [Strategy] for many random cases. The strategy draws all random values from
+//! the [Source], e.g. arbitrary payloads or arbitrary chunkings of the stream, and checks the
+//! property on every Request. When the property fails or the Sans task panics, the random
+//! choices of the case are shrunk to the minimal failing case, which is reported as the
+//! [Failure] with the transcript recorded by the [Hook].
+//!
+//! The shrinking removes and decreases the recorded choices, so strategies should map smaller
+//! choices to simpler values - [Source] methods return the start of the range, the whole data or
+//! `false` for the zero choice. Choices drawn past the end of the shrunk sequence are zeros.
+//!
+//! ```
+//! # use asansio::Sans;
+//! # use asansio::net::Stream;
+//! # use asansio::net::StreamRequest;
+//! # use asansio::net::StreamResponse;
+//! # use asansio::prop::Source;
+//! # use asansio::prop::Property;
+//! # use asansio::prop::Strategy;
+//! # use asansio::transcript::DebugHook;
+//! #
+//! /// Sums bytes of the stream, panics for big sums
+//! async fn sum(mut sans: Sans<'_, Stream>) {
+//!     let read = StreamRequest::Read {
+//!         max: 4,
+//!         timeout: None,
+//!     };
+//!     let mut sum: u8 = 0;
+//!     let mut response = sans.start(&read).await;
+//!     while let StreamResponse::Data { data } = response.response() {
+//!         for byte in *data {
+//!             sum = sum.checked_add(*byte).expect("sum overflow");
+//!         }
+//!         response = sans.handle(response, &read).await;
+//!     }
+//! }
+//!
+//! /// Sends up to 10 random chunks
+//! #[derive(Default)]
+//! struct Chunks {
+//!     chunk: Vec<u8>,
+//!     sent: usize,
+//! }
+//!
+//! impl Strategy<Stream> for Chunks {
+//!     fn respond<'a>(
+//!         &'a mut self,
+//!         source: &mut Source,
+//!         _: &StreamRequest<'_>,
+//!     ) -> Result<Option<StreamResponse<'a>>, String> {
+//!         if self.sent == 10 {
+//!             return Ok(Some(StreamResponse::Closed));
+//!         }
+//!         self.sent += 1;
+//!         self.chunk = source.bytes(1..=4);
+//!         Ok(Some(StreamResponse::Data { data: &self.chunk }))
+//!     }
+//! }
+//!
+//! let failure = Property::new(0)
+//!     .check(|sans| Box::pin(sum(sans)), Chunks::default, DebugHook)
+//!     .unwrap_err();
+//! assert_eq!(failure.message, "Sans task panicked: sum overflow");
+//! // The single chunk of two bytes with the sum over 255
+//! assert_eq!(failure.choices, [1, 177, 79]);
+//! ```
+
+use crate::Drive;
+use crate::Message;
+use crate::Sans;
+use crate::Session;
+use crate::explore::Task;
+use crate::sim::Rng;
+use crate::transcript::Hook;
+use crate::transcript::Recorder;
+use crate::transcript::Transcript;
+use core::fmt;
+use core::fmt::Debug;
+use core::mem;
+use core::ops::Range;
+use core::ops::RangeInclusive;
+use std::string::String;
+use std::string::ToString;
+use std::vec::Vec;
+
+/// The source of random choices for the [Strategy].
+///
+/// All choices are recorded, so the case could be replayed and shrunk.
+pub struct Source {
+    choices: Vec<u64>,
+    spans: Vec<Range<usize>>,
+    pos: usize,
+    origin: Origin,
+    exhausted: bool,
+}
+
+/// The origin of choices past the recorded ones
+pub(crate) enum Origin {
+    /// Random choices of the property
+    Random(Rng),
+    /// Bytes of the fuzzer input
+    Input { data: Vec<u8>, offset: usize },
+    /// Zeros past the end of shrunk choices
+    Zeros,
+}
+
+impl Source {
+    pub(crate) fn new(choices: Vec<u64>, origin: Origin) -> Self {
+        Self {
+            choices,
+            spans: Vec::new(),
+            pos: 0,
+            origin,
+            exhausted: false,
+        }
+    }
+
+    /// The number of consumed bytes of the fuzzer input
+    pub(crate) fn consumed(&self) -> usize {
+        match self.origin {
+            Origin::Input { offset, .. } => offset,
+            _ => 0,
+        }
+    }
+
+    /// Some choice was drawn past the end of the fuzzer input
+    pub(crate) fn is_exhausted(&self) -> bool {
+        self.exhausted
+    }
+
+    /// Records choices drawn from the start as the span, which could be removed by shrinking
+    fn span(&mut self, start: usize) {
+        if self.pos > start {
+            self.spans.push(start..self.pos);
+        }
+    }
+
+    /// Draws the next choice below the bound, the zero bound returns zero.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        let choice = match (self.choices.get(self.pos), &mut self.origin) {
+            (Some(choice), _) => *choice,
+            (None, Origin::Random(rng)) => rng.next(),
+            (None, Origin::Input { data, offset }) => {
+                // As many bytes as needed for the bound, little endian
+                let width = (u64::BITS - bound.saturating_sub(1).leading_zeros()).div_ceil(8);
+                let end = *offset + width as usize;
+                let bytes = data.get(*offset..end);
+                *offset = end.min(data.len());
+                match bytes {
+                    Some(bytes) => bytes
+                        .iter()
+                        .rev()
+                        .fold(0, |choice, byte| (choice << 8) | *byte as u64),
+                    None => {
+                        self.exhausted = true;
+                        0
+                    }
+                }
+            }
+            (None, Origin::Zeros) => 0,
+        };
+        let choice = choice.checked_rem(bound).unwrap_or(0);
+        match self.choices.get_mut(self.pos) {
+            Some(recorded) => *recorded = choice,
+            None => self.choices.push(choice),
+        }
+        self.pos += 1;
+        choice
+    }
+
+    /// Draws the number from the range.
+    pub fn range(&mut self, range: RangeInclusive<usize>) -> usize {
+        let (start, end) = range.into_inner();
+        if end <= start {
+            return start;
+        }
+        start + self.below((end - start) as u64 + 1) as usize
+    }
+
+    /// Draws the boolean value.
+    pub fn bool(&mut self) -> bool {
+        self.below(2) == 1
+    }
+
+    /// Chooses one of the items, which must not be empty.
+    pub fn choose<'t, T>(&mut self, items: &'t [T]) -> &'t T {
+        &items[self.below(items.len() as u64) as usize]
+    }
+
+    /// Draws bytes with the length from the range.
+    pub fn bytes(&mut self, len: RangeInclusive<usize>) -> Vec<u8> {
+        let start = self.pos;
+        let len = self.range(len);
+        let bytes = (0..len).map(|_| self.below(256) as u8).collect();
+        self.span(start);
+        bytes
+    }
+
+    /// Splits the data into arbitrary non-empty chunks, as the stream could deliver it.
+    pub fn chunks<'d>(&mut self, mut data: &'d [u8]) -> Vec<&'d [u8]> {
+        let start = self.pos;
+        let mut chunks = Vec::new();
+        while !data.is_empty() {
+            let len = data.len() - self.below(data.len() as u64) as usize;
+            let (chunk, rest) = data.split_at(len);
+            chunks.push(chunk);
+            data = rest;
+        }
+        self.span(start);
+        chunks
+    }
+}
+
+/// Generates Responses for the Sans task and checks the property.
+///
+/// The new strategy is created for every case.
+pub trait Strategy<M: Message> {
+    /// Generates the Response for the Request. `Ok(None)` finishes the case and `Err` with the
+    /// message fails the property.
+    fn respond<'a>(
+        &'a mut self,
+        source: &mut Source,
+        request: &M::Request<'_>,
+    ) -> Result<Option<M::Response<'a>>, String>;
+}
+
+/// The minimal failing case found by the [Property].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Failure<Req, Resp> {
+    /// The seed of the property.
+    pub seed: u64,
+    /// The number of the first failing case, starting from 0.
+    pub case: usize,
+    /// The message of the failed property or the panic of the Sans task.
+    pub message: String,
+    /// The shrunk random choices.
+    pub choices: Vec<u64>,
+    /// The transcript of the shrunk case.
+    pub transcript: Transcript<Req, Resp>,
+}
+
+impl<Req: Debug, Resp: Debug> fmt::Display for Failure<Req, Resp> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "property failed in case {} with seed {}: {}",
+            self.case, self.seed, self.message
+        )?;
+        writeln!(f, "shrunk choices: {:?}", self.choices)?;
+        write!(f, "{}", self.transcript)
+    }
+}
+
+impl<Req: Debug, Resp: Debug> std::error::Error for Failure<Req, Resp> {}
+
+/// The property-based test runner.
+#[derive(Clone, Copy, Debug)]
+pub struct Property {
+    seed: u64,
+    cases: usize,
+    steps: usize,
+    shrinks: usize,
+}
+
+impl Property {
+    /// Creates the runner with the seed of random choices. The default is 100 cases, 1000
+    /// Responses per case and 10000 runs for shrinking.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            cases: 100,
+            steps: 1000,
+            shrinks: 10000,
+        }
+    }
+
+    /// Sets the number of random cases.
+    pub fn cases(mut self, cases: usize) -> Self {
+        self.cases = cases;
+        self
+    }
+
+    /// Sets the maximal number of Responses in the case, the longer case is finished.
+    pub fn steps(mut self, steps: usize) -> Self {
+        self.steps = steps;
+        self
+    }
+
+    /// Sets the maximal number of runs for shrinking the failing case.
+    pub fn shrinks(mut self, shrinks: usize) -> Self {
+        self.shrinks = shrinks;
+        self
+    }
+
+    /// Checks the property of the Sans task created by the factory, with the new strategy for
+    /// every case. The hook records the transcript of the failing case.
+    pub fn check<M, S, H>(
+        &self,
+        mut factory: impl for<'s> FnMut(Sans<'s, M>) -> Task<'s>,
+        mut strategy: impl FnMut() -> S,
+        mut hook: H,
+    ) -> Result<(), Failure<H::Request, H::Response>>
+    where
+        M: Message,
+        S: Strategy<M>,
+        H: Hook<M>,
+    {
+        let mut origin = Origin::Random(Rng(self.seed));
+        for case in 0..self.cases {
+            let mut source = Source::new(Vec::new(), origin);
+            let failed = self.run(&mut factory, &mut strategy(), &mut source, &mut hook);
+            origin = mem::replace(&mut source.origin, Origin::Zeros);
+            if failed.0.is_ok() {
+                continue;
+            }
+
+            let shrunk = self.shrink(source, |choices| {
+                let mut source = Source::new(choices, Origin::Zeros);
+                let failed = self
+                    .run(&mut factory, &mut strategy(), &mut source, &mut hook)
+                    .0
+                    .is_err();
+                failed.then_some(source)
+            });
+            let mut source = Source::new(shrunk.choices, Origin::Zeros);
+            let (result, transcript) =
+                self.run(&mut factory, &mut strategy(), &mut source, &mut hook);
+            return Err(Failure {
+                seed: self.seed,
+                case,
+                message: result.unwrap_err(),
+                choices: source.choices,
+                transcript,
+            });
+        }
+        Ok(())
+    }
+
+    /// Runs the single case, the used choices and spans of Responses stay in the source
+    fn run<M: Message, S: Strategy<M>, H: Hook<M>>(
+        &self,
+        factory: &mut impl for<'s> FnMut(Sans<'s, M>) -> Task<'s>,
+        strategy: &mut S,
+        source: &mut Source,
+        hook: &mut H,
+    ) -> (Result<(), String>, Transcript<H::Request, H::Response>) {
+        let mut session = Session::<M>::new();
+        let (sans, io) = session.split();
+        let mut task = factory(sans);
+        let mut recorder = Recorder::new(io, hook);
+
+        let mut result = recorder.try_start(task.as_mut());
+        let mut outcome = Ok(());
+        for _ in 0..self.steps {
+            let handler = match result {
+                Ok(Some(handler)) => handler,
+                Ok(None) => break,
+                Err(err) => {
+                    outcome = Err(err.to_string());
+                    break;
+                }
+            };
+            let start = source.pos;
+            let response = strategy.respond(source, handler.request());
+            source.span(start);
+            match response {
+                Ok(Some(response)) => result = recorder.try_handle(handler, &response),
+                Ok(None) => break,
+                Err(msg) => {
+                    outcome = Err(msg);
+                    break;
+                }
+            }
+        }
+        // Choices past the end are zeros
+        source.choices.truncate(source.pos);
+        while source.choices.last() == Some(&0) {
+            source.choices.pop();
+        }
+        (outcome, recorder.into_transcript())
+    }
+
+    /// Shrinks choices by removing spans and blocks and decreasing single choices while the case
+    /// fails. The failing run returns the source it used.
+    fn shrink(
+        &self,
+        mut best: Source,
+        mut fails: impl FnMut(Vec<u64>) -> Option<Source>,
+    ) -> Source {
+        let mut runs = 0;
+        let mut attempt = |candidate: Vec<u64>, best: &mut Source| {
+            if runs == self.shrinks {
+                return false;
+            }
+            runs += 1;
+            match fails(candidate) {
+                Some(source)
+                    if (source.choices.len(), &source.choices)
+                        < (best.choices.len(), &best.choices) =>
+                {
+                    *best = source;
+                    true
+                }
+                _ => false,
+            }
+        };
+
+        let mut shrunk = true;
+        while shrunk {
+            shrunk = false;
+
+            // Spans of whole values and Responses and up to three following spans, the latest
+            // first
+            let mut idx = best.spans.len();
+            while idx > 0 {
+                idx -= 1;
+                for next in idx..idx + 3 {
+                    let (Some(first), Some(last)) = (best.spans.get(idx), best.spans.get(next))
+                    else {
+                        break;
+                    };
+                    let len = best.choices.len();
+                    let span = first.start.min(len)..last.end.clamp(first.start.min(len), len);
+                    let mut candidate = best.choices.clone();
+                    candidate.drain(span);
+                    shrunk |= attempt(candidate, &mut best);
+                }
+            }
+
+            for len in [4, 2, 1] {
+                let mut start = best.choices.len().saturating_sub(len);
+                loop {
+                    if start + len <= best.choices.len() {
+                        let mut candidate = best.choices.clone();
+                        candidate.drain(start..start + len);
+                        shrunk |= attempt(candidate, &mut best);
+                    }
+                    if start == 0 {
+                        break;
+                    }
+                    start -= 1;
+                }
+            }
+
+            let mut idx = 0;
+            while idx < best.choices.len() {
+                // The binary search of the smallest failing choice
+                let (mut low, mut high) = (0, best.choices[idx]);
+                while low < high && idx < best.choices.len() {
+                    let mid = low + (high - low) / 2;
+                    let mut candidate = best.choices.clone();
+                    candidate[idx] = mid;
+                    if attempt(candidate, &mut best) {
+                        shrunk = true;
+                        high = mid;
+                    } else {
+                        low = mid + 1;
+                    }
+                }
+                idx += 1;
+            }
+        }
+        best
+    }
+}
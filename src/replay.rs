@@ -0,0 +1,124 @@
+//! Deterministic replay of recorded transcripts against a Sans task.
+//!
+//! The [replay] function drives the Sans task without any I/O: it feeds the recorded Responses
+//! into the [Io] and checks that every Request of the task matches the recorded one. The first
+//! event which differs from the [Transcript] is reported as the [Divergence], so the transcript
+//! of a failing session becomes a regression test.
+//!
+//! The hook used for recording must also implement [Replay] to restore Responses from records.
+
+use crate::Io;
+use crate::IoRequest;
+use crate::Message;
+use crate::transcript::Event;
+use crate::transcript::Hook;
+use crate::transcript::Transcript;
+use core::fmt;
+use core::fmt::Debug;
+use core::pin::Pin;
+use std::string::String;
+
+/// Restores Responses from records of the [Hook].
+pub trait Replay<M: Message>: Hook<M> {
+    /// Restores the Response borrowing the record.
+    fn restore_response<'r>(&mut self, record: &'r Self::Response) -> M::Response<'r>;
+}
+
+/// The first event of the Sans task which differs from the transcript.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence<Req, Resp> {
+    /// The sequence number of the recorded event.
+    pub seq: u64,
+    /// The recorded event.
+    pub expected: Event<Req, Resp>,
+    /// The event of the Sans task.
+    pub actual: Event<Req, Resp>,
+}
+
+impl<Req: Debug, Resp: Debug> fmt::Display for Divergence<Req, Resp> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "replay diverged at {}: expected {:?}, actual {:?}",
+            self.seq, self.expected, self.actual
+        )
+    }
+}
+
+impl<Req: Debug, Resp: Debug> std::error::Error for Divergence<Req, Resp> {}
+
+/// Replays the transcript against the Sans task.
+///
+/// The replay succeeds when the task reproduces all recorded events. It uses [Io::try_start] and
+/// [Io::try_handle], so a recorded panic is reproduced as well.
+///
+/// # Panics
+///
+/// Panics if the transcript is malformed - a recorded Request is not followed by the Response.
+pub fn replay<'s, 'a, M, H, Task>(
+    io: &Io<'s, M>,
+    task: Pin<&'a mut Task>,
+    hook: &mut H,
+    transcript: &Transcript<H::Request, H::Response>,
+) -> Result<(), Divergence<H::Request, H::Response>>
+where
+    's: 'a,
+    M: Message,
+    H: Replay<M>,
+    H::Request: PartialEq + Clone,
+    H::Response: Clone,
+    Task: Future<Output = ()> + ?Sized,
+{
+    let mut entries = transcript.entries().iter();
+    let mut result = io.try_start(task);
+    loop {
+        let actual = event(hook, &result);
+        let Some(expected) = entries.next() else {
+            return Ok(());
+        };
+        if !matches(&expected.event, &actual) {
+            return Err(Divergence {
+                seq: expected.seq,
+                expected: expected.event.clone(),
+                actual,
+            });
+        }
+        let Ok(Some(handler)) = result else {
+            return Ok(());
+        };
+        let Some(entry) = entries.next() else {
+            return Ok(());
+        };
+        let Event::Response(record) = &entry.event else {
+            panic!("Malformed transcript: the Request is not followed by the Response");
+        };
+        let response = hook.restore_response(record);
+        result = io.try_handle(handler, &response);
+    }
+}
+
+/// Records the result of the Io as the event.
+fn event<M, H, Task>(
+    hook: &mut H,
+    result: &Result<Option<IoRequest<M, Task>>, crate::Error>,
+) -> Event<H::Request, H::Response>
+where
+    M: Message,
+    H: Hook<M>,
+    Task: Future<Output = ()> + ?Sized,
+{
+    match result {
+        Ok(Some(handler)) => Event::Request(hook.request(handler.request())),
+        Ok(None) => Event::Finished,
+        Err(err) => Event::Panicked(err.message().map(String::from)),
+    }
+}
+
+fn matches<Req: PartialEq, Resp>(expected: &Event<Req, Resp>, actual: &Event<Req, Resp>) -> bool {
+    match (expected, actual) {
+        (Event::Request(expected), Event::Request(actual)) => expected == actual,
+        (Event::Finished, Event::Finished) => true,
+        (Event::Panicked(expected), Event::Panicked(actual)) => expected == actual,
+        _ => false,
+    }
+}
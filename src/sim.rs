@@ -0,0 +1,587 @@
+//! Deterministic network simulator for Sans protocols.
+//!
+//! The [Simulator] drives a client Sans task against a server Sans task, both written against
+//! the [Stream] or the [Datagram] vocabulary. The network between them is simulated with virtual
+//! time and the seeded pseudo random generator, so the same seed always gives the same
+//! conversation, even with adverse conditions:
+//!
+//! - every packet is delayed by the random latency,
+//! - writes to the [Stream] could be split into many packets and reads could coalesce packets
+//!   or return only their part,
+//! - datagrams are reordered by the random latency and they could be dropped or duplicated.
+//!
+//! The virtual time advances only when both tasks wait for the data or sleep, so timeouts are
+//! simulated without waiting. A task which never waits would stop the virtual time, so the number
+//! of Requests served at the same virtual time is limited and exceeding it is the [Error].
+
+use crate::Io;
+use crate::IoRequest;
+use crate::Message;
+use crate::loopback::End;
+use crate::net::Datagram;
+use crate::net::DatagramRequest;
+use crate::net::DatagramResponse;
+use crate::net::Stream;
+use crate::net::StreamRequest;
+use crate::net::StreamResponse;
+use core::fmt;
+use core::pin::Pin;
+use core::time::Duration;
+use std::collections::VecDeque;
+use std::vec::Vec;
+
+const CLIENT: usize = 0;
+const SERVER: usize = 1;
+
+/// The ends of both sides of the simulated conversation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Outcome {
+    /// The end of the client side. [End::Stopped] means the deadline was reached.
+    pub client: End,
+    /// The end of the server side. [End::Stopped] means the deadline was reached.
+    pub server: End,
+    /// The virtual time of the end of the conversation.
+    pub time: Duration,
+}
+
+/// The error of the simulation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The side exceeded the maximum number of Requests served at the same virtual time.
+    Livelock {
+        /// The side is the client.
+        client: bool,
+        /// The virtual time, which did not advance.
+        time: Duration,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Livelock { client, time } => {
+                let side = if *client { "client" } else { "server" };
+                write!(f, "{side} made no progress at the virtual time {time:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// The configuration of the simulated network.
+#[derive(Clone, Debug)]
+pub struct Simulator {
+    seed: u64,
+    min_latency: Duration,
+    max_latency: Duration,
+    split: bool,
+    drop_rate: f64,
+    duplicate_rate: f64,
+    deadline: Duration,
+    max_steps: usize,
+}
+
+impl Simulator {
+    /// Creates the perfect network with the seed of the pseudo random generator. The latency is
+    /// 1 ms, the deadline is 1 hour of the virtual time and the side could serve 100 000 Requests
+    /// at the same virtual time.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            min_latency: Duration::from_millis(1),
+            max_latency: Duration::from_millis(1),
+            split: false,
+            drop_rate: 0.0,
+            duplicate_rate: 0.0,
+            deadline: Duration::from_secs(3600),
+            max_steps: 100_000,
+        }
+    }
+
+    /// Sets the range of the random latency of packets.
+    pub fn latency(mut self, min: Duration, max: Duration) -> Self {
+        self.min_latency = min;
+        self.max_latency = max.max(min);
+        self
+    }
+
+    /// Splits writes to the [Stream] into random packets and returns random parts of the read
+    /// data.
+    pub fn split(mut self, split: bool) -> Self {
+        self.split = split;
+        self
+    }
+
+    /// Sets the probability of dropping the datagram.
+    pub fn drop_rate(mut self, rate: f64) -> Self {
+        self.drop_rate = rate;
+        self
+    }
+
+    /// Sets the probability of duplicating the datagram.
+    pub fn duplicate_rate(mut self, rate: f64) -> Self {
+        self.duplicate_rate = rate;
+        self
+    }
+
+    /// Sets the virtual time when the simulation stops.
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Sets the maximum number of Requests of the side served at the same virtual time.
+    pub fn max_steps(mut self, max: usize) -> Self {
+        self.max_steps = max;
+        self
+    }
+
+    /// Runs the client task against the server task over the simulated [Stream].
+    pub fn run_stream<'cs, 'ss, 'c, 's, ClientTask, ServerTask>(
+        &self,
+        client_io: &Io<'cs, Stream>,
+        client_task: Pin<&'c mut ClientTask>,
+        server_io: &Io<'ss, Stream>,
+        server_task: Pin<&'s mut ServerTask>,
+    ) -> Result<Outcome, Error>
+    where
+        'cs: 'c,
+        'ss: 's,
+        ClientTask: Future<Output = ()>,
+        ServerTask: Future<Output = ()>,
+    {
+        self.run(client_io, client_task, server_io, server_task)
+    }
+
+    /// Runs the client task against the server task over the simulated [Datagram] transport.
+    pub fn run_datagram<'cs, 'ss, 'c, 's, ClientTask, ServerTask>(
+        &self,
+        client_io: &Io<'cs, Datagram>,
+        client_task: Pin<&'c mut ClientTask>,
+        server_io: &Io<'ss, Datagram>,
+        server_task: Pin<&'s mut ServerTask>,
+    ) -> Result<Outcome, Error>
+    where
+        'cs: 'c,
+        'ss: 's,
+        ClientTask: Future<Output = ()>,
+        ServerTask: Future<Output = ()>,
+    {
+        self.run(client_io, client_task, server_io, server_task)
+    }
+
+    fn run<'cs, 'ss, 'c, 's, V, ClientTask, ServerTask>(
+        &self,
+        client_io: &Io<'cs, V>,
+        client_task: Pin<&'c mut ClientTask>,
+        server_io: &Io<'ss, V>,
+        server_task: Pin<&'s mut ServerTask>,
+    ) -> Result<Outcome, Error>
+    where
+        'cs: 'c,
+        'ss: 's,
+        V: Vocabulary,
+        ClientTask: Future<Output = ()>,
+        ServerTask: Future<Output = ()>,
+    {
+        let mut net = Net::new(self);
+        let mut inboxes = [Inbox::default(), Inbox::default()];
+
+        let mut client = Side::new(client_io.start(client_task), &mut net, SERVER);
+        let mut server = Side::new(server_io.start(server_task), &mut net, CLIENT);
+        let mut stopped = false;
+        loop {
+            let [client_inbox, server_inbox] = &mut inboxes;
+            client.run(client_io, &mut net, CLIENT, client_inbox)?;
+            server.run(server_io, &mut net, SERVER, server_inbox)?;
+
+            let active = [!client.finished, !server.finished];
+            let next = [net.next_packet(active), client.until(), server.until()]
+                .into_iter()
+                .flatten()
+                .min();
+            let Some(next) = next else {
+                break;
+            };
+            if next > self.deadline {
+                stopped = true;
+                break;
+            }
+            net.now = next;
+            net.deliver::<V>(&mut inboxes);
+        }
+
+        Ok(Outcome {
+            client: client.end(stopped),
+            server: server.end(stopped),
+            time: net.now,
+        })
+    }
+}
+
+/// The SplitMix64 pseudo random generator
+pub(crate) struct Rng(pub(crate) u64);
+
+impl Rng {
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// The uniform number in the range min..=max
+    fn range(&mut self, min: u64, max: u64) -> u64 {
+        if max <= min {
+            return min;
+        }
+        match (max - min).checked_add(1) {
+            Some(len) => min + self.next() % len,
+            None => self.next(),
+        }
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        let sample = (self.next() >> 11) as f64 / (1u64 << 53) as f64;
+        sample < probability
+    }
+}
+
+enum Payload {
+    Data(Vec<u8>),
+    Close,
+}
+
+/// The packet in flight
+struct Packet {
+    at: Duration,
+    seq: u64,
+    to: usize,
+    payload: Payload,
+}
+
+/// The simulated network between both sides
+struct Net<'c> {
+    config: &'c Simulator,
+    rng: Rng,
+    now: Duration,
+    seq: u64,
+    packets: Vec<Packet>,
+    /// The arrival time of the latest stream packet for each side, to keep the stream ordered
+    last: [Duration; 2],
+}
+
+impl<'c> Net<'c> {
+    fn new(config: &'c Simulator) -> Self {
+        Self {
+            config,
+            rng: Rng(config.seed),
+            now: Duration::ZERO,
+            seq: 0,
+            packets: Vec::new(),
+            last: [Duration::ZERO; 2],
+        }
+    }
+
+    fn latency(&mut self) -> Duration {
+        let min = self.config.min_latency.as_nanos() as u64;
+        let max = self.config.max_latency.as_nanos() as u64;
+        Duration::from_nanos(self.rng.range(min, max))
+    }
+
+    fn push(&mut self, to: usize, at: Duration, payload: Payload) {
+        self.packets.push(Packet {
+            at,
+            seq: self.seq,
+            to,
+            payload,
+        });
+        self.seq += 1;
+    }
+
+    fn push_ordered(&mut self, to: usize, payload: Payload) {
+        let at = (self.now + self.latency()).max(self.last[to]);
+        self.last[to] = at;
+        self.push(to, at, payload);
+    }
+
+    fn send_stream(&mut self, to: usize, mut data: &[u8]) {
+        while !data.is_empty() {
+            let len = if self.config.split {
+                self.rng.range(1, data.len() as u64) as usize
+            } else {
+                data.len()
+            };
+            let (packet, rest) = data.split_at(len);
+            self.push_ordered(to, Payload::Data(packet.to_vec()));
+            data = rest;
+        }
+    }
+
+    fn send_datagram(&mut self, to: usize, data: &[u8]) {
+        if self.rng.chance(self.config.drop_rate) {
+            return;
+        }
+        let copies = 1 + usize::from(self.rng.chance(self.config.duplicate_rate));
+        for _ in 0..copies {
+            let at = self.now + self.latency();
+            self.push(to, at, Payload::Data(data.to_vec()));
+        }
+    }
+
+    fn close(&mut self, to: usize) {
+        self.push_ordered(to, Payload::Close);
+    }
+
+    /// The arrival time of the next packet, packets for finished sides are never received
+    fn next_packet(&self, active: [bool; 2]) -> Option<Duration> {
+        self.packets
+            .iter()
+            .filter(|packet| active[packet.to])
+            .map(|packet| packet.at)
+            .min()
+    }
+
+    /// Delivers packets which arrived until now in order of their arrival
+    fn deliver<V: Vocabulary>(&mut self, inboxes: &mut [Inbox; 2]) {
+        self.packets.sort_by_key(|packet| (packet.at, packet.seq));
+        let arrived = self
+            .packets
+            .iter()
+            .take_while(|packet| packet.at <= self.now)
+            .count();
+        for packet in self.packets.drain(..arrived) {
+            let inbox = &mut inboxes[packet.to];
+            match packet.payload {
+                Payload::Data(data) => V::deliver(inbox, data),
+                Payload::Close => inbox.closed = true,
+            }
+        }
+    }
+}
+
+/// The data received by the side
+#[derive(Default)]
+struct Inbox {
+    stream: VecDeque<u8>,
+    datagrams: VecDeque<Vec<u8>>,
+    closed: bool,
+    read: Vec<u8>,
+}
+
+/// The reason of waiting of the side
+#[derive(Clone, Copy)]
+enum Wait {
+    Read { until: Option<Duration> },
+    Sleep { until: Duration },
+}
+
+impl Wait {
+    fn until(self) -> Option<Duration> {
+        match self {
+            Self::Read { until } => until,
+            Self::Sleep { until } => Some(until),
+        }
+    }
+}
+
+enum Serve<R> {
+    Respond(R),
+    Wait(Wait),
+}
+
+/// The vocabulary supported by the simulator
+trait Vocabulary: Message {
+    /// Serves the Request at the current time of the network
+    fn serve<'b>(
+        request: &Self::Request<'_>,
+        net: &mut Net,
+        side: usize,
+        inbox: &'b mut Inbox,
+    ) -> Serve<Self::Response<'b>>;
+
+    /// The Response when the wait expired
+    fn expired<'b>(wait: Wait) -> Self::Response<'b>;
+
+    /// Stores the received packet
+    fn deliver(inbox: &mut Inbox, data: Vec<u8>);
+}
+
+fn peer(side: usize) -> usize {
+    1 - side
+}
+
+impl Vocabulary for Stream {
+    fn serve<'b>(
+        request: &StreamRequest<'_>,
+        net: &mut Net,
+        side: usize,
+        inbox: &'b mut Inbox,
+    ) -> Serve<StreamResponse<'b>> {
+        match request {
+            StreamRequest::Read { max, timeout } => {
+                assert!(*max > 0, "the stream read of zero bytes");
+                if !inbox.stream.is_empty() {
+                    let len = inbox.stream.len().min(*max);
+                    let len = if net.config.split {
+                        net.rng.range(1, len as u64) as usize
+                    } else {
+                        len
+                    };
+                    inbox.read.clear();
+                    inbox.read.extend(inbox.stream.drain(..len));
+                    Serve::Respond(StreamResponse::Data { data: &inbox.read })
+                } else if inbox.closed {
+                    Serve::Respond(StreamResponse::Closed)
+                } else {
+                    Serve::Wait(Wait::Read {
+                        until: timeout.map(|timeout| net.now + timeout),
+                    })
+                }
+            }
+            StreamRequest::Write { data } => {
+                net.send_stream(peer(side), data);
+                Serve::Respond(StreamResponse::Written)
+            }
+            StreamRequest::Sleep { duration } => Serve::Wait(Wait::Sleep {
+                until: net.now + *duration,
+            }),
+        }
+    }
+
+    fn expired<'b>(wait: Wait) -> StreamResponse<'b> {
+        match wait {
+            Wait::Read { .. } => StreamResponse::Timeout,
+            Wait::Sleep { .. } => StreamResponse::Woken,
+        }
+    }
+
+    fn deliver(inbox: &mut Inbox, data: Vec<u8>) {
+        inbox.stream.extend(data);
+    }
+}
+
+impl Vocabulary for Datagram {
+    fn serve<'b>(
+        request: &DatagramRequest<'_>,
+        net: &mut Net,
+        side: usize,
+        inbox: &'b mut Inbox,
+    ) -> Serve<DatagramResponse<'b>> {
+        match request {
+            DatagramRequest::Recv { timeout } => match inbox.datagrams.pop_front() {
+                Some(data) => {
+                    inbox.read = data;
+                    Serve::Respond(DatagramResponse::Datagram { data: &inbox.read })
+                }
+                None => Serve::Wait(Wait::Read {
+                    until: timeout.map(|timeout| net.now + timeout),
+                }),
+            },
+            DatagramRequest::Send { data } => {
+                net.send_datagram(peer(side), data);
+                Serve::Respond(DatagramResponse::Sent)
+            }
+            DatagramRequest::Sleep { duration } => Serve::Wait(Wait::Sleep {
+                until: net.now + *duration,
+            }),
+        }
+    }
+
+    fn expired<'b>(wait: Wait) -> DatagramResponse<'b> {
+        match wait {
+            Wait::Read { .. } => DatagramResponse::Timeout,
+            Wait::Sleep { .. } => DatagramResponse::Woken,
+        }
+    }
+
+    fn deliver(inbox: &mut Inbox, data: Vec<u8>) {
+        inbox.datagrams.push_back(data);
+    }
+}
+
+/// The state of the side
+struct Side<'a, V: Message, Task> {
+    handler: Option<IoRequest<'a, V, Task>>,
+    wait: Option<Wait>,
+    finished: bool,
+    /// The virtual time of the last served Request and the number of Requests served at it
+    steps: (Duration, usize),
+}
+
+impl<'a, V: Vocabulary, Task: Future<Output = ()>> Side<'a, V, Task> {
+    fn new(handler: Option<IoRequest<'a, V, Task>>, net: &mut Net, peer: usize) -> Self {
+        let finished = handler.is_none();
+        if finished {
+            net.close(peer);
+        }
+        Self {
+            handler,
+            wait: None,
+            finished,
+            steps: (Duration::ZERO, 0),
+        }
+    }
+
+    /// Serves Requests until the side waits
+    fn run(
+        &mut self,
+        io: &Io<'_, V>,
+        net: &mut Net,
+        side: usize,
+        inbox: &mut Inbox,
+    ) -> Result<(), Error> {
+        while let Some(handler) = self.handler.take() {
+            let now = net.now;
+            if self.steps.0 != now {
+                self.steps = (now, 0);
+            }
+            let response = match self.wait {
+                Some(wait @ Wait::Sleep { until }) => (until <= now).then(|| V::expired(wait)),
+                _ => match V::serve(handler.request(), net, side, inbox) {
+                    Serve::Respond(response) => Some(response),
+                    Serve::Wait(wait) => {
+                        // The repeated Read keeps its original deadline
+                        let wait = self.wait.unwrap_or(wait);
+                        self.wait = Some(wait);
+                        wait.until()
+                            .is_some_and(|until| until <= now)
+                            .then(|| V::expired(wait))
+                    }
+                },
+            };
+            let Some(response) = response else {
+                self.handler = Some(handler);
+                return Ok(());
+            };
+            self.steps.1 += 1;
+            if self.steps.1 > net.config.max_steps {
+                return Err(Error::Livelock {
+                    client: side == CLIENT,
+                    time: now,
+                });
+            }
+            self.wait = None;
+            self.handler = io.handle(handler, &response);
+            if self.handler.is_none() {
+                self.finished = true;
+                net.close(peer(side));
+            }
+        }
+        Ok(())
+    }
+
+    fn until(&self) -> Option<Duration> {
+        self.wait.and_then(Wait::until)
+    }
+
+    fn end(&self, stopped: bool) -> End {
+        match (self.finished, stopped) {
+            (true, _) => End::Finished,
+            (false, true) => End::Stopped,
+            (false, false) => End::Blocked,
+        }
+    }
+}
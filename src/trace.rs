@@ -0,0 +1,107 @@
+//! Integration with the `tracing` crate.
+//!
+//! With the `tracing` feature every [Io::start] and [Io::handle] call opens the `asansio` span
+//! at the debug level with fields:
+//!
+//! - `session` - the address of the [Session](crate::Session), which identifies the session
+//!   while it is alive,
+//! - `step` - the number of polls of the Sans task before the call,
+//! - `state` - the label of the state set by [Sans::state](crate::Sans::state), updated after
+//!   the poll.
+//!
+//! The span contains the `finished` event when the Sans task finishes. The Io part with the
+//! [TraceHook] set by [Io::with_hook] records also the Response passed to the Sans task as the
+//! `response` event and the next Request as the `request` event, so protocol steps are
+//! correlated with other traces of the service.
+//!
+//! ```
+//! # use asansio::Session;
+//! # use asansio::trace::DebugTraceHook;
+//! # use std::pin::pin;
+//! #
+//! #[derive(Debug)]
+//! struct Request<'a>(&'a [u8]);
+//! #[derive(Debug)]
+//! struct Response<'a>(&'a [u8]);
+//!
+//! struct Proto;
+//!
+//! asansio::message! {
+//!     Proto {
+//!         type Request<'r> = Request<'r>;
+//!         type Response<'r> = Response<'r>;
+//!     }
+//! }
+//!
+//! let mut session = Session::<Proto>::new();
+//! let (mut sans, io) = session.split();
+//! let io = io.with_hook(&DebugTraceHook);
+//!
+//! let task = pin!(async {
+//!     sans.start(&Request(&[1])).await;
+//! });
+//!
+//! let request = io.start(task).unwrap();
+//! assert!(io.handle(request, &Response(&[2])).is_none());
+//! ```
+//!
+//! [Io::start]: crate::Io::start
+//! [Io::handle]: crate::Io::handle
+//! [Io::with_hook]: crate::Io::with_hook
+
+use crate::Message;
+use core::fmt;
+use core::fmt::Debug;
+
+/// Formats messages recorded in events of the session.
+///
+/// The Io part is `Send`, so the hook is shared between threads.
+pub trait TraceHook<M: Message>: Sync {
+    /// Formats the Request from the Sans part.
+    fn request(&self, request: &M::Request<'_>, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+
+    /// Formats the Response from the Io part.
+    fn response(&self, response: &M::Response<'_>, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+}
+
+/// The [TraceHook] recording the `Debug` output of messages.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DebugTraceHook;
+
+impl<M: Message> TraceHook<M> for DebugTraceHook
+where
+    for<'r> M::Request<'r>: Debug,
+    for<'r> M::Response<'r>: Debug,
+{
+    fn request(&self, request: &M::Request<'_>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        request.fmt(f)
+    }
+
+    fn response(&self, response: &M::Response<'_>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        response.fmt(f)
+    }
+}
+
+/// The Request formatted by the hook
+pub(crate) struct Request<'a, M: Message>(
+    pub(crate) &'a dyn TraceHook<M>,
+    pub(crate) &'a M::Request<'a>,
+);
+
+impl<M: Message> Debug for Request<'_, M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.request(self.1, f)
+    }
+}
+
+/// The Response formatted by the hook
+pub(crate) struct Response<'a, M: Message>(
+    pub(crate) &'a dyn TraceHook<M>,
+    pub(crate) &'a M::Response<'a>,
+);
+
+impl<M: Message> Debug for Response<'_, M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.response(self.1, f)
+    }
+}
@@ -0,0 +1,349 @@
+//! Recording of the Request/Response exchanges of a session.
+//!
+//! The [Recorder] wraps the [Io](crate::Io) or another [Drive] and captures every Request from
+//! the Sans part and every Response from the Io part into a [Transcript]. The messages borrow
+//! their buffers only for a single exchange, so they are converted by the user-provided [Hook]
+//! into owned records. The [DebugHook] records the `Debug` output of the messages. Transcripts
+//! recorded with a hook which could also restore Responses are replayed by the
+//! [replay](crate::replay) module.
+//!
+//! ```
+//! # use asansio::Drive;
+//! # use asansio::Session;
+//! # use asansio::transcript::DebugHook;
+//! # use asansio::transcript::Recorder;
+//! # use std::pin::pin;
+//! #
+//! #[derive(Debug)]
+//! struct Request<'a>(&'a [u8]);
+//! #[derive(Debug)]
+//! struct Response<'a>(&'a [u8]);
+//!
+//! struct Proto;
+//!
+//! asansio::message! {
+//!     Proto {
+//!         type Request<'r> = Request<'r>;
+//!         type Response<'r> = Response<'r>;
+//!     }
+//! }
+//!
+//! let mut session = Session::<Proto>::new();
+//! let (mut sans, io) = session.split();
+//! let mut recorder = Recorder::new(io, DebugHook);
+//!
+//! let task = pin!(async {
+//!     sans.start(&Request(&[1])).await;
+//! });
+//!
+//! let request = recorder.start(task).unwrap();
+//! assert!(recorder.handle(request, &Response(&[2])).is_none());
+//!
+//! let transcript = recorder.into_transcript();
+//! assert_eq!(transcript.len(), 3);
+//! println!("{transcript}");
+//! ```
+
+use crate::Drive;
+use crate::IoRequest;
+use crate::Message;
+use core::fmt;
+use core::fmt::Debug;
+use core::pin::Pin;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+use std::format;
+use std::string::String;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+use std::vec::Vec;
+
+/// Converts borrowed messages into owned records stored in the [Transcript].
+pub trait Hook<M: Message> {
+    /// The record of the Request.
+    type Request;
+
+    /// The record of the Response.
+    type Response;
+
+    /// Records the Request from the Sans part.
+    fn request(&mut self, request: &M::Request<'_>) -> Self::Request;
+
+    /// Records the Response from the Io part.
+    fn response(&mut self, response: &M::Response<'_>) -> Self::Response;
+}
+
+impl<M: Message, H: Hook<M>> Hook<M> for &mut H {
+    type Request = H::Request;
+    type Response = H::Response;
+
+    fn request(&mut self, request: &M::Request<'_>) -> H::Request {
+        (**self).request(request)
+    }
+
+    fn response(&mut self, response: &M::Response<'_>) -> H::Response {
+        (**self).response(response)
+    }
+}
+
+/// The [Hook] recording the `Debug` output of messages.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DebugHook;
+
+impl<M: Message> Hook<M> for DebugHook
+where
+    for<'r> M::Request<'r>: Debug,
+    for<'r> M::Response<'r>: Debug,
+{
+    type Request = String;
+    type Response = String;
+
+    fn request(&mut self, request: &M::Request<'_>) -> String {
+        format!("{request:?}")
+    }
+
+    fn response(&mut self, response: &M::Response<'_>) -> String {
+        format!("{response:?}")
+    }
+}
+
+/// A single recorded event of the session.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event<Req, Resp> {
+    /// The Request sent by the Sans part.
+    Request(Req),
+    /// The Response sent by the Io part.
+    Response(Resp),
+    /// The Sans task finished.
+    Finished,
+    /// The Sans task panicked with the message, if any.
+    Panicked(Option<String>),
+}
+
+/// The order of events recorded by all transcripts
+static ORDER: AtomicU64 = AtomicU64::new(0);
+
+/// The [Event] with its sequence number and time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry<Req, Resp> {
+    /// The sequence number of the event, starting from 0.
+    pub seq: u64,
+    /// The position of the event among events of all transcripts of the process. It increases
+    /// monotonically, unlike the timestamp, so it orders events of many sessions.
+    pub order: u64,
+    /// The time of the event.
+    pub timestamp: SystemTime,
+    /// The recorded event.
+    pub event: Event<Req, Resp>,
+}
+
+impl<Req: Debug, Resp: Debug> fmt::Display for Entry<Req, Resp> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let timestamp = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        write!(
+            f,
+            "{} {}.{:06} ",
+            self.seq,
+            timestamp.as_secs(),
+            timestamp.subsec_micros()
+        )?;
+        match &self.event {
+            Event::Request(request) => write!(f, "> {request:?}"),
+            Event::Response(response) => write!(f, "< {response:?}"),
+            Event::Finished => write!(f, "finished"),
+            Event::Panicked(Some(msg)) => write!(f, "panicked: {msg}"),
+            Event::Panicked(None) => write!(f, "panicked"),
+        }
+    }
+}
+
+/// The recorded events of the session in order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transcript<Req, Resp> {
+    entries: Vec<Entry<Req, Resp>>,
+}
+
+impl<Req, Resp> Transcript<Req, Resp> {
+    /// Creates an empty transcript.
+    pub const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Appends the event with the next sequence number, the next order and the current time.
+    pub fn push(&mut self, event: Event<Req, Resp>) {
+        self.entries.push(Entry {
+            seq: self.entries.len() as u64,
+            order: ORDER.fetch_add(1, Ordering::Relaxed),
+            timestamp: SystemTime::now(),
+            event,
+        });
+    }
+
+    /// Retrieve the recorded entries.
+    pub fn entries(&self) -> &[Entry<Req, Resp>] {
+        &self.entries
+    }
+
+    /// The number of recorded entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if there are no recorded entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<Req, Resp> Default for Transcript<Req, Resp> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Req: Debug, Resp: Debug> fmt::Display for Transcript<Req, Resp> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{entry}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The Io part which records exchanges of the session into the [Transcript].
+///
+/// It wraps the [Io](crate::Io) or another [Drive], so it could replace it in the driver of the
+/// session.
+pub struct Recorder<D: Drive, H: Hook<D::Message>> {
+    io: D,
+    hook: H,
+    transcript: Transcript<H::Request, H::Response>,
+}
+
+impl<D: Drive, H: Hook<D::Message>> Recorder<D, H> {
+    /// Creates the recorder for the Io part with the hook converting messages.
+    pub fn new(io: D, hook: H) -> Self {
+        Self {
+            io,
+            hook,
+            transcript: Transcript::new(),
+        }
+    }
+
+    /// Retrieve the transcript recorded so far.
+    pub fn transcript(&self) -> &Transcript<H::Request, H::Response> {
+        &self.transcript
+    }
+
+    /// Returns the recorded transcript, consuming the recorder.
+    pub fn into_transcript(self) -> Transcript<H::Request, H::Response> {
+        self.transcript
+    }
+
+    /// Returns the Io part and the recorded transcript, consuming the recorder.
+    pub fn into_parts(self) -> (D, Transcript<H::Request, H::Response>) {
+        (self.io, self.transcript)
+    }
+
+    fn record_request<Task: Future<Output = ()> + ?Sized>(
+        &mut self,
+        handler: Option<&IoRequest<D::Message, Task>>,
+    ) {
+        let event = match handler.map(IoRequest::request) {
+            Some(request) => Event::Request(self.hook.request(request)),
+            None => Event::Finished,
+        };
+        self.transcript.push(event);
+    }
+
+    fn record_response(&mut self, response: &<D::Message as Message>::Response<'_>) {
+        let event = Event::Response(self.hook.response(response));
+        self.transcript.push(event);
+    }
+
+    fn record_result<Task: Future<Output = ()> + ?Sized>(
+        &mut self,
+        handler: Result<&Option<IoRequest<D::Message, Task>>, &D::Error>,
+    ) {
+        match handler.map_err(D::task_error) {
+            Ok(handler) => self.record_request(handler.as_ref()),
+            Err(Some(err @ crate::Error::Panicked(_))) => self
+                .transcript
+                .push(Event::Panicked(err.message().map(String::from))),
+            Err(_) => {}
+        }
+    }
+}
+
+impl<D: Drive, H: Hook<D::Message>> Drive for Recorder<D, H> {
+    type Message = D::Message;
+    type Error = D::Error;
+
+    /// The same as [Drive::start], the Request is recorded.
+    fn start<'a, Task>(
+        &mut self,
+        task: Pin<&'a mut Task>,
+    ) -> Option<IoRequest<'a, D::Message, Task>>
+    where
+        Self: 'a,
+        Task: Future<Output = ()> + ?Sized,
+    {
+        let handler = self.io.start(task);
+        self.record_request(handler.as_ref());
+        handler
+    }
+
+    /// The same as [Drive::handle], the Response and the next Request are recorded.
+    fn handle<'a, Task>(
+        &mut self,
+        handler: IoRequest<'a, D::Message, Task>,
+        response: &<D::Message as Message>::Response<'_>,
+    ) -> Option<IoRequest<'a, D::Message, Task>>
+    where
+        Task: Future<Output = ()> + ?Sized,
+    {
+        self.record_response(response);
+        let handler = self.io.handle(handler, response);
+        self.record_request(handler.as_ref());
+        handler
+    }
+
+    /// The same as [Drive::try_start], the Request or the panic is recorded.
+    fn try_start<'a, Task>(
+        &mut self,
+        task: Pin<&'a mut Task>,
+    ) -> Result<Option<IoRequest<'a, D::Message, Task>>, D::Error>
+    where
+        Self: 'a,
+        Task: Future<Output = ()> + ?Sized,
+    {
+        let handler = self.io.try_start(task);
+        self.record_result(handler.as_ref());
+        handler
+    }
+
+    /// The same as [Drive::try_handle], the Response and the next Request or the panic are
+    /// recorded.
+    fn try_handle<'a, Task>(
+        &mut self,
+        handler: IoRequest<'a, D::Message, Task>,
+        response: &<D::Message as Message>::Response<'_>,
+    ) -> Result<Option<IoRequest<'a, D::Message, Task>>, D::Error>
+    where
+        Task: Future<Output = ()> + ?Sized,
+    {
+        self.record_response(response);
+        let handler = self.io.try_handle(handler, response);
+        self.record_result(handler.as_ref());
+        handler
+    }
+
+    fn task_error(err: &D::Error) -> Option<&crate::Error> {
+        D::task_error(err)
+    }
+}
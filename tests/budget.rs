@@ -0,0 +1,131 @@
+use asansio::Drive;
+use asansio::Sans;
+use asansio::Session;
+use asansio::budget::Budget;
+use asansio::budget::Error;
+use asansio::metrics::Meter;
+use asansio::metrics::Stats;
+use std::pin::pin;
+
+enum Request<'a> {
+    ReadPayload,
+    Echo(&'a [u8]),
+}
+
+struct Response<'a>(&'a [u8]);
+
+struct Proto;
+
+asansio::message! {
+    Proto {
+        type Request<'r> = Request<'r>;
+        type Response<'r> = Response<'r>;
+    }
+}
+
+/// Echoes payloads until the empty one, panics on the zero byte
+async fn echo(mut sans: Sans<'_, Proto>) {
+    let mut response = sans.start(&Request::ReadPayload).await;
+    loop {
+        let payload = response.response().0.to_vec();
+        if payload.first() == Some(&0) {
+            panic!("zero byte");
+        }
+        response = if payload.is_empty() {
+            sans.handle(response, &Request::ReadPayload).await
+        } else {
+            sans.handle(response, &Request::Echo(&payload)).await
+        };
+    }
+}
+
+/// Reading or writing a non empty payload is a progress
+fn progress(request: &Request, response: &Response) -> bool {
+    match request {
+        Request::ReadPayload => !response.0.is_empty(),
+        Request::Echo(payload) => !payload.is_empty(),
+    }
+}
+
+#[test]
+fn livelock() {
+    let mut session = Session::<Proto>::new();
+    let (sans, io) = session.split();
+    let mut io = Budget::new(io).idle(2, progress);
+    let mut task = pin!(echo(sans));
+
+    let mut handler = io.try_start(task.as_mut()).unwrap();
+    for payload in [&[][..], &[], &[1], &[], &[], &[]] {
+        handler = io.try_handle(handler.unwrap(), &Response(payload)).unwrap();
+    }
+    // The third empty read in a row
+    let err = io
+        .try_handle(handler.unwrap(), &Response(&[]))
+        .err()
+        .unwrap();
+    assert!(matches!(err, Error::Livelock(2)));
+    assert_eq!(err.to_string(), "session made no progress for 2 steps");
+    assert!(io.is_exceeded());
+    assert_eq!(io.taken(), 7);
+}
+
+#[test]
+fn steps_and_panics() {
+    let mut session = Session::<Proto>::new();
+    let (sans, io) = session.split();
+    let mut io = Budget::new(io).steps(3);
+    let mut task = pin!(echo(sans));
+
+    let mut handler = io.try_start(task.as_mut()).unwrap();
+    for _ in 0..3 {
+        handler = io.try_handle(handler.unwrap(), &Response(&[1])).unwrap();
+    }
+    let err = io
+        .try_handle(handler.unwrap(), &Response(&[1]))
+        .err()
+        .unwrap();
+    assert!(matches!(err, Error::Steps(3)));
+
+    // The exceeded session is terminal
+    assert!(matches!(io.try_start(task.as_mut()), Err(Error::Steps(3))));
+
+    let mut session = Session::<Proto>::new();
+    let (sans, io) = session.split();
+    let mut io = Budget::new(io).steps(3);
+    let mut task = pin!(echo(sans));
+    let handler = io.try_start(task.as_mut()).unwrap();
+    let err = io
+        .try_handle(handler.unwrap(), &Response(&[0]))
+        .err()
+        .unwrap();
+    assert!(matches!(err, Error::Task(_)));
+    assert_eq!(err.to_string(), "Sans task panicked: zero byte");
+}
+
+#[test]
+fn stacked_over_meter() {
+    let mut session = Session::<Proto>::new();
+    let (sans, io) = session.split();
+    let stats = Stats::new(|request: &Request| match request {
+        Request::ReadPayload => "read",
+        Request::Echo(_) => "echo",
+    });
+    let mut io = Budget::new(Meter::new(io, stats)).steps(2);
+    let mut task = pin!(echo(sans));
+
+    let mut handler = io.try_start(task.as_mut()).unwrap();
+    for _ in 0..2 {
+        handler = io.try_handle(handler.unwrap(), &Response(&[1])).unwrap();
+    }
+    let err = io
+        .try_handle(handler.unwrap(), &Response(&[1]))
+        .err()
+        .unwrap();
+    assert!(matches!(err, Error::Steps(2)));
+
+    // The step over the budget is not passed to the meter
+    let stats = io.into_io().into_metrics();
+    assert_eq!(stats.total().exchanges, 2);
+    assert_eq!(stats.kind("read").unwrap().exchanges, 1);
+    assert_eq!(stats.kind("echo").unwrap().exchanges, 1);
+}
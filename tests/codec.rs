@@ -0,0 +1,148 @@
+use asansio::Io;
+use asansio::Sans;
+use asansio::Session;
+use asansio::codec::Decoder;
+use asansio::codec::Encoder;
+use asansio::codec::Error;
+use asansio::codec::Framed;
+use asansio::net::Stream;
+use asansio::net::StreamRequest;
+use asansio::net::StreamResponse;
+use std::pin::Pin;
+use std::pin::pin;
+
+/// Frames with the one byte length prefix
+struct Short;
+
+impl Decoder for Short {
+    type Frame<'a> = &'a [u8];
+    type Error = ();
+
+    fn frame_len(&mut self, buf: &[u8]) -> Result<Option<usize>, ()> {
+        Ok(buf
+            .first()
+            .map(|len| *len as usize + 1)
+            .filter(|len| *len <= buf.len()))
+    }
+
+    fn decode<'a>(&mut self, frame: &'a [u8]) -> Result<&'a [u8], ()> {
+        Ok(&frame[1..])
+    }
+}
+
+impl Encoder<[u8]> for Short {
+    type Error = ();
+
+    fn encoded_len(&mut self, item: &[u8]) -> Result<usize, ()> {
+        u8::try_from(item.len())
+            .map(|len| len as usize + 1)
+            .map_err(|_| ())
+    }
+
+    fn encode(&mut self, item: &[u8], buf: &mut [u8]) {
+        buf[0] = item.len() as u8;
+        buf[1..].copy_from_slice(item);
+    }
+}
+
+/// Answers reads with chunks and then closes the stream, returns written data
+fn drive<T: Future<Output = ()>>(
+    io: &Io<'_, Stream>,
+    task: Pin<&mut T>,
+    chunks: &[&[u8]],
+) -> Vec<u8> {
+    let mut chunks = chunks.iter();
+    let mut written = Vec::new();
+    let mut handler = io.start(task);
+    while let Some(request) = handler {
+        let response = match request.request() {
+            StreamRequest::Read { .. } => match chunks.next() {
+                Some(data) => StreamResponse::Data { data },
+                None => StreamResponse::Closed,
+            },
+            StreamRequest::Write { data } => {
+                written.extend_from_slice(data);
+                StreamResponse::Written
+            }
+            StreamRequest::Sleep { .. } => StreamResponse::Woken,
+        };
+        handler = io.handle(request, &response);
+    }
+    written
+}
+
+/// Echoes frames reversed until the stream is closed, returns the result of the last read
+async fn reverse(mut sans: Sans<'_, Stream>, read_len: usize) -> Result<(), Error<()>> {
+    let (mut read_buf, mut write_buf) = ([0; 16], [0; 16]);
+    let mut framed = Framed::new(&mut sans, &mut read_buf[..read_len], &mut write_buf);
+    let mut frame = [0; 16];
+    while let Some(data) = framed.read(&mut Short).await? {
+        let frame = &mut frame[..data.len()];
+        frame.copy_from_slice(data);
+        frame.reverse();
+        framed.write(&mut Short, frame).await?;
+    }
+    Ok(())
+}
+
+#[test]
+fn reassembled_frames() {
+    let mut session = Session::<Stream>::new();
+    let (sans, io) = session.split();
+    let mut result = None;
+    // Frames split between reads and many frames in a single read
+    let written = drive(
+        &io,
+        pin!(async {
+            result = Some(reverse(sans, 16).await);
+        }),
+        &[b"\x02a", b"b\x01c\x03", b"de", b"f\x00"],
+    );
+    assert_eq!(written, b"\x02ba\x01c\x03fed\x00");
+    assert_eq!(result, Some(Ok(())));
+}
+
+/// Runs the reverse task with the read buffer of the length
+fn reverse_result(chunks: &[&[u8]], read_len: usize) -> Option<Result<(), Error<()>>> {
+    let mut session = Session::<Stream>::new();
+    let (sans, io) = session.split();
+    let mut result = None;
+    let task = async {
+        result = Some(reverse(sans, read_len).await);
+    };
+    drive(&io, pin!(task), chunks);
+    result
+}
+
+#[test]
+fn errors() {
+    assert_eq!(reverse_result(&[b"\x02a"], 16), Some(Err(Error::Closed)));
+    // The next frame does not fit after the compaction
+    assert_eq!(
+        reverse_result(&[b"\x01a\x04", b"bcd"], 4),
+        Some(Err(Error::Overflow))
+    );
+    // The data is longer than the max of the read
+    assert_eq!(
+        reverse_result(&[b"\x01a\x04bc"], 4),
+        Some(Err(Error::Unexpected))
+    );
+}
+
+#[test]
+#[should_panic(expected = "the stream is read by Framed::read")]
+fn request_read() {
+    let mut session = Session::<Stream>::new();
+    let (mut sans, io) = session.split();
+    let task = async {
+        let (mut read_buf, mut write_buf) = ([0; 16], [0; 16]);
+        let mut framed = Framed::new(&mut sans, &mut read_buf, &mut write_buf);
+        framed
+            .request(&StreamRequest::Read {
+                max: 1024,
+                timeout: None,
+            })
+            .await;
+    };
+    drive(&io, pin!(task), &[b"\x01a"]);
+}
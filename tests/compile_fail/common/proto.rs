@@ -0,0 +1,12 @@
+
+pub struct Request<'a>(pub &'a [u8]);
+pub struct Response<'a>(pub &'a [u8]);
+
+pub struct Proto;
+
+asansio::message! {
+    Proto {
+        type Request<'r> = Request<'r>;
+        type Response<'r> = Response<'r>;
+    }
+}
@@ -0,0 +1,15 @@
+use std::cell::Cell;
+
+struct Request<'a>(Cell<&'a [u8]>);
+struct Response<'a>(Cell<&'a [u8]>);
+
+struct Proto;
+
+asansio::message! {
+    Proto {
+        type Request<'r> = Request<'r>;
+        type Response<'r> = Response<'r>;
+    }
+}
+
+fn main() {}
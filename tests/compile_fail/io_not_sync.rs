@@ -0,0 +1,14 @@
+#[path = "common/proto.rs"]
+mod proto;
+
+use asansio::Session;
+use proto::Proto;
+use std::thread;
+
+fn main() {
+    let mut session = Session::<Proto>::new();
+    let (_, io) = session.split();
+    thread::scope(|scope| {
+        scope.spawn(|| drop(&io));
+    });
+}
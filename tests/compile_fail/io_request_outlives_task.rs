@@ -0,0 +1,21 @@
+#[path = "common/proto.rs"]
+mod proto;
+
+use asansio::Session;
+use proto::Proto;
+use proto::Request;
+use proto::Response;
+use std::pin::pin;
+
+fn main() {
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
+
+    let request = {
+        let task = pin!(async {
+            sans.start(&Request(&[1])).await;
+        });
+        io.start(task).unwrap()
+    };
+    io.handle(request, &Response(&[2]));
+}
@@ -0,0 +1,24 @@
+#[path = "common/proto.rs"]
+mod proto;
+
+use asansio::Session;
+use proto::Proto;
+use proto::Request;
+use proto::Response;
+use std::pin::pin;
+
+fn main() {
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
+
+    let task = pin!(async {
+        let response = sans.start(&Request(&[1])).await;
+        sans.handle(response, &Request(&[2])).await;
+    });
+
+    let request = io.start(task).unwrap();
+    let held = request.request();
+    let next = io.handle(request, &Response(&[3]));
+    assert_eq!(held.0, [1]);
+    drop(next);
+}
@@ -0,0 +1,16 @@
+#[path = "common/proto.rs"]
+mod proto;
+
+use asansio::Sans;
+use proto::Proto;
+use proto::Request;
+
+async fn sans_task(mut sans: Sans<'_, Proto>) {
+    let response = sans.start(&Request(&[1])).await;
+    let echo = Request(response.response().0);
+    sans.handle(response, &echo).await;
+}
+
+fn main() {
+    let _ = sans_task;
+}
@@ -0,0 +1,18 @@
+#[path = "common/proto.rs"]
+mod proto;
+
+use asansio::Sans;
+use proto::Proto;
+use proto::Request;
+
+async fn sans_task(mut sans: Sans<'_, Proto>) {
+    let mut request_buf = [1u8; 4];
+    let request = Request(&request_buf);
+    let handle = sans.start(&request);
+    request_buf.fill(2);
+    handle.await;
+}
+
+fn main() {
+    let _ = sans_task;
+}
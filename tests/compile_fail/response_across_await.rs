@@ -0,0 +1,18 @@
+#[path = "common/proto.rs"]
+mod proto;
+
+use asansio::Sans;
+use proto::Proto;
+use proto::Request;
+
+async fn sans_task(mut sans: Sans<'_, Proto>) {
+    let response = sans.start(&Request(&[1])).await;
+    let held = response.response();
+    let next = sans.handle(response, &Request(&[2])).await;
+    assert_eq!(held.0, [3]);
+    drop(next);
+}
+
+fn main() {
+    let _ = sans_task;
+}
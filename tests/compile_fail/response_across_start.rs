@@ -0,0 +1,19 @@
+#[path = "common/proto.rs"]
+mod proto;
+
+use asansio::Sans;
+use proto::Proto;
+use proto::Request;
+
+async fn sans_task(mut sans: Sans<'_, Proto>) {
+    let response = sans.start(&Request(&[1])).await;
+    let response = sans.handle(response, &Request(&[2])).await;
+    let held = response.response();
+    let next = sans.start(&Request(&[3])).await;
+    assert_eq!(held.0, [4]);
+    drop(next);
+}
+
+fn main() {
+    let _ = sans_task;
+}
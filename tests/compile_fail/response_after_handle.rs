@@ -0,0 +1,17 @@
+#[path = "common/proto.rs"]
+mod proto;
+
+use asansio::Sans;
+use proto::Proto;
+use proto::Request;
+
+async fn sans_task(mut sans: Sans<'_, Proto>) {
+    let response = sans.start(&Request(&[1])).await;
+    let next = sans.handle(response, &Request(&[2])).await;
+    let _ = response.response();
+    drop(next);
+}
+
+fn main() {
+    let _ = sans_task;
+}
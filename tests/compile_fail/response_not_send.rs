@@ -0,0 +1,30 @@
+use asansio::Sans;
+use std::cell::Cell;
+
+struct Request<'a>(&'a [u8]);
+struct Response<'a>(&'a Cell<u8>);
+
+struct Proto;
+
+asansio::message! {
+    Proto {
+        type Request<'r> = Request<'r>;
+        type Response<'r> = Response<'r>;
+    }
+}
+
+async fn sans_task(mut sans: Sans<'_, Proto>) {
+    let mut sans_resp = sans.start(&Request(&[0])).await;
+    loop {
+        let payload = [sans_resp.response().0.get()];
+        sans_resp = sans.handle(sans_resp, &Request(&payload)).await;
+    }
+}
+
+fn assert_send<T: Send>(_: T) {}
+
+fn main() {
+    let mut session = asansio::Session::<Proto>::new();
+    let (sans, _) = session.split();
+    assert_send(sans_task(sans));
+}
@@ -0,0 +1,18 @@
+#[path = "common/proto.rs"]
+mod proto;
+
+use asansio::Sans;
+use proto::Proto;
+use proto::Request;
+
+async fn sans_task(mut sans: Sans<'_, Proto>) {
+    let response = sans.start(&Request(&[1])).await;
+    let payload: &[u8] = response.response().0;
+    let next = sans.handle(response, &Request(&[2])).await;
+    assert_eq!(payload, [3]);
+    drop(next);
+}
+
+fn main() {
+    let _ = sans_task;
+}
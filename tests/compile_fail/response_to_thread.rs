@@ -0,0 +1,18 @@
+#[path = "common/proto.rs"]
+mod proto;
+
+use asansio::Sans;
+use proto::Proto;
+use proto::Request;
+use std::thread;
+
+async fn sans_task(mut sans: Sans<'_, Proto>) {
+    let response = sans.start(&Request(&[1])).await;
+    thread::spawn(move || {
+        assert_eq!(response.response().0, [2]);
+    });
+}
+
+fn main() {
+    let _ = sans_task;
+}
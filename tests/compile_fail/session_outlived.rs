@@ -0,0 +1,16 @@
+#[path = "common/proto.rs"]
+mod proto;
+
+use asansio::Io;
+use asansio::Sans;
+use asansio::Session;
+use proto::Proto;
+
+fn split() -> (Sans<'static, Proto>, Io<'static, Proto>) {
+    let mut session = Session::<Proto>::new();
+    session.split()
+}
+
+fn main() {
+    drop(split());
+}
@@ -0,0 +1,12 @@
+#[path = "common/proto.rs"]
+mod proto;
+
+use asansio::Session;
+use proto::Proto;
+
+fn main() {
+    let mut session = Session::<Proto>::new();
+    let (sans_a, io_a) = session.split();
+    let (sans_b, io_b) = session.split();
+    drop((sans_a, io_a, sans_b, io_b));
+}
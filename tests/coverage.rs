@@ -0,0 +1,124 @@
+use asansio::Sans;
+use asansio::Session;
+use asansio::codec::Framed;
+use asansio::codec::length::LengthDelimited;
+use asansio::codec::length::Prefix;
+use asansio::coverage;
+use asansio::coverage::Coverage;
+use asansio::net::Stream;
+use asansio::net::StreamResponse;
+use std::pin::pin;
+
+enum Request {
+    Hello,
+    Next,
+    Overflow,
+}
+
+struct Response(u8);
+
+struct Proto;
+
+asansio::message! {
+    Proto {
+        type Request<'r> = Request;
+        type Response<'r> = Response;
+    }
+}
+
+const HELLO_LINE: u32 = line!() + 5;
+const NEXT_LINE: u32 = line!() + 12;
+
+/// Counts responses until the zero, reports the overflow
+async fn counter(mut sans: Sans<'_, Proto>) {
+    let mut response = sans.start(&Request::Hello).await;
+    let mut count: u8 = 0;
+    while response.response().0 != 0 {
+        let Some(next) = count.checked_add(1) else {
+            sans.handle(response, &Request::Overflow).await;
+            return;
+        };
+        count = next;
+        response = sans.handle(response, &Request::Next).await;
+    }
+}
+
+#[test]
+fn await_points() {
+    let mut session = Session::<Proto>::new();
+    let (sans, io) = session.split();
+    let mut task = pin!(counter(sans));
+
+    let mut lines = Vec::new();
+    let mut handler = io.start(task.as_mut());
+    for response in [1, 1, 1, 0] {
+        let request = handler.unwrap();
+        let location = request.location();
+        assert!(location.file().ends_with("coverage.rs"));
+        lines.push(location.line());
+        handler = io.handle(request, &Response(response));
+    }
+    assert!(handler.is_none());
+    assert_eq!(lines, [HELLO_LINE, NEXT_LINE, NEXT_LINE, NEXT_LINE]);
+
+    // The overflow is never reported by tests
+    let report = coverage::report().file("tests/coverage.rs");
+    let counts: Vec<_> = report
+        .iter()
+        .map(|(location, count)| (location.line(), count))
+        .collect();
+    assert_eq!(counts, [(HELLO_LINE, 1), (NEXT_LINE, 3)]);
+}
+
+const FIRST_READ_LINE: u32 = line!() + 8;
+const SECOND_READ_LINE: u32 = line!() + 8;
+
+/// Reads two frames with the Framed helper
+async fn two_reads(mut sans: Sans<'_, Stream>) {
+    let (mut read_buf, mut write_buf) = ([0; 16], [0; 16]);
+    let mut framed = Framed::new(&mut sans, &mut read_buf, &mut write_buf);
+    let mut codec = LengthDelimited::new().prefix(Prefix::U8);
+    framed.read(&mut codec).await.unwrap();
+    framed.read(&mut codec).await.unwrap();
+}
+
+#[test]
+fn framed_await_points() {
+    let mut session = Session::<Stream>::new();
+    let (sans, io) = session.split();
+    let mut task = pin!(two_reads(sans));
+
+    // The await points are in the Sans task, not in the Framed helper
+    let request = io.start(task.as_mut()).unwrap();
+    let first = request.location();
+    let request = io
+        .handle(request, &StreamResponse::Data { data: b"\x01a" })
+        .unwrap();
+    let second = request.location();
+    assert!(
+        io.handle(request, &StreamResponse::Data { data: b"\x01b" })
+            .is_none()
+    );
+    assert!(first.file().ends_with("coverage.rs"));
+    assert_eq!(first.line(), FIRST_READ_LINE);
+    assert!(second.file().ends_with("coverage.rs"));
+    assert_eq!(second.line(), SECOND_READ_LINE);
+}
+
+#[test]
+fn merge_and_display() {
+    let first = std::panic::Location::caller();
+    let second = std::panic::Location::caller();
+
+    let mut coverage = Coverage::new();
+    assert!(coverage.is_empty());
+    coverage.record(first);
+    let mut other = Coverage::new();
+    other.record(first);
+    other.record(second);
+    coverage.merge(&other);
+
+    assert_eq!(coverage.count(first), 2);
+    assert_eq!(coverage.count(second), 1);
+    assert_eq!(coverage.to_string(), format!("{first} 2\n{second} 1\n"));
+}
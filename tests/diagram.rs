@@ -0,0 +1,190 @@
+use asansio::Drive;
+use asansio::Sans;
+use asansio::Session;
+use asansio::diagram::Diagram;
+use asansio::loopback;
+use asansio::loopback::Action;
+use asansio::loopback::Link;
+use asansio::loopback::Recorded;
+use asansio::loopback::Translate;
+use asansio::net::Stream;
+use asansio::net::StreamRequest;
+use asansio::net::StreamResponse;
+use asansio::transcript::DebugHook;
+use asansio::transcript::Hook;
+use asansio::transcript::Recorder;
+use std::pin::pin;
+
+/// Passes the stream through the link
+struct Pipe;
+
+impl Translate<Stream> for Pipe {
+    fn translate<'a>(
+        &'a mut self,
+        request: &StreamRequest<'_>,
+        link: &'a mut Link<'_>,
+    ) -> Action<StreamResponse<'a>> {
+        match request {
+            StreamRequest::Read { max, .. } if link.available() > 0 => {
+                Action::Respond(StreamResponse::Data {
+                    data: link.read(*max),
+                })
+            }
+            StreamRequest::Read { .. } if link.is_closed() => {
+                Action::Respond(StreamResponse::Closed)
+            }
+            StreamRequest::Read { .. } => Action::Wait,
+            StreamRequest::Write { data } => {
+                link.write(data);
+                Action::Respond(StreamResponse::Written)
+            }
+            StreamRequest::Sleep { .. } => Action::Respond(StreamResponse::Woken),
+        }
+    }
+}
+
+/// Labels of messages with text data
+struct Text;
+
+impl Hook<Stream> for Text {
+    type Request = String;
+    type Response = String;
+
+    fn request(&mut self, request: &StreamRequest<'_>) -> String {
+        match request {
+            StreamRequest::Read { .. } => "read".to_string(),
+            StreamRequest::Write { data } => format!("write {}", String::from_utf8_lossy(data)),
+            StreamRequest::Sleep { duration } => format!("sleep {duration:?}"),
+        }
+    }
+
+    fn response(&mut self, response: &StreamResponse<'_>) -> String {
+        match response {
+            StreamResponse::Data { data } => format!("data {}", String::from_utf8_lossy(data)),
+            response => format!("{response:?}").to_lowercase(),
+        }
+    }
+}
+
+async fn ping(mut sans: Sans<'_, Stream>) {
+    let response = sans.start(&StreamRequest::Write { data: b"ping" }).await;
+    sans.handle(
+        response,
+        &StreamRequest::Read {
+            max: 1024,
+            timeout: None,
+        },
+    )
+    .await;
+}
+
+async fn pong(mut sans: Sans<'_, Stream>) {
+    let response = sans
+        .start(&StreamRequest::Read {
+            max: 1024,
+            timeout: None,
+        })
+        .await;
+    sans.handle(response, &StreamRequest::Write { data: b"pong" })
+        .await;
+}
+
+#[test]
+fn loopback_conversation() {
+    let mut client_session = Session::<Stream>::new();
+    let (client_sans, client_io) = client_session.split();
+    let mut server_session = Session::<Stream>::new();
+    let (server_sans, server_io) = server_session.split();
+
+    let mut client = Recorded::new(Pipe, Text);
+    let mut server = Recorded::new(Pipe, Text);
+    loopback::run(
+        &client_io,
+        pin!(ping(client_sans)),
+        &mut client,
+        &server_io,
+        pin!(pong(server_sans)),
+        &mut server,
+    );
+    let diagram = Diagram::new()
+        .session("Client", "Client Io", client.transcript())
+        .session("Server", "Server Io", server.transcript());
+    assert_eq!(
+        diagram.plantuml(),
+        "@startuml\n\
+         participant \"Client\" as P0\n\
+         participant \"Client Io\" as P1\n\
+         participant \"Server\" as P2\n\
+         participant \"Server Io\" as P3\n\
+         P0 -> P1: write ping\n\
+         P1 --> P0: written\n\
+         P2 -> P3: read\n\
+         P3 --> P2: data ping\n\
+         P2 -> P3: write pong\n\
+         P3 --> P2: written\n\
+         note over P2: finished\n\
+         P0 -> P1: read\n\
+         P1 --> P0: data pong\n\
+         note over P0: finished\n\
+         @enduml\n"
+    );
+}
+
+#[test]
+fn bounded_reads() {
+    let mut client_session = Session::<Stream>::new();
+    let (client_sans, client_io) = client_session.split();
+    let mut server_session = Session::<Stream>::new();
+    let (mut server_sans, server_io) = server_session.split();
+
+    // The pending data is longer than the buffer of the reader
+    let mut reads = Vec::new();
+    loopback::run(
+        &client_io,
+        pin!(ping(client_sans)),
+        &mut Pipe,
+        &server_io,
+        pin!(async {
+            let read = StreamRequest::Read {
+                max: 3,
+                timeout: None,
+            };
+            let mut response = server_sans.start(&read).await;
+            while let StreamResponse::Data { data } = response.response() {
+                reads.push(data.to_vec());
+                response = server_sans.handle(response, &read).await;
+            }
+        }),
+        &mut Pipe,
+    );
+    assert_eq!(reads, [&b"pin"[..], b"g"]);
+}
+
+#[test]
+fn escaped_labels() {
+    let mut session = Session::<Stream>::new();
+    let (mut sans, io) = session.split();
+    let mut recorder = Recorder::new(io, DebugHook);
+
+    let mut task = pin!(async {
+        sans.start(&StreamRequest::Write { data: b"a;b#c" }).await;
+        panic!("stop;\nnow");
+    });
+    let handler = recorder.try_start(task.as_mut()).unwrap().unwrap();
+    assert!(
+        recorder
+            .try_handle(handler, &StreamResponse::Written)
+            .is_err()
+    );
+
+    let diagram = Diagram::new().session("Sans; task", "Io", recorder.transcript());
+    assert_eq!(
+        diagram.mermaid(),
+        "sequenceDiagram\n    \
+         participant P0 as Sans#59; task\n    \
+         participant P1 as Io\n    \
+         P0->>P1: Write { data: [97, 59, 98, 35, 99] }\n    \
+         P1-->>P0: Written\n    \
+         Note over P0: panicked: stop#59;<br>now\n"
+    );
+}
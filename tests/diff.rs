@@ -0,0 +1,214 @@
+#[allow(dead_code)]
+#[path = "../examples/tlv_proto/mod.rs"]
+mod tlv_proto;
+
+use asansio::Sans;
+use asansio::Session;
+use asansio::diff;
+use asansio::diff::Mismatch;
+use asansio::diff::Respond;
+use asansio::transcript::Event;
+use asansio::transcript::Hook;
+use std::collections::VecDeque;
+use std::pin::pin;
+use tlv_proto::ClientProto;
+use tlv_proto::ClientRequest;
+use tlv_proto::ClientResponse;
+
+/// Returns the end of the varint length and the end of the packet, if it is complete
+fn varint_packet(read: &[u8]) -> Option<(usize, usize)> {
+    let (mut len, mut shift, mut idx) = (0, 0, 1);
+    loop {
+        let byte = *read.get(idx)?;
+        len |= ((byte & 0x7f) as usize) << shift;
+        idx += 1;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    (read.len() >= idx + len).then_some((idx, idx + len))
+}
+
+/// The rewrite of the TLV client with varint lengths
+async fn varint_client(mut sans: Sans<'_, ClientProto>) {
+    let mut read = Vec::new();
+    let mut consumed = 0;
+    let mut write = Vec::new();
+
+    let mut response = sans.start(&ClientRequest::ReadPayload).await;
+    loop {
+        let request = match response.response() {
+            ClientResponse::ReadPayload { payload } => {
+                read.drain(..consumed);
+                read.extend_from_slice(payload);
+                match varint_packet(&read) {
+                    Some((start, end)) => {
+                        consumed = end;
+                        ClientRequest::Read {
+                            tag: read[0],
+                            val: &read[start..end],
+                        }
+                    }
+                    None => {
+                        consumed = 0;
+                        ClientRequest::ReadPayload
+                    }
+                }
+            }
+            ClientResponse::Write { tag, val } => {
+                write.clear();
+                write.push(*tag);
+                let mut len = val.len();
+                while len >= 0x80 {
+                    write.push(len as u8 | 0x80);
+                    len >>= 7;
+                }
+                write.push(len as u8);
+                write.extend_from_slice(val);
+                ClientRequest::WritePayload { payload: &write }
+            }
+        };
+        response = sans.handle(response, &request).await;
+    }
+}
+
+fn same(left: &ClientRequest<'_>, right: &ClientRequest<'_>) -> bool {
+    match (left, right) {
+        (ClientRequest::ReadPayload, ClientRequest::ReadPayload) => true,
+        (
+            ClientRequest::WritePayload { payload: left },
+            ClientRequest::WritePayload { payload: right },
+        ) => left == right,
+        (
+            ClientRequest::Read {
+                tag: left_tag,
+                val: left_val,
+            },
+            ClientRequest::Read {
+                tag: right_tag,
+                val: right_val,
+            },
+        ) => (left_tag, left_val) == (right_tag, right_val),
+        _ => false,
+    }
+}
+
+enum Step {
+    Write(u8, Vec<u8>),
+    Payload(Vec<u8>),
+}
+
+/// Responds with steps to reads of the payload
+struct Script {
+    steps: VecDeque<Step>,
+    step: Step,
+}
+
+impl Script {
+    fn new(steps: impl IntoIterator<Item = Step>) -> Self {
+        Self {
+            steps: steps.into_iter().collect(),
+            step: Step::Payload(Vec::new()),
+        }
+    }
+}
+
+impl Respond<ClientProto> for Script {
+    fn respond<'a>(&'a mut self, request: &ClientRequest<'_>) -> Option<ClientResponse<'a>> {
+        self.step = match request {
+            ClientRequest::ReadPayload => self.steps.pop_front()?,
+            _ => Step::Payload(Vec::new()),
+        };
+        Some(match &self.step {
+            Step::Write(tag, val) => ClientResponse::Write { tag: *tag, val },
+            Step::Payload(payload) => ClientResponse::ReadPayload { payload },
+        })
+    }
+}
+
+/// Records kinds of Requests with lengths of their data
+struct Lengths;
+
+impl Hook<ClientProto> for Lengths {
+    type Request = String;
+    type Response = ();
+
+    fn request(&mut self, request: &ClientRequest<'_>) -> String {
+        match request {
+            ClientRequest::ReadPayload => "ReadPayload".to_string(),
+            ClientRequest::WritePayload { payload } => format!("WritePayload {}", payload.len()),
+            ClientRequest::Read { tag, val } => format!("Read {tag} {}", val.len()),
+            ClientRequest::Error { err } => format!("Error {err}"),
+        }
+    }
+
+    fn response(&mut self, _: &ClientResponse<'_>) {}
+}
+
+fn compare(steps: impl IntoIterator<Item = Step>) -> Result<usize, Mismatch<String, ()>> {
+    let mut left_session = Session::<ClientProto>::new();
+    let (left_sans, left_io) = left_session.split();
+    let mut right_session = Session::<ClientProto>::new();
+    let (right_sans, right_io) = right_session.split();
+
+    diff::run(
+        &left_io,
+        pin!(tlv_proto::run_client(left_sans)),
+        &right_io,
+        pin!(varint_client(right_sans)),
+        &mut Script::new(steps),
+        same,
+        &mut Lengths,
+    )
+}
+
+#[test]
+fn common_subset() {
+    let steps = (0..128).flat_map(|len| {
+        let val = vec![len as u8; len];
+        let mut packet = vec![7, len as u8];
+        packet.extend_from_slice(&val);
+        let (first, second) = packet.split_at(packet.len() / 2);
+        [
+            Step::Write(7, val),
+            Step::Payload(first.to_vec()),
+            Step::Payload(second.to_vec()),
+        ]
+    });
+    // Every value is written with two Responses and read back with three Responses
+    assert_eq!(compare(steps), Ok(128 * 5));
+}
+
+#[test]
+fn long_value() {
+    let steps = [Step::Write(1, vec![0; 100]), Step::Write(1, vec![0; 200])];
+    assert_eq!(
+        compare(steps),
+        Err(Mismatch {
+            step: 3,
+            left: Event::Request("WritePayload 202".to_string()),
+            right: Event::Request("WritePayload 203".to_string()),
+        })
+    );
+
+    let mut left_session = Session::<ClientProto>::new();
+    let (left_sans, left_io) = left_session.split();
+    let mut right_session = Session::<ClientProto>::new();
+    let (_, right_io) = right_session.split();
+
+    let mismatch = diff::run(
+        &left_io,
+        pin!(tlv_proto::run_client(left_sans)),
+        &right_io,
+        pin!(async {}),
+        &mut Script::new([]),
+        same,
+        &mut Lengths,
+    )
+    .unwrap_err();
+    assert_eq!(
+        mismatch.to_string(),
+        "tasks differ after 0 responses: left Request(\"ReadPayload\"), right Finished"
+    );
+}
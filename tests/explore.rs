@@ -0,0 +1,105 @@
+use asansio::Sans;
+use asansio::explore::Explorer;
+use asansio::explore::Report;
+use asansio::explore::Violation;
+
+#[derive(Debug)]
+enum Request {
+    Balance(u8),
+    Closed,
+}
+
+enum Response {
+    Deposit,
+    Withdraw,
+    Close,
+}
+
+struct Account;
+
+asansio::message! {
+    Account {
+        type Request<'r> = Request;
+        type Response<'r> = Response;
+    }
+}
+
+/// Keeps the balance, the withdraw from the empty account panics on the underflow
+async fn account(mut sans: Sans<'_, Account>) {
+    let mut balance: u8 = 0;
+    let mut response = sans.start(&Request::Balance(balance)).await;
+    loop {
+        match response.response() {
+            Response::Deposit => balance += 1,
+            Response::Withdraw => balance = balance.checked_sub(1).expect("empty account"),
+            Response::Close => break,
+        }
+        response = sans.handle(response, &Request::Balance(balance)).await;
+    }
+    sans.handle(response, &Request::Closed).await;
+}
+
+#[test]
+fn minimal_invariant_counterexample() {
+    let counterexample = Explorer::<Account>::new(vec![Response::Deposit, Response::Close])
+        .depth(6)
+        .invariant("balance below 3", |request| {
+            !matches!(request, Request::Balance(3..))
+        })
+        .describe(|request| format!("{request:?}"))
+        .run(|sans| Box::pin(account(sans)))
+        .unwrap_err();
+
+    assert_eq!(counterexample.responses, [0, 0, 0]);
+    assert_eq!(
+        counterexample.violation,
+        Violation::Invariant("balance below 3".to_string())
+    );
+    assert_eq!(
+        counterexample.requests,
+        ["Balance(0)", "Balance(1)", "Balance(2)", "Balance(3)"]
+    );
+    assert_eq!(
+        counterexample.to_string(),
+        "invariant `balance below 3` violated after responses [0, 0, 0]\n\
+         > Balance(0)\n< 0\n> Balance(1)\n< 0\n> Balance(2)\n< 0\n> Balance(3)"
+    );
+}
+
+#[test]
+fn panic_counterexample() {
+    let counterexample = Explorer::<Account>::new(vec![Response::Deposit, Response::Withdraw])
+        .depth(6)
+        .run(|sans| Box::pin(account(sans)))
+        .unwrap_err();
+
+    // The shortest panicking sequence starts with the withdraw, the first one of them in the
+    // order of the alphabet is the only one
+    assert_eq!(counterexample.responses, [1]);
+    assert_eq!(
+        counterexample.violation,
+        Violation::Panicked(Some("empty account".to_string()))
+    );
+    assert!(counterexample.requests.is_empty());
+}
+
+#[test]
+fn exhaustive_report() {
+    let report = Explorer::<Account>::new(vec![Response::Deposit, Response::Close])
+        .depth(3)
+        .invariant("open account", |request| {
+            matches!(request, Request::Balance(..) | Request::Closed)
+        })
+        .run(|sans| Box::pin(account(sans)))
+        .unwrap();
+
+    // Every Close is followed by the Closed Request, the next Response finishes the task
+    assert_eq!(
+        report,
+        Report {
+            runs: 11,
+            finished: 4,
+            bounded: 2,
+        }
+    );
+}
@@ -0,0 +1,181 @@
+#[allow(dead_code)]
+#[path = "../examples/tlv_proto/mod.rs"]
+mod tlv_proto;
+
+use asansio::Sans;
+use asansio::fuzz::Fuzzer;
+use asansio::net::Stream;
+use asansio::net::StreamRequest;
+use asansio::net::StreamResponse;
+use asansio::prop::Source;
+use asansio::prop::Strategy;
+use std::panic;
+use tlv_proto::ClientProto;
+use tlv_proto::ClientRequest;
+use tlv_proto::ClientResponse;
+
+/// Writes arbitrary values and reads arbitrary payloads
+#[derive(Default)]
+struct Client(Vec<u8>);
+
+impl Strategy<ClientProto> for Client {
+    fn respond<'a>(
+        &'a mut self,
+        source: &mut Source,
+        request: &ClientRequest<'_>,
+    ) -> Result<Option<ClientResponse<'a>>, String> {
+        if !matches!(request, ClientRequest::ReadPayload) {
+            return Ok(Some(ClientResponse::ReadPayload { payload: &[] }));
+        }
+        if source.bool() {
+            let tag = source.below(256) as u8;
+            self.0 = source.bytes(0..=255);
+            return Ok(Some(ClientResponse::Write { tag, val: &self.0 }));
+        }
+        self.0 = source.bytes(1..=16);
+        Ok(Some(ClientResponse::ReadPayload { payload: &self.0 }))
+    }
+}
+
+#[test]
+fn arbitrary_input() {
+    let mut state = 1_u32;
+    for len in 0..300 {
+        let data: Vec<u8> = (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        Fuzzer::new().run(
+            &data,
+            |sans| Box::pin(tlv_proto::run_client(sans)),
+            Client::default(),
+        );
+    }
+}
+
+/// Reads the stream, the zero byte is reserved
+async fn reserved(mut sans: Sans<'_, Stream>) {
+    let read = StreamRequest::Read {
+        max: 4,
+        timeout: None,
+    };
+    let mut response = sans.start(&read).await;
+    loop {
+        match response.response() {
+            StreamResponse::Data { data } if data.contains(&0) => unreachable!("reserved byte"),
+            StreamResponse::Data { .. } | StreamResponse::Timeout => {}
+            _ => return,
+        }
+        response = sans.handle(response, &read).await;
+    }
+}
+
+/// Reads chunks or times out for the zero choice
+#[derive(Default)]
+struct Chunks(Vec<u8>);
+
+impl Strategy<Stream> for Chunks {
+    fn respond<'a>(
+        &'a mut self,
+        source: &mut Source,
+        _: &StreamRequest<'_>,
+    ) -> Result<Option<StreamResponse<'a>>, String> {
+        match source.below(3) {
+            0 => Ok(Some(StreamResponse::Timeout)),
+            1 => {
+                self.0 = source.bytes(1..=4);
+                Ok(Some(StreamResponse::Data { data: &self.0 }))
+            }
+            _ => Ok(Some(StreamResponse::Closed)),
+        }
+    }
+}
+
+fn crash(data: &[u8]) -> Option<String> {
+    panic::catch_unwind(|| {
+        Fuzzer::new()
+            .idle(10)
+            .run(data, |sans| Box::pin(reserved(sans)), Chunks::default())
+    })
+    .err()
+    .map(|payload| match payload.downcast::<String>() {
+        Ok(msg) => *msg,
+        Err(payload) => payload.downcast_ref::<&str>().unwrap().to_string(),
+    })
+}
+
+#[test]
+fn crashes() {
+    // The empty or exhausted input and the closed stream are not crashes
+    assert_eq!(crash(&[]), None);
+    assert_eq!(crash(&[1, 1, 5, 2]), None);
+    assert_eq!(crash(&[2, 0]), None);
+    assert_eq!(crash(&[1, 3, 5]), None);
+
+    assert_eq!(
+        crash(&[1, 1, 5, 1, 1, 1, 7, 0]),
+        Some("internal error: entered unreachable code: reserved byte".to_string())
+    );
+}
+
+/// Times out without drawing choices
+struct Timeouts;
+
+impl Strategy<Stream> for Timeouts {
+    fn respond<'a>(
+        &'a mut self,
+        _: &mut Source,
+        _: &StreamRequest<'_>,
+    ) -> Result<Option<StreamResponse<'a>>, String> {
+        Ok(Some(StreamResponse::Timeout))
+    }
+}
+
+#[test]
+fn infinite_request_loop() {
+    // Timeouts consume the input by their choices
+    assert_eq!(crash(&[0; 10]), None);
+
+    let msg = panic::catch_unwind(|| {
+        Fuzzer::new()
+            .idle(10)
+            .run(&[1, 2, 3], |sans| Box::pin(reserved(sans)), Timeouts)
+    })
+    .unwrap_err();
+    assert_eq!(
+        msg.downcast_ref::<String>().unwrap(),
+        "infinite request loop: 11 Responses without consuming the input"
+    );
+}
+
+/// Fails the property of the closed stream
+struct Unclosed;
+
+impl Strategy<Stream> for Unclosed {
+    fn respond<'a>(
+        &'a mut self,
+        source: &mut Source,
+        _: &StreamRequest<'_>,
+    ) -> Result<Option<StreamResponse<'a>>, String> {
+        if source.bool() {
+            return Err("stream closed".to_string());
+        }
+        Ok(Some(StreamResponse::Timeout))
+    }
+}
+
+#[test]
+fn failed_property() {
+    let msg = panic::catch_unwind(|| {
+        Fuzzer::new().run(&[0, 1], |sans| Box::pin(reserved(sans)), Unclosed)
+    })
+    .unwrap_err();
+    assert_eq!(
+        msg.downcast_ref::<String>().unwrap(),
+        "property failed: stream closed"
+    );
+}
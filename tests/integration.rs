@@ -1,3 +1,4 @@
+use asansio::Session;
 use core::pin::pin;
 
 #[test]
@@ -5,7 +6,17 @@ fn no_response() {
     struct Request;
     struct Response;
 
-    let (_, io) = asansio::new::<Request, Response>();
+    struct Proto;
+
+    asansio::message! {
+        Proto {
+            type Request<'r> = Request;
+            type Response<'r> = Response;
+        }
+    }
+
+    let mut session = Session::<Proto>::new();
+    let (_, io) = session.split();
 
     let task = pin!(async {});
     assert!(io.start(task).is_none());
@@ -16,15 +27,25 @@ fn single_call() {
     struct Request;
     struct Response;
 
-    let (sans, io) = asansio::new::<Request, Response>();
+    struct Proto;
+
+    asansio::message! {
+        Proto {
+            type Request<'r> = Request;
+            type Response<'r> = Response;
+        }
+    }
+
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
 
     let task = pin!(async {
         let response = sans.start(&Request).await;
-        assert!(matches!(response.response(), Some(&Response)));
+        let &Response = response.response();
     });
 
     let request = io.start(task).unwrap();
-    assert!(matches!(request.request(), Some(&Request)));
+    let &Request = request.request();
 
     assert!(io.handle(request, &Response).is_none());
 }
@@ -34,25 +55,31 @@ fn send_owned_payload() {
     struct Request([u8; 10]);
     struct Response([u8; 20]);
 
-    let (sans, io) = asansio::new::<Request, Response>();
+    struct Proto;
+
+    asansio::message! {
+        Proto {
+            type Request<'r> = Request;
+            type Response<'r> = Response;
+        }
+    }
+
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
 
     let task = pin!(async {
         let response = sans.start(&Request([1; 10])).await;
-        assert!(matches!(response.response(), Some(&Response(_))));
-        assert_eq!(response.response().unwrap().0, [2; 20]);
+        assert_eq!(response.response().0, [2; 20]);
 
         let response = sans.handle(response, &Request([3; 10])).await;
-        assert!(matches!(response.response(), Some(&Response(_))));
-        assert_eq!(response.response().unwrap().0, [4; 20]);
+        assert_eq!(response.response().0, [4; 20]);
     });
 
     let request = io.start(task).unwrap();
-    assert!(matches!(request.request(), Some(&Request(_))));
-    assert_eq!(request.request().unwrap().0, [1; 10]);
+    assert_eq!(request.request().0, [1; 10]);
 
     let request = io.handle(request, &Response([2; 20])).unwrap();
-    assert!(matches!(request.request(), Some(&Request(_))));
-    assert_eq!(request.request().unwrap().0, [3; 10]);
+    assert_eq!(request.request().0, [3; 10]);
 
     assert!(io.handle(request, &Response([4; 20])).is_none());
 }
@@ -62,45 +89,49 @@ fn send_borrowed_payload() {
     struct Request<'a>(&'a [u8]);
     struct Response<'a>(&'a [u8]);
 
-    let (sans, io) = asansio::new::<Request, Response>();
+    struct Proto;
+
+    asansio::message! {
+        Proto {
+            type Request<'r> = Request<'r>;
+            type Response<'r> = Response<'r>;
+        }
+    }
+
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
 
     let task = pin!(async {
         let mut request_buf = vec![0u8; 10];
 
         request_buf.fill(1);
         let response = sans.start(&Request(&request_buf)).await;
-        assert!(matches!(response.response(), Some(&Response(_))));
-        assert_eq!(response.response().unwrap().0, [2; 20]);
+        assert_eq!(response.response().0, [2; 20]);
 
         request_buf.fill(3);
         let response = sans.handle(response, &Request(&request_buf)).await;
-        assert!(matches!(response.response(), Some(&Response(_))));
-        assert_eq!(response.response().unwrap().0, [4; 20]);
+        assert_eq!(response.response().0, [4; 20]);
 
         drop(request_buf);
         let mut request_buf = vec![0u8; 10];
 
         request_buf.fill(5);
         let response = sans.handle(response, &Request(&request_buf)).await;
-        assert!(matches!(response.response(), Some(&Response(_))));
-        assert_eq!(response.response().unwrap().0, [6; 20]);
+        assert_eq!(response.response().0, [6; 20]);
     });
 
     let request = io.start(task).unwrap();
-    assert!(matches!(request.request(), Some(&Request(_))));
-    assert_eq!(request.request().unwrap().0, [1; 10]);
+    assert_eq!(request.request().0, [1; 10]);
 
     let mut response_buf = vec![0; 20];
 
     response_buf.fill(2);
     let request = io.handle(request, &Response(&response_buf)).unwrap();
-    assert!(matches!(request.request(), Some(&Request(_))));
-    assert_eq!(request.request().unwrap().0, [3; 10]);
+    assert_eq!(request.request().0, [3; 10]);
 
     response_buf.fill(4);
     let request = io.handle(request, &Response(&response_buf)).unwrap();
-    assert!(matches!(request.request(), Some(&Request(_))));
-    assert_eq!(request.request().unwrap().0, [5; 10]);
+    assert_eq!(request.request().0, [5; 10]);
 
     drop(response_buf);
     let mut response_buf = vec![0; 20];
@@ -108,3 +139,80 @@ fn send_borrowed_payload() {
     response_buf.fill(6);
     assert!(io.handle(request, &Response(&response_buf)).is_none());
 }
+
+#[test]
+fn short_lived_payload() {
+    struct Request<'a>(&'a [u8]);
+    struct Response<'a>(&'a [u8]);
+
+    struct Proto;
+
+    asansio::message! {
+        Proto {
+            type Request<'r> = Request<'r>;
+            type Response<'r> = Response<'r>;
+        }
+    }
+
+    async fn sans_task(mut sans: asansio::Sans<'_, Proto>) {
+        let mut response = sans.start(&Request(&[0])).await;
+        for idx in 1..3 {
+            let request_buf = response.response().0.to_vec();
+            assert_eq!(request_buf, vec![idx; idx as usize + 1]);
+            response = sans.handle(response, &Request(&request_buf)).await;
+        }
+        assert_eq!(response.response().0, [3; 4]);
+    }
+
+    let mut session = Session::<Proto>::new();
+    let (sans, io) = session.split();
+
+    let task = pin!(sans_task(sans));
+
+    let mut request = io.start(task);
+    for idx in 1..4 {
+        let response_buf = vec![idx; request.as_ref().unwrap().request().0.len() + 1];
+        request = io.handle(request.unwrap(), &Response(&response_buf));
+    }
+    assert!(request.is_none());
+}
+
+#[test]
+fn boxed_sub_future() {
+    struct Request(u8);
+    struct Response(u8);
+
+    struct Proto;
+
+    asansio::message! {
+        Proto {
+            type Request<'r> = Request;
+            type Response<'r> = Response;
+        }
+    }
+
+    /// Exchanges two Requests, it is boxed by the caller
+    async fn sub(sans: &mut asansio::Sans<'_, Proto>) -> u8 {
+        let response = sans.start(&Request(1)).await;
+        let first = response.response().0;
+        let response = sans.handle(response, &Request(2)).await;
+        first + response.response().0
+    }
+
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
+
+    let mut sum = 0;
+    {
+        let task = pin!(async {
+            sum = Box::pin(sub(&mut sans)).await;
+        });
+
+        let request = io.start(task).unwrap();
+        assert_eq!(request.request().0, 1);
+        let request = io.handle(request, &Response(10)).unwrap();
+        assert_eq!(request.request().0, 2);
+        assert!(io.handle(request, &Response(20)).is_none());
+    }
+    assert_eq!(sum, 30);
+}
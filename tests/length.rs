@@ -0,0 +1,96 @@
+use asansio::codec::Decoder;
+use asansio::codec::Encoder;
+use asansio::codec::length::Endian;
+use asansio::codec::length::LengthDelimited;
+use asansio::codec::length::LengthError;
+use asansio::codec::length::Prefix;
+
+fn encode(codec: &mut LengthDelimited, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0; codec.encoded_len(payload).unwrap()];
+    codec.encode(payload, &mut frame);
+    frame
+}
+
+#[test]
+fn prefixes() {
+    let payload = [7; 300];
+    let cases = [
+        (Prefix::U8, Endian::Big, &payload[..3], &[3][..]),
+        (Prefix::U16, Endian::Big, &payload[..], &[1, 44]),
+        (Prefix::U16, Endian::Little, &payload[..], &[44, 1]),
+        (Prefix::U32, Endian::Big, &payload[..], &[0, 0, 1, 44]),
+        (Prefix::U32, Endian::Little, &payload[..], &[44, 1, 0, 0]),
+        (Prefix::Varint, Endian::Big, &payload[..3], &[3]),
+        (Prefix::Varint, Endian::Big, &payload[..], &[0xac, 0x02]),
+    ];
+    for (prefix, endian, payload, header) in cases {
+        let mut codec = LengthDelimited::new().prefix(prefix).endian(endian);
+        let frame = encode(&mut codec, payload);
+        assert_eq!(&frame[..header.len()], header);
+
+        // Incomplete frames wait for more data, trailing data is not the part of the frame
+        for len in 0..frame.len() {
+            assert_eq!(codec.frame_len(&frame[..len]), Ok(None));
+        }
+        let mut buf = frame.clone();
+        buf.push(0);
+        assert_eq!(codec.frame_len(&buf), Ok(Some(frame.len())));
+        assert_eq!(codec.decode(&frame), Ok(payload));
+    }
+}
+
+#[test]
+fn adjustment() {
+    // The prefix counts itself
+    let mut codec = LengthDelimited::new().prefix(Prefix::U8).adjustment(-1);
+    assert_eq!(encode(&mut codec, b"ab"), b"\x03ab");
+    assert_eq!(codec.decode(b"\x03ab"), Ok(&b"ab"[..]));
+    assert_eq!(codec.frame_len(b"\x00"), Err(LengthError::Invalid));
+    assert_eq!(
+        codec.encoded_len(&[0; 255]),
+        Err(LengthError::TooLong { len: 255, max: 254 })
+    );
+
+    // The prefix does not count the checksum after the payload
+    let mut codec = LengthDelimited::new().prefix(Prefix::U8).adjustment(1);
+    assert_eq!(encode(&mut codec, b"ab!"), b"\x02ab!");
+    assert_eq!(codec.frame_len(b"\x02ab!"), Ok(Some(4)));
+    assert_eq!(codec.encoded_len(b""), Err(LengthError::Invalid));
+}
+
+#[test]
+fn limits() {
+    let mut codec = LengthDelimited::new().max_frame_len(16);
+    // The huge frame is rejected before its payload is read
+    assert_eq!(
+        codec.frame_len(&[0, 0, 1, 0]),
+        Err(LengthError::TooLong { len: 256, max: 16 })
+    );
+    assert_eq!(
+        codec.encoded_len(&[0; 17]),
+        Err(LengthError::TooLong { len: 17, max: 16 })
+    );
+    assert_eq!(
+        LengthError::TooLong { len: 17, max: 16 }.to_string(),
+        "frame of 17 bytes exceeds the maximum of 16 bytes"
+    );
+
+    let mut codec = LengthDelimited::new().prefix(Prefix::U8);
+    assert_eq!(
+        codec.encoded_len(&[0; 256]),
+        Err(LengthError::TooLong { len: 256, max: 255 })
+    );
+
+    // Varints longer than 64 bits are malformed
+    let mut codec = LengthDelimited::new().prefix(Prefix::Varint);
+    assert_eq!(codec.frame_len(&[0x80; 10]), Err(LengthError::Invalid));
+    assert_eq!(codec.frame_len(&[0x80; 9]), Ok(None));
+
+    // The unlimited length of the payload overflows with the prefix
+    let mut codec = LengthDelimited::new()
+        .prefix(Prefix::Varint)
+        .max_frame_len(usize::MAX);
+    let header = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01];
+    assert_eq!(codec.frame_len(&header), Err(LengthError::Invalid));
+    assert_eq!(codec.decode(&header), Err(LengthError::Invalid));
+}
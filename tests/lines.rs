@@ -0,0 +1,221 @@
+use asansio::Io;
+use asansio::Sans;
+use asansio::Session;
+use asansio::codec::Chunk;
+use asansio::codec::Decoder;
+use asansio::codec::Encoder;
+use asansio::codec::Error;
+use asansio::codec::Framed;
+use asansio::codec::lines::Delimiter;
+use asansio::codec::lines::LineError;
+use asansio::codec::lines::Lines;
+use asansio::net::Stream;
+use asansio::net::StreamRequest;
+use asansio::net::StreamResponse;
+use std::pin::Pin;
+use std::pin::pin;
+
+/// Decodes all complete lines in the buffer
+fn decode_all<'a>(codec: &mut Lines, mut buf: &'a [u8]) -> Result<Vec<&'a [u8]>, LineError> {
+    let mut lines = Vec::new();
+    while let Some(len) = codec.frame_len(buf)? {
+        lines.push(codec.decode(&buf[..len])?);
+        buf = &buf[len..];
+    }
+    Ok(lines)
+}
+
+/// Encodes the line
+fn encode(codec: &mut Lines, line: &str) -> Result<Vec<u8>, LineError> {
+    let mut buf = vec![0; codec.encoded_len(line)?];
+    codec.encode(line, &mut buf);
+    Ok(buf)
+}
+
+#[test]
+fn delimiters() {
+    let buf = b"a\nb\r\nc\r\rd\re||f\n";
+    let cases = [
+        (
+            Delimiter::Any,
+            vec![&b"a"[..], b"b", b"c\r\rd\re||f"],
+            &b"a\r\n"[..],
+        ),
+        (Delimiter::Lf, vec![b"a", b"b\r", b"c\r\rd\re||f"], b"a\n"),
+        (Delimiter::CrLf, vec![b"a\nb"], b"a\r\n"),
+        (Delimiter::Bytes(b"||"), vec![b"a\nb\r\nc\r\rd\re"], b"a||"),
+    ];
+    for (delimiter, lines, encoded) in cases {
+        let mut codec = Lines::new().delimiter(delimiter);
+        assert_eq!(decode_all(&mut codec, buf), Ok(lines));
+        assert_eq!(encode(&mut codec, "a"), Ok(encoded.to_vec()));
+    }
+
+    // The line split in the middle of the delimiter
+    let mut codec = Lines::new().delimiter(Delimiter::CrLf);
+    assert_eq!(codec.frame_len(b"ab\r"), Ok(None));
+    assert_eq!(codec.frame_len(b"ab\r\n"), Ok(Some(4)));
+}
+
+#[test]
+fn errors() {
+    let mut codec = Lines::new().max_line_len(3);
+    assert_eq!(
+        decode_all(&mut codec, b"abc\r\nabc\r"),
+        Ok(vec![&b"abc"[..]])
+    );
+    assert_eq!(
+        codec.frame_len(b"abcd\n"),
+        Err(LineError::TooLong { len: 4, max: 3 })
+    );
+    // The incomplete line is rejected early
+    assert_eq!(
+        codec.frame_len(b"abcde"),
+        Err(LineError::TooLong { len: 4, max: 3 })
+    );
+    assert_eq!(
+        encode(&mut codec, "abcd"),
+        Err(LineError::TooLong { len: 4, max: 3 })
+    );
+    assert_eq!(encode(&mut codec, "a\nb"), Err(LineError::Delimiter));
+    assert_eq!(
+        LineError::TooLong { len: 4, max: 3 }.to_string(),
+        "line of 4 bytes exceeds the maximum of 3 bytes"
+    );
+
+    // The line would end with the delimiter overlapping its start
+    let mut codec = Lines::new().delimiter(Delimiter::Bytes(b"aba"));
+    assert_eq!(encode(&mut codec, "xab"), Err(LineError::Delimiter));
+    assert_eq!(encode(&mut codec, "xa"), Ok(b"xaaba".to_vec()));
+}
+
+/// Answers reads with chunks and then closes the stream, returns written data
+fn drive<T: Future<Output = ()>>(
+    io: &Io<'_, Stream>,
+    task: Pin<&mut T>,
+    chunks: &[&[u8]],
+) -> Vec<u8> {
+    let mut chunks = chunks.iter();
+    let mut written = Vec::new();
+    let mut handler = io.start(task);
+    while let Some(request) = handler {
+        let response = match request.request() {
+            StreamRequest::Read { .. } => match chunks.next() {
+                Some(data) => StreamResponse::Data { data },
+                None => StreamResponse::Closed,
+            },
+            StreamRequest::Write { data } => {
+                written.extend_from_slice(data);
+                StreamResponse::Written
+            }
+            StreamRequest::Sleep { .. } => StreamResponse::Woken,
+        };
+        handler = io.handle(request, &response);
+    }
+    written
+}
+
+/// Receives messages of the DATA command longer than the read buffer, replies with their lengths
+async fn smtp(mut sans: Sans<'_, Stream>) -> Result<(), Error<LineError>> {
+    let (mut read_buf, mut write_buf) = ([0; 16], [0; 16]);
+    let mut framed = Framed::new(&mut sans, &mut read_buf, &mut write_buf);
+    let mut lines = Lines::new().max_line_len(6);
+    while let Some(command) = framed.read(&mut lines).await? {
+        assert_eq!(command, b"DATA");
+        let mut len = 0;
+        loop {
+            match framed.read_until(b"\r\n.\r\n").await? {
+                Some(Chunk::Part(data)) => len += data.len(),
+                Some(Chunk::Last(data)) => break len += data.len(),
+                None => return Err(Error::Closed),
+            }
+        }
+        framed.write(&mut lines, &*len.to_string()).await?;
+    }
+    Ok(())
+}
+
+/// Runs the SMTP task with chunks, returns the result and written data
+fn smtp_result(chunks: &[&[u8]]) -> (Option<Result<(), Error<LineError>>>, Vec<u8>) {
+    let mut session = Session::<Stream>::new();
+    let (sans, io) = session.split();
+    let mut result = None;
+    let task = async {
+        result = Some(smtp(sans).await);
+    };
+    let written = drive(&io, pin!(task), chunks);
+    (result, written)
+}
+
+#[test]
+fn read_until() {
+    let chunks: [&[u8]; _] = [
+        b"DATA\r\nHello",
+        b" world, hi",
+        b" again\r\n.",
+        b"\r",
+        b"\nDATA\n\r\n.\r\n",
+    ];
+    let (result, written) = smtp_result(&chunks);
+    assert_eq!(result, Some(Ok(())));
+    assert_eq!(written, b"21\r\n0\r\n");
+
+    // Closed in the middle of the message
+    let (result, written) = smtp_result(&[b"DATA\r\nHello"]);
+    assert_eq!(result, Some(Err(Error::Closed)));
+    assert_eq!(written, b"");
+
+    let (result, _) = smtp_result(&[b"DATA\r\nHi\r\n"]);
+    assert_eq!(result, Some(Err(Error::Closed)));
+}
+
+/// Answers reads with Responses and then closes the stream
+fn respond<T: Future<Output = ()>>(
+    io: &Io<'_, Stream>,
+    task: Pin<&mut T>,
+    responses: &[StreamResponse<'_>],
+) {
+    let mut responses = responses.iter();
+    let mut handler = io.start(task);
+    while let Some(request) = handler {
+        let response = match request.request() {
+            StreamRequest::Read { .. } => *responses.next().unwrap_or(&StreamResponse::Closed),
+            _ => StreamResponse::Written,
+        };
+        handler = io.handle(request, &response);
+    }
+}
+
+/// Reads the line after the timeout in the middle of it and skips the data until `c`
+async fn skip_after_timeout(mut sans: Sans<'_, Stream>) -> Result<Vec<u8>, Error<LineError>> {
+    let (mut read_buf, mut write_buf) = ([0; 16], [0; 16]);
+    let mut framed = Framed::new(&mut sans, &mut read_buf, &mut write_buf);
+    let mut codec = Lines::new().delimiter(Delimiter::Bytes(b"||"));
+    assert_eq!(framed.read(&mut codec).await, Err(Error::Timeout));
+    while let Some(Chunk::Part(_)) = framed.read_until(b"c").await? {}
+    let line = framed.read(&mut codec).await?.ok_or(Error::Closed)?;
+    Ok(line.to_vec())
+}
+
+#[test]
+fn reused_codec() {
+    let mut codec = Lines::new();
+    assert_eq!(codec.frame_len(b"HELO"), Ok(None));
+    codec.reset();
+    assert_eq!(codec.frame_len(b"a\n"), Ok(Some(2)));
+
+    // The searched part of the timed out line is consumed by other reads
+    let mut session = Session::<Stream>::new();
+    let (sans, io) = session.split();
+    let mut result = None;
+    let task = async {
+        result = Some(skip_after_timeout(sans).await);
+    };
+    let responses = [
+        StreamResponse::Data { data: b"ab|" },
+        StreamResponse::Timeout,
+        StreamResponse::Data { data: b"co||" },
+    ];
+    respond(&io, pin!(task), &responses);
+    assert_eq!(result, Some(Ok(b"o".to_vec())));
+}
@@ -0,0 +1,104 @@
+use asansio::Session;
+use log::Level;
+use log::Log;
+use log::Metadata;
+use log::Record;
+use std::cell::RefCell;
+use std::panic::AssertUnwindSafe;
+use std::pin::pin;
+
+struct Request;
+struct Response;
+
+struct Proto;
+
+asansio::message! {
+    Proto {
+        type Request<'r> = Request;
+        type Response<'r> = Response;
+    }
+}
+
+thread_local! {
+    static RECORDS: RefCell<Vec<(Level, String)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Collects records of the current thread, as tests run in parallel
+struct Logger;
+
+impl Log for Logger {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if record.target().starts_with("asansio") {
+            RECORDS.with_borrow_mut(|records| {
+                records.push((record.level(), record.args().to_string()))
+            });
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn records(run: impl FnOnce()) -> Vec<(Level, String)> {
+    static LOGGER: Logger = Logger;
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(log::LevelFilter::Trace);
+    RECORDS.with_borrow_mut(Vec::clear);
+    run();
+    RECORDS.take()
+}
+
+#[test]
+fn steps() {
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
+    let mut task = pin!(async {
+        let response = sans.start(&Request).await;
+        sans.handle(response, &Request).await;
+    });
+
+    let records = records(|| {
+        let mut handler = io.start(task.as_mut());
+        for _ in 0..2 {
+            handler = io.handle(handler.unwrap(), &Response);
+        }
+        assert!(handler.is_none());
+    });
+    assert_eq!(
+        records,
+        [
+            (Level::Debug, "step 1, finished: false".to_string()),
+            (Level::Debug, "step 2, finished: false".to_string()),
+            (Level::Debug, "step 3, finished: true".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn misuse() {
+    let mut session = Session::<Proto>::new();
+    let (_, io) = session.split();
+    let mut task = pin!(async {
+        std::future::pending::<()>().await;
+    });
+
+    let records = records(|| {
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            io.start(task.as_mut());
+        }));
+        assert!(result.is_err());
+    });
+    assert_eq!(
+        records,
+        [
+            (Level::Debug, "step 1, finished: false".to_string()),
+            (
+                Level::Error,
+                "Sans task pending outside of Sans::start or Sans::handle".to_string()
+            ),
+        ]
+    );
+}
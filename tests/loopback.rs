@@ -0,0 +1,205 @@
+#[path = "../examples/tlv_pingpong_proto/mod.rs"]
+mod tlv_pingpong_proto;
+
+use asansio::Session;
+use asansio::loopback;
+use asansio::loopback::Action;
+use asansio::loopback::End;
+use asansio::loopback::Link;
+use asansio::loopback::Outcome;
+use asansio::loopback::Translate;
+use std::pin::pin;
+use std::time::Duration;
+use tlv_pingpong_proto::ClientProto;
+use tlv_pingpong_proto::ClientRequest;
+use tlv_pingpong_proto::ClientResponse;
+use tlv_pingpong_proto::ServerProto;
+use tlv_pingpong_proto::ServerRequest;
+use tlv_pingpong_proto::ServerResponse;
+
+/// Sends messages and sleeps, stops after receiving all replies
+struct Client {
+    messages: usize,
+    reads: usize,
+    sent: usize,
+    msg: String,
+    received: Vec<String>,
+}
+
+impl Client {
+    fn new(messages: usize) -> Self {
+        Self {
+            messages,
+            reads: 0,
+            sent: 0,
+            msg: String::new(),
+            received: Vec::new(),
+        }
+    }
+}
+
+impl Translate<ClientProto> for Client {
+    fn translate<'a>(
+        &'a mut self,
+        request: &ClientRequest<'_>,
+        link: &'a mut Link<'_>,
+    ) -> Action<ClientResponse<'a>> {
+        match request {
+            ClientRequest::ReadPayload => {
+                if link.available() > 0 {
+                    return Action::Respond(ClientResponse::ReadPayload {
+                        payload: link.read(usize::MAX),
+                    });
+                }
+                if self.sent < self.messages {
+                    self.reads += 1;
+                    if self.reads.is_multiple_of(3) {
+                        return Action::Respond(ClientResponse::Sleep {
+                            duration: Duration::from_millis(200),
+                        });
+                    }
+                    self.sent += 1;
+                    self.msg = format!("packet {}", self.sent);
+                    return Action::Respond(ClientResponse::Message { msg: &self.msg });
+                }
+                if link.is_closed() {
+                    Action::Stop
+                } else {
+                    Action::Wait
+                }
+            }
+            ClientRequest::WritePayload { payload } => {
+                link.write(payload);
+                Action::Respond(ClientResponse::ReadPayload { payload: &[] })
+            }
+            ClientRequest::Message { msg } => {
+                self.received.push(msg.to_string());
+                if self.received.len() == self.messages {
+                    Action::Stop
+                } else {
+                    Action::Respond(ClientResponse::ReadPayload { payload: &[] })
+                }
+            }
+            ClientRequest::Error => Action::Stop,
+        }
+    }
+}
+
+/// Passes payloads through the link and counts sleeps
+#[derive(Default)]
+struct Server {
+    sleeps: Vec<Duration>,
+}
+
+impl Translate<ServerProto> for Server {
+    fn translate<'a>(
+        &'a mut self,
+        request: &ServerRequest<'_>,
+        link: &'a mut Link<'_>,
+    ) -> Action<ServerResponse<'a>> {
+        match request {
+            ServerRequest::ReadPayload => {
+                if link.available() > 0 {
+                    Action::Respond(ServerResponse::ReadPayload {
+                        payload: link.read(usize::MAX),
+                    })
+                } else if link.is_closed() {
+                    Action::Stop
+                } else {
+                    Action::Wait
+                }
+            }
+            ServerRequest::WritePayload { payload } => {
+                link.write(payload);
+                Action::Respond(ServerResponse::ReadPayload { payload: &[] })
+            }
+            ServerRequest::Sleep { duration } => {
+                self.sleeps.push(*duration);
+                Action::Respond(ServerResponse::ReadPayload { payload: &[] })
+            }
+            ServerRequest::Error => Action::Stop,
+        }
+    }
+}
+
+#[test]
+fn conversation() {
+    let mut client_session = Session::<ClientProto>::new();
+    let (client_sans, client_io) = client_session.split();
+    let mut server_session = Session::<ServerProto>::new();
+    let (server_sans, server_io) = server_session.split();
+
+    let mut client = Client::new(10);
+    let mut server = Server::default();
+    let outcome = loopback::run(
+        &client_io,
+        pin!(tlv_pingpong_proto::run_client(client_sans)),
+        &mut client,
+        &server_io,
+        pin!(tlv_pingpong_proto::run_server(server_sans)),
+        &mut server,
+    );
+
+    assert_eq!(
+        outcome,
+        Outcome {
+            client: End::Stopped,
+            server: End::Stopped,
+        }
+    );
+    let expected: Vec<_> = (1..=10)
+        .map(|idx| format!("Received: packet {idx}"))
+        .collect();
+    assert_eq!(client.received, expected);
+    assert_eq!(server.sleeps, [Duration::from_millis(200); 4]);
+}
+
+#[test]
+fn blocked_conversation() {
+    let mut client_session = Session::<ClientProto>::new();
+    let (client_sans, client_io) = client_session.split();
+    let mut server_session = Session::<ServerProto>::new();
+    let (server_sans, server_io) = server_session.split();
+
+    let outcome = loopback::run(
+        &client_io,
+        pin!(tlv_pingpong_proto::run_client(client_sans)),
+        &mut Client::new(0),
+        &server_io,
+        pin!(tlv_pingpong_proto::run_server(server_sans)),
+        &mut Server::default(),
+    );
+
+    assert_eq!(
+        outcome,
+        Outcome {
+            client: End::Blocked,
+            server: End::Blocked,
+        }
+    );
+}
+
+#[test]
+fn finished_client() {
+    let mut client_session = Session::<ClientProto>::new();
+    let (_, client_io) = client_session.split();
+    let mut server_session = Session::<ServerProto>::new();
+    let (server_sans, server_io) = server_session.split();
+
+    let outcome = loopback::run(
+        &client_io,
+        pin!(async {}),
+        &mut Client::new(0),
+        &server_io,
+        pin!(tlv_pingpong_proto::run_server(server_sans)),
+        &mut Server::default(),
+    );
+
+    assert_eq!(
+        outcome,
+        Outcome {
+            client: End::Finished,
+            server: End::Stopped,
+        }
+    );
+}
@@ -0,0 +1,113 @@
+use asansio::Drive;
+use asansio::Sans;
+use asansio::Session;
+use asansio::metrics::Meter;
+use asansio::metrics::Metrics;
+use asansio::metrics::Stats;
+use std::pin::pin;
+use std::thread;
+use std::time::Duration;
+
+enum Request {
+    Login,
+    Get,
+}
+
+struct Response(u8);
+
+struct Proto;
+
+asansio::message! {
+    Proto {
+        type Request<'r> = Request;
+        type Response<'r> = Response;
+    }
+}
+
+fn classify(request: &Request) -> &'static str {
+    match request {
+        Request::Login => "login",
+        Request::Get => "get",
+    }
+}
+
+/// Logs in and gets values until the zero, panics on the overflow
+async fn client(mut sans: Sans<'_, Proto>) {
+    let mut response = sans.start(&Request::Login).await;
+    loop {
+        response = sans.handle(response, &Request::Get).await;
+        if response.response().0 == 0 {
+            break;
+        }
+        if response.response().0 == u8::MAX {
+            panic!("overflow");
+        }
+    }
+}
+
+const DELAY: Duration = Duration::from_millis(5);
+
+#[test]
+fn histogram_of_kinds() {
+    let mut session = Session::<Proto>::new();
+    let (sans, io) = session.split();
+    let mut meter = Meter::new(io, Stats::new(classify));
+    let mut task = pin!(client(sans));
+
+    let mut handler = meter.start(task.as_mut());
+    for response in [1, 2, 3, 0] {
+        let request = handler.unwrap();
+        if let Request::Login = request.request() {
+            // The slow authentication
+            thread::sleep(DELAY);
+        }
+        handler = meter.handle(request, &Response(response));
+    }
+    assert!(handler.is_none());
+
+    let stats = meter.into_metrics();
+    assert_eq!(stats.polls(), 5);
+    assert_eq!(stats.total().exchanges, 4);
+    let kinds: Vec<_> = stats
+        .kinds()
+        .map(|(kind, totals)| (kind, totals.exchanges))
+        .collect();
+    assert_eq!(kinds, [("get", 3), ("login", 1)]);
+    assert!(stats.kind("login").unwrap().io >= DELAY);
+    assert!(stats.total().io >= stats.kind("login").unwrap().io);
+}
+
+/// Records every measurement
+#[derive(Default)]
+struct Log(Vec<String>);
+
+impl Metrics<Proto> for Log {
+    fn classify(&mut self, request: &Request) -> &'static str {
+        classify(request)
+    }
+
+    fn exchange(&mut self, kind: &'static str, _: Duration, _: Duration) {
+        self.0.push(format!("exchange {kind}"));
+    }
+
+    fn finish(&mut self, _: Duration) {
+        self.0.push("finish".to_string());
+    }
+}
+
+#[test]
+fn panicked_task() {
+    let mut session = Session::<Proto>::new();
+    let (sans, io) = session.split();
+    let mut meter = Meter::new(io, Log::default());
+    let mut task = pin!(client(sans));
+
+    let handler = meter.try_start(task.as_mut()).unwrap().unwrap();
+    let handler = meter.try_handle(handler, &Response(0)).unwrap().unwrap();
+    assert!(meter.try_handle(handler, &Response(u8::MAX)).is_err());
+
+    assert_eq!(
+        meter.into_metrics().0,
+        ["exchange login", "exchange get", "finish"]
+    );
+}
@@ -0,0 +1,209 @@
+use asansio::Sans;
+use asansio::Session;
+use asansio::mock::Failure;
+use asansio::mock::MockIo;
+use std::pin::pin;
+
+#[derive(Debug)]
+enum Request<'a> {
+    Login { user: &'a str },
+    Get { key: u8 },
+    Bye,
+}
+
+enum Response<'a> {
+    Ok,
+    Value { value: &'a [u8] },
+}
+
+struct Proto;
+
+asansio::message! {
+    Proto {
+        type Request<'r> = Request<'r>;
+        type Response<'r> = Response<'r>;
+    }
+}
+
+/// Logs in, gets values for keys and says bye
+async fn sans_task(mut sans: Sans<'_, Proto>, keys: &[u8], values: &mut Vec<u8>) {
+    let mut response = sans.start(&Request::Login { user: "user" }).await;
+    for &key in keys {
+        response = sans.handle(response, &Request::Get { key }).await;
+        if let Response::Value { value } = response.response() {
+            values.extend_from_slice(value);
+        }
+    }
+    sans.handle(response, &Request::Bye).await;
+}
+
+#[test]
+fn script() {
+    let mut session = Session::<Proto>::new();
+    let (sans, io) = session.split();
+
+    let mut values = Vec::new();
+    MockIo::new()
+        .expect(
+            "login",
+            |request| matches!(request, Request::Login { user } if *user == "user"),
+        )
+        .respond(Response::Ok)
+        .expect("get 1", |request| {
+            matches!(request, Request::Get { key: 1 })
+        })
+        .respond(Response::Value { value: &[10] })
+        .expect("get 2", |request| {
+            matches!(request, Request::Get { key: 2 })
+        })
+        .respond_with(|request| match request {
+            Request::Get { key: 2 } => Response::Value { value: &[20, 21] },
+            _ => unreachable!(),
+        })
+        .expect("bye", |request| matches!(request, Request::Bye))
+        .respond(Response::Ok)
+        .expect_end()
+        .run(&io, pin!(sans_task(sans, &[1, 2], &mut values)));
+
+    assert_eq!(values, [10, 20, 21]);
+}
+
+#[test]
+fn optional_steps() {
+    fn script<'m>() -> MockIo<'m, Proto> {
+        MockIo::new()
+            .expect("login", |request| matches!(request, Request::Login { .. }))
+            .respond(Response::Ok)
+            .expect("get", |request| matches!(request, Request::Get { .. }))
+            .optional()
+            .respond(Response::Ok)
+            .expect("bye", |request| matches!(request, Request::Bye))
+            .respond(Response::Ok)
+            .expect_end()
+    }
+
+    let mut session = Session::<Proto>::new();
+    let (sans, io) = session.split();
+    script().run(&io, pin!(sans_task(sans, &[], &mut Vec::new())));
+
+    let mut session = Session::<Proto>::new();
+    let (sans, io) = session.split();
+    script().run(&io, pin!(sans_task(sans, &[1], &mut Vec::new())));
+
+    let mut session = Session::<Proto>::new();
+    let (sans, io) = session.split();
+    assert_eq!(
+        script().try_run(&io, pin!(sans_task(sans, &[1, 2], &mut Vec::new()))),
+        Err(Failure::UnexpectedRequest {
+            number: 3,
+            request: None,
+            expected: vec!["bye".to_string()],
+        })
+    );
+}
+
+#[test]
+fn any_order() {
+    fn script<'m>() -> MockIo<'m, Proto> {
+        MockIo::new()
+            .expect("login", |request| matches!(request, Request::Login { .. }))
+            .respond(Response::Ok)
+            .any_order(|script| {
+                script
+                    .expect("get 1", |request| {
+                        matches!(request, Request::Get { key: 1 })
+                    })
+                    .respond(Response::Value { value: &[1] })
+                    .expect("get 2", |request| {
+                        matches!(request, Request::Get { key: 2 })
+                    })
+                    .respond(Response::Value { value: &[2] })
+            })
+            .expect("bye", |request| matches!(request, Request::Bye))
+            .respond(Response::Ok)
+            .expect_end()
+    }
+
+    for keys in [[1, 2], [2, 1]] {
+        let mut session = Session::<Proto>::new();
+        let (sans, io) = session.split();
+        let mut values = Vec::new();
+        script().run(&io, pin!(sans_task(sans, &keys, &mut values)));
+        assert_eq!(values, keys);
+    }
+
+    let mut session = Session::<Proto>::new();
+    let (sans, io) = session.split();
+    assert_eq!(
+        script().try_run(&io, pin!(sans_task(sans, &[1, 1], &mut Vec::new()))),
+        Err(Failure::UnexpectedRequest {
+            number: 3,
+            request: None,
+            expected: vec!["get 2".to_string()],
+        })
+    );
+}
+
+#[test]
+fn end_of_task() {
+    let mut session = Session::<Proto>::new();
+    let (sans, io) = session.split();
+    assert_eq!(
+        MockIo::new()
+            .expect("login", |request| matches!(request, Request::Login { .. }))
+            .respond(Response::Ok)
+            .try_run(&io, pin!(sans_task(sans, &[1], &mut Vec::new()))),
+        Ok(())
+    );
+
+    let mut session = Session::<Proto>::new();
+    let (sans, io) = session.split();
+    let failure = MockIo::new()
+        .expect("login", |request| matches!(request, Request::Login { .. }))
+        .respond(Response::Ok)
+        .expect_end()
+        .describe(|request| format!("{request:?}"))
+        .try_run(&io, pin!(sans_task(sans, &[1], &mut Vec::new())))
+        .unwrap_err();
+    assert_eq!(
+        failure.to_string(),
+        "request #2 Get { key: 1 } received after the last step, the Sans task should finish"
+    );
+
+    let mut session = Session::<Proto>::new();
+    let (_, io) = session.split();
+    let failure = MockIo::new()
+        .expect("login", |request| matches!(request, Request::Login { .. }))
+        .respond(Response::Ok)
+        .expect("bye", |request| matches!(request, Request::Bye))
+        .optional()
+        .respond(Response::Ok)
+        .try_run(&io, pin!(async {}))
+        .unwrap_err();
+    assert_eq!(
+        failure.to_string(),
+        "Sans task finished, but steps are still expected: login"
+    );
+}
+
+#[test]
+#[should_panic(
+    expected = "request #1 Bye does not match any of expected steps: get (optional), login"
+)]
+fn readable_failure() {
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
+
+    let task = pin!(async {
+        sans.start(&Request::Bye).await;
+    });
+
+    MockIo::new()
+        .expect("get", |request| matches!(request, Request::Get { .. }))
+        .optional()
+        .respond(Response::Ok)
+        .expect("login", |request| matches!(request, Request::Login { .. }))
+        .respond(Response::Ok)
+        .describe(|request| format!("{request:?}"))
+        .run(&io, task);
+}
@@ -0,0 +1,105 @@
+use asansio::Error;
+use asansio::Session;
+use std::pin::pin;
+
+struct Request<'a>(&'a [u8]);
+struct Response<'a>(&'a [u8]);
+
+struct Proto;
+
+asansio::message! {
+    Proto {
+        type Request<'r> = Request<'r>;
+        type Response<'r> = Response<'r>;
+    }
+}
+
+#[test]
+fn no_panic() {
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
+
+    let task = pin!(async {
+        let response = sans.start(&Request(&[1])).await;
+        assert_eq!(response.response().0, [2]);
+    });
+
+    let request = io.try_start(task).unwrap().unwrap();
+    assert_eq!(request.request().0, [1]);
+    assert!(io.try_handle(request, &Response(&[2])).unwrap().is_none());
+    assert!(!io.is_poisoned());
+}
+
+#[test]
+fn panic_in_start() {
+    let mut session = Session::<Proto>::new();
+    let (_, io) = session.split();
+
+    let task = pin!(async {
+        panic!("malformed peer");
+    });
+
+    let Err(err) = io.try_start(task) else {
+        panic!("the panic is not caught");
+    };
+    assert!(matches!(err, Error::Panicked(_)));
+    assert_eq!(err.message(), Some("malformed peer"));
+    assert!(io.is_poisoned());
+}
+
+#[test]
+fn panic_in_handle() {
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
+
+    let task = pin!(async {
+        let response = sans.start(&Request(&[1])).await;
+        let len = response.response().0.len();
+        panic!("unexpected length {len}");
+    });
+
+    let request = io.try_start(task).unwrap().unwrap();
+    let Err(err) = io.try_handle(request, &Response(&[2, 3])) else {
+        panic!("the panic is not caught");
+    };
+    assert_eq!(err.message(), Some("unexpected length 2"));
+    assert_eq!(err.to_string(), "Sans task panicked: unexpected length 2");
+}
+
+#[test]
+fn poisoned_session() {
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
+
+    {
+        let task = pin!(async {
+            sans.start(&Request(&[1])).await;
+            unreachable!();
+        });
+
+        let request = io.try_start(task).unwrap().unwrap();
+        assert!(io.try_handle(request, &Response(&[2])).is_err());
+    }
+
+    let task = pin!(async {
+        sans.start(&Request(&[3])).await;
+    });
+    assert!(matches!(io.try_start(task), Err(Error::Poisoned)));
+}
+
+#[test]
+#[should_panic(expected = "Sans task polled after it panicked")]
+fn poisoned_session_without_try() {
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
+
+    let task = pin!(async {
+        panic!("malformed peer");
+    });
+    assert!(io.try_start(task).is_err());
+
+    let task = pin!(async {
+        sans.start(&Request(&[1])).await;
+    });
+    io.start(task);
+}
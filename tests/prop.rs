@@ -0,0 +1,170 @@
+#[allow(dead_code)]
+#[path = "../examples/tlv_proto/mod.rs"]
+mod tlv_proto;
+
+use asansio::prop::Property;
+use asansio::prop::Source;
+use asansio::prop::Strategy;
+use asansio::transcript::Event;
+use asansio::transcript::Hook;
+use std::collections::VecDeque;
+use tlv_proto::ClientProto;
+use tlv_proto::ClientRequest;
+use tlv_proto::ClientResponse;
+
+/// Writes random values and reads back the written payload in random chunks, the read value
+/// must be the written one
+struct Roundtrip {
+    max_len: usize,
+    writes: usize,
+    written: Option<(u8, Vec<u8>)>,
+    chunks: VecDeque<Vec<u8>>,
+    chunk: Vec<u8>,
+}
+
+impl Roundtrip {
+    fn new(max_len: usize) -> Self {
+        Self {
+            max_len,
+            writes: 0,
+            written: None,
+            chunks: VecDeque::new(),
+            chunk: Vec::new(),
+        }
+    }
+
+    fn next_chunk(&mut self) -> ClientResponse<'_> {
+        self.chunk = self.chunks.pop_front().unwrap_or_default();
+        ClientResponse::ReadPayload {
+            payload: &self.chunk,
+        }
+    }
+}
+
+impl Strategy<ClientProto> for Roundtrip {
+    fn respond<'a>(
+        &'a mut self,
+        source: &mut Source,
+        request: &ClientRequest<'_>,
+    ) -> Result<Option<ClientResponse<'a>>, String> {
+        match request {
+            ClientRequest::ReadPayload if !self.chunks.is_empty() => Ok(Some(self.next_chunk())),
+            ClientRequest::ReadPayload if self.written.is_some() => {
+                Err("the written value was not read".to_string())
+            }
+            ClientRequest::ReadPayload if self.writes == 3 => Ok(None),
+            ClientRequest::ReadPayload => {
+                self.writes += 1;
+                let tag = source.below(256) as u8;
+                let (_, val) = self.written.insert((tag, source.bytes(0..=self.max_len)));
+                Ok(Some(ClientResponse::Write { tag, val }))
+            }
+            ClientRequest::WritePayload { payload } => {
+                self.chunks = source
+                    .chunks(payload)
+                    .into_iter()
+                    .map(<[u8]>::to_vec)
+                    .collect();
+                Ok(Some(self.next_chunk()))
+            }
+            ClientRequest::Read { tag, val } => {
+                let (written_tag, written_val) = self.written.take().unwrap();
+                if (*tag, *val) != (written_tag, written_val.as_slice()) {
+                    return Err(format!(
+                        "read tag {tag} with {} bytes, written tag {written_tag} with {} bytes",
+                        val.len(),
+                        written_val.len()
+                    ));
+                }
+                Ok(Some(self.next_chunk()))
+            }
+            ClientRequest::Error { err } => Err(format!("write failed: {err}")),
+        }
+    }
+}
+
+/// Records kinds of messages with lengths of their data
+struct Lengths;
+
+impl Hook<ClientProto> for Lengths {
+    type Request = String;
+    type Response = String;
+
+    fn request(&mut self, request: &ClientRequest<'_>) -> String {
+        match request {
+            ClientRequest::ReadPayload => "ReadPayload".to_string(),
+            ClientRequest::WritePayload { payload } => format!("WritePayload {}", payload.len()),
+            ClientRequest::Read { tag, val } => format!("Read {tag} {}", val.len()),
+            ClientRequest::Error { err } => format!("Error {err}"),
+        }
+    }
+
+    fn response(&mut self, response: &ClientResponse<'_>) -> String {
+        match response {
+            ClientResponse::ReadPayload { payload } => format!("ReadPayload {}", payload.len()),
+            ClientResponse::Write { tag, val } => format!("Write {tag} {}", val.len()),
+        }
+    }
+}
+
+#[test]
+fn roundtrip_short_values() {
+    Property::new(0)
+        .check(
+            |sans| Box::pin(tlv_proto::run_client(sans)),
+            || Roundtrip::new(255),
+            Lengths,
+        )
+        .unwrap();
+}
+
+#[test]
+fn roundtrip_long_values() {
+    let failure = Property::new(0)
+        .check(
+            |sans| Box::pin(tlv_proto::run_client(sans)),
+            || Roundtrip::new(300),
+            Lengths,
+        )
+        .unwrap_err();
+
+    // The length of the value is the single byte, the minimal failing case is the first write of
+    // 256 zeros with the zero tag
+    assert_eq!(
+        failure.message,
+        "write failed: value of 256 bytes exceeds the maximum of 255 bytes"
+    );
+    assert_eq!(failure.choices, [0, 256]);
+    let events: Vec<_> = failure
+        .transcript
+        .entries()
+        .iter()
+        .map(|entry| entry.event.clone())
+        .collect();
+    assert_eq!(
+        events,
+        [
+            Event::Request("ReadPayload".to_string()),
+            Event::Response("Write 0 256".to_string()),
+            Event::Request("Error value of 256 bytes exceeds the maximum of 255 bytes".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn deterministic_seed() {
+    let check = |seed| {
+        Property::new(seed)
+            .shrinks(0)
+            .check(
+                |sans| Box::pin(tlv_proto::run_client(sans)),
+                || Roundtrip::new(300),
+                Lengths,
+            )
+            .unwrap_err()
+    };
+    let failure = check(7);
+    assert_eq!(failure.case, check(7).case);
+    assert_eq!(failure.choices, check(7).choices);
+    assert!(failure.choices.len() > 2);
+}
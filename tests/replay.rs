@@ -0,0 +1,144 @@
+use asansio::Drive;
+use asansio::Sans;
+use asansio::Session;
+use asansio::replay::Divergence;
+use asansio::replay::Replay;
+use asansio::replay::replay;
+use asansio::transcript::Event;
+use asansio::transcript::Hook;
+use asansio::transcript::Recorder;
+use asansio::transcript::Transcript;
+use std::pin::pin;
+
+struct Request<'a>(&'a [u8]);
+struct Response<'a>(&'a [u8]);
+
+struct Proto;
+
+asansio::message! {
+    Proto {
+        type Request<'r> = Request<'r>;
+        type Response<'r> = Response<'r>;
+    }
+}
+
+/// Records payloads of messages
+struct BytesHook;
+
+impl Hook<Proto> for BytesHook {
+    type Request = Vec<u8>;
+    type Response = Vec<u8>;
+
+    fn request(&mut self, request: &Request<'_>) -> Vec<u8> {
+        request.0.to_vec()
+    }
+
+    fn response(&mut self, response: &Response<'_>) -> Vec<u8> {
+        response.0.to_vec()
+    }
+}
+
+impl Replay<Proto> for BytesHook {
+    fn restore_response<'r>(&mut self, record: &'r Vec<u8>) -> Response<'r> {
+        Response(record)
+    }
+}
+
+/// Sends the sum of received payloads until the Response is empty, panics on the zero payload
+async fn sum_task(mut sans: Sans<'_, Proto>, step: u8) {
+    let mut sum = 0u8;
+    let mut response = sans.start(&Request(&[sum])).await;
+    while let Some(&byte) = response.response().0.first() {
+        assert_ne!(byte, 0, "zero payload");
+        sum += byte * step;
+        response = sans.handle(response, &Request(&[sum])).await;
+    }
+}
+
+fn record(responses: &[&[u8]]) -> Transcript<Vec<u8>, Vec<u8>> {
+    let mut session = Session::<Proto>::new();
+    let (sans, io) = session.split();
+    let mut recorder = Recorder::new(io, BytesHook);
+
+    let task = pin!(sum_task(sans, 1));
+
+    let mut request = recorder.try_start(task).unwrap();
+    for response in responses {
+        let Ok(next) = recorder.try_handle(request.unwrap(), &Response(response)) else {
+            break;
+        };
+        request = next;
+    }
+    recorder.into_transcript()
+}
+
+#[test]
+fn same_task() {
+    let transcript = record(&[&[1], &[2], &[]]);
+    assert_eq!(transcript.len(), 7);
+
+    let mut session = Session::<Proto>::new();
+    let (sans, io) = session.split();
+    let task = pin!(sum_task(sans, 1));
+    assert_eq!(replay(&io, task, &mut BytesHook, &transcript), Ok(()));
+}
+
+#[test]
+fn first_divergence() {
+    let transcript = record(&[&[1], &[2], &[]]);
+
+    let mut session = Session::<Proto>::new();
+    let (sans, io) = session.split();
+    let task = pin!(sum_task(sans, 2));
+    let divergence = replay(&io, task, &mut BytesHook, &transcript).unwrap_err();
+    assert_eq!(
+        divergence,
+        Divergence {
+            seq: 2,
+            expected: Event::Request(vec![1]),
+            actual: Event::Request(vec![2]),
+        }
+    );
+    assert_eq!(
+        divergence.to_string(),
+        "replay diverged at 2: expected Request([1]), actual Request([2])"
+    );
+}
+
+#[test]
+fn unfinished_transcript() {
+    let transcript = record(&[&[1]]);
+    assert_eq!(transcript.len(), 3);
+
+    let mut session = Session::<Proto>::new();
+    let (sans, io) = session.split();
+    let task = pin!(sum_task(sans, 1));
+    assert_eq!(replay(&io, task, &mut BytesHook, &transcript), Ok(()));
+}
+
+#[test]
+fn recorded_panic() {
+    let transcript = record(&[&[1], &[0]]);
+    assert_eq!(
+        transcript.entries().last().unwrap().event,
+        Event::Panicked(Some(
+            "assertion `left != right` failed: zero payload\n  left: 0\n right: 0".to_string()
+        ))
+    );
+
+    let mut session = Session::<Proto>::new();
+    let (sans, io) = session.split();
+    let task = pin!(sum_task(sans, 1));
+    assert_eq!(replay(&io, task, &mut BytesHook, &transcript), Ok(()));
+
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
+    let task = pin!(async {
+        let response = sans.start(&Request(&[0])).await;
+        let response = sans.handle(response, &Request(&[1])).await;
+        sans.handle(response, &Request(&[1])).await;
+    });
+    let divergence = replay(&io, task, &mut BytesHook, &transcript).unwrap_err();
+    assert_eq!(divergence.seq, 4);
+    assert_eq!(divergence.actual, Event::Request(vec![1]));
+}
@@ -0,0 +1,367 @@
+use asansio::Sans;
+use asansio::Session;
+use asansio::codec::Framed;
+use asansio::codec::length::LengthDelimited;
+use asansio::codec::length::Prefix;
+use asansio::loopback::End;
+use asansio::net::Datagram;
+use asansio::net::DatagramRequest;
+use asansio::net::DatagramResponse;
+use asansio::net::Stream;
+use asansio::net::StreamRequest;
+use asansio::net::StreamResponse;
+use asansio::sim::Error;
+use asansio::sim::Simulator;
+use std::pin::pin;
+use std::time::Duration;
+
+/// Frames with one byte of the length
+fn frame(payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![payload.len() as u8];
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// Collects the read data and splits it into frames
+#[derive(Default)]
+struct Frames {
+    buf: Vec<u8>,
+}
+
+impl Frames {
+    fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let len = *self.buf.first()? as usize;
+        if self.buf.len() < len + 1 {
+            return None;
+        }
+        let frame = self.buf[1..len + 1].to_vec();
+        self.buf.drain(..len + 1);
+        Some(frame)
+    }
+}
+
+/// Sends frames one by one and waits for their echo
+async fn echo_client(mut sans: Sans<'_, Stream>, payloads: &[&[u8]], echoes: &mut Vec<Vec<u8>>) {
+    let mut frames = Frames::default();
+    let mut write = frame(payloads[0]);
+    let mut response = sans.start(&StreamRequest::Write { data: &write }).await;
+    for idx in 0..payloads.len() {
+        loop {
+            if let Some(echo) = frames.next() {
+                echoes.push(echo);
+                break;
+            }
+            response = sans
+                .handle(
+                    response,
+                    &StreamRequest::Read {
+                        max: 1024,
+                        timeout: None,
+                    },
+                )
+                .await;
+            match response.response() {
+                StreamResponse::Data { data } => frames.push(data),
+                _ => return,
+            }
+        }
+        if let Some(payload) = payloads.get(idx + 1) {
+            write = frame(payload);
+            response = sans
+                .handle(response, &StreamRequest::Write { data: &write })
+                .await;
+        }
+    }
+}
+
+/// Echoes frames until the stream is closed. The naive server assumes every read is a single
+/// frame.
+async fn echo_server(mut sans: Sans<'_, Stream>, naive: bool) {
+    let mut frames = Frames::default();
+    let mut write = Vec::new();
+    let mut response = sans
+        .start(&StreamRequest::Read {
+            max: 1024,
+            timeout: None,
+        })
+        .await;
+    loop {
+        write.clear();
+        match response.response() {
+            StreamResponse::Data { data } if naive => write.extend(frame(&data[1..])),
+            StreamResponse::Data { data } => {
+                frames.push(data);
+                while let Some(payload) = frames.next() {
+                    write.extend(frame(&payload));
+                }
+            }
+            _ => return,
+        }
+        if !write.is_empty() {
+            response = sans
+                .handle(response, &StreamRequest::Write { data: &write })
+                .await;
+        }
+        response = sans
+            .handle(
+                response,
+                &StreamRequest::Read {
+                    max: 1024,
+                    timeout: None,
+                },
+            )
+            .await;
+    }
+}
+
+const PAYLOADS: [&[u8]; 5] = [b"first", b"second", b"third", b"fourth", b"fifth"];
+
+fn echo(simulator: &Simulator, naive: bool) -> (Vec<Vec<u8>>, asansio::sim::Outcome) {
+    let mut client_session = Session::<Stream>::new();
+    let (client_sans, client_io) = client_session.split();
+    let mut server_session = Session::<Stream>::new();
+    let (server_sans, server_io) = server_session.split();
+
+    let mut echoes = Vec::new();
+    let outcome = simulator
+        .run_stream(
+            &client_io,
+            pin!(echo_client(client_sans, &PAYLOADS, &mut echoes)),
+            &server_io,
+            pin!(echo_server(server_sans, naive)),
+        )
+        .unwrap();
+    (echoes, outcome)
+}
+
+#[test]
+fn stream_perfect_network() {
+    for naive in [false, true] {
+        let (echoes, outcome) = echo(&Simulator::new(0), naive);
+        assert_eq!(echoes, PAYLOADS);
+        assert_eq!(outcome.client, End::Finished);
+        assert_eq!(outcome.server, End::Finished);
+        // Every frame and its echo takes 2 ms, the close takes 1 ms
+        assert_eq!(outcome.time, Duration::from_millis(11));
+    }
+}
+
+#[test]
+fn stream_split_writes() {
+    for seed in 0..20 {
+        let simulator = Simulator::new(seed)
+            .latency(Duration::from_millis(1), Duration::from_millis(20))
+            .split(true);
+        let (echoes, outcome) = echo(&simulator, false);
+        assert_eq!(echoes, PAYLOADS);
+        assert_eq!(outcome.client, End::Finished);
+        assert_eq!(outcome.server, End::Finished);
+    }
+
+    // The partial frame handling bug of the naive server is reproduced deterministically
+    let simulator = Simulator::new(1).split(true);
+    let naive = echo(&simulator, true);
+    assert_ne!(naive.0, PAYLOADS);
+    assert_eq!(echo(&simulator, true), naive);
+}
+
+#[test]
+fn framed_burst() {
+    let mut client_session = Session::<Stream>::new();
+    let (mut client_sans, client_io) = client_session.split();
+    let mut server_session = Session::<Stream>::new();
+    let (mut server_sans, server_io) = server_session.split();
+
+    // The burst of frames arrives at once, but it is read in parts fitting into the buffer
+    let mut frames = 0;
+    let mut result = None;
+    Simulator::new(1)
+        .run_stream(
+            &client_io,
+            pin!(async {
+                let (mut read_buf, mut write_buf) = ([0; 16], [0; 16]);
+                let mut framed = Framed::new(&mut client_sans, &mut read_buf, &mut write_buf);
+                let mut codec = LengthDelimited::new().prefix(Prefix::U8);
+                for _ in 0..10 {
+                    framed.write(&mut codec, b"ping").await.unwrap();
+                }
+            }),
+            &server_io,
+            pin!(async {
+                let (mut read_buf, mut write_buf) = ([0; 16], [0; 16]);
+                let mut framed = Framed::new(&mut server_sans, &mut read_buf, &mut write_buf);
+                let mut codec = LengthDelimited::new().prefix(Prefix::U8);
+                result = Some(loop {
+                    match framed.read(&mut codec).await {
+                        Ok(Some(frame)) => {
+                            assert_eq!(frame, b"ping");
+                            frames += 1;
+                        }
+                        other => break other.map(|_| ()),
+                    }
+                });
+            }),
+        )
+        .unwrap();
+    assert_eq!(result, Some(Ok(())));
+    assert_eq!(frames, 10);
+}
+
+/// Sends numbered datagrams
+async fn datagram_client(mut sans: Sans<'_, Datagram>, count: u8) {
+    let mut response = sans.start(&DatagramRequest::Send { data: &[0] }).await;
+    for idx in 1..count {
+        response = sans
+            .handle(response, &DatagramRequest::Send { data: &[idx] })
+            .await;
+    }
+}
+
+/// Receives datagrams until the timeout
+async fn datagram_server(mut sans: Sans<'_, Datagram>, received: &mut Vec<u8>) {
+    let recv = DatagramRequest::Recv {
+        timeout: Some(Duration::from_millis(100)),
+    };
+    let mut response = sans.start(&recv).await;
+    while let DatagramResponse::Datagram { data } = response.response() {
+        received.extend_from_slice(data);
+        response = sans.handle(response, &recv).await;
+    }
+}
+
+fn datagrams(simulator: &Simulator) -> Vec<u8> {
+    let mut client_session = Session::<Datagram>::new();
+    let (client_sans, client_io) = client_session.split();
+    let mut server_session = Session::<Datagram>::new();
+    let (server_sans, server_io) = server_session.split();
+
+    let mut received = Vec::new();
+    let outcome = simulator
+        .run_datagram(
+            &client_io,
+            pin!(datagram_client(client_sans, 20)),
+            &server_io,
+            pin!(datagram_server(server_sans, &mut received)),
+        )
+        .unwrap();
+    assert_eq!(outcome.client, End::Finished);
+    assert_eq!(outcome.server, End::Finished);
+    received
+}
+
+#[test]
+fn datagram_adverse_network() {
+    let sent: Vec<u8> = (0..20).collect();
+    assert_eq!(datagrams(&Simulator::new(0)), sent);
+
+    let simulator = Simulator::new(3)
+        .latency(Duration::from_millis(1), Duration::from_millis(50))
+        .drop_rate(0.2)
+        .duplicate_rate(0.2);
+    let received = datagrams(&simulator);
+    assert_ne!(received, sent);
+    assert!(received.iter().all(|idx| sent.contains(idx)));
+    assert_eq!(datagrams(&simulator), received);
+}
+
+#[test]
+fn virtual_time() {
+    let mut client_session = Session::<Stream>::new();
+    let (mut client_sans, client_io) = client_session.split();
+    let mut server_session = Session::<Stream>::new();
+    let (mut server_sans, server_io) = server_session.split();
+
+    let mut timeouts = 0;
+    let outcome = Simulator::new(0)
+        .run_stream(
+            &client_io,
+            pin!(async {
+                let duration = Duration::from_secs(10);
+                client_sans.start(&StreamRequest::Sleep { duration }).await;
+            }),
+            &server_io,
+            pin!(async {
+                let read = StreamRequest::Read {
+                    max: 1024,
+                    timeout: Some(Duration::from_secs(3)),
+                };
+                let mut response = server_sans.start(&read).await;
+                while let StreamResponse::Timeout = response.response() {
+                    timeouts += 1;
+                    response = server_sans.handle(response, &read).await;
+                }
+            }),
+        )
+        .unwrap();
+    assert_eq!(timeouts, 3);
+    assert_eq!(outcome.client, End::Finished);
+    assert_eq!(outcome.server, End::Finished);
+    assert_eq!(outcome.time, Duration::from_millis(10_001));
+
+    let mut client_session = Session::<Stream>::new();
+    let (mut client_sans, client_io) = client_session.split();
+    let mut server_session = Session::<Stream>::new();
+    let (mut server_sans, server_io) = server_session.split();
+
+    let outcome = Simulator::new(0)
+        .run_stream(
+            &client_io,
+            pin!(async {
+                let duration = Duration::from_secs(7200);
+                client_sans.start(&StreamRequest::Sleep { duration }).await;
+            }),
+            &server_io,
+            pin!(async {
+                let read = StreamRequest::Read {
+                    max: 1024,
+                    timeout: None,
+                };
+                server_sans.start(&read).await;
+            }),
+        )
+        .unwrap();
+    assert_eq!(outcome.client, End::Stopped);
+    assert_eq!(outcome.server, End::Stopped);
+}
+
+#[test]
+fn livelock() {
+    let mut client_session = Session::<Stream>::new();
+    let (mut client_sans, client_io) = client_session.split();
+    let mut server_session = Session::<Stream>::new();
+    let (mut server_sans, server_io) = server_session.split();
+
+    // The client writes without ever waiting, so the virtual time never advances
+    let err = Simulator::new(0)
+        .max_steps(100)
+        .run_stream(
+            &client_io,
+            pin!(async {
+                let write = StreamRequest::Write { data: b"spam" };
+                let mut response = client_sans.start(&write).await;
+                loop {
+                    response = client_sans.handle(response, &write).await;
+                }
+            }),
+            &server_io,
+            pin!(async {
+                let read = StreamRequest::Read {
+                    max: 1024,
+                    timeout: None,
+                };
+                server_sans.start(&read).await;
+            }),
+        )
+        .unwrap_err();
+    assert_eq!(
+        err,
+        Error::Livelock {
+            client: true,
+            time: Duration::ZERO,
+        }
+    );
+}
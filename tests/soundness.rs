@@ -0,0 +1,280 @@
+//! Misuse scenarios of the Sans/Io parts. The suite is designed to be run also with Miri:
+//! `cargo +nightly miri test --features std --test soundness`.
+
+// Buffers are allocated on the heap, so Miri detects their use after free
+#![allow(clippy::useless_vec)]
+use asansio::Sans;
+use asansio::SansResponse;
+use asansio::Session;
+use core::cell::RefCell;
+use core::future::Future;
+use core::mem;
+use core::pin::Pin;
+use core::pin::pin;
+use core::task::Context;
+use core::task::Poll;
+use core::task::Waker;
+#[cfg(feature = "std")]
+use std::panic;
+#[cfg(feature = "std")]
+use std::thread;
+
+struct Request<'a>(&'a [u8]);
+struct Response<'a>(&'a [u8]);
+
+struct Proto;
+
+asansio::message! {
+    Proto {
+        type Request<'r> = Request<'r>;
+        type Response<'r> = Response<'r>;
+    }
+}
+
+/// Pending once without sending any Request
+struct Yield(bool);
+
+impl Future for Yield {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            Poll::Ready(())
+        } else {
+            self.0 = true;
+            Poll::Pending
+        }
+    }
+}
+
+#[test]
+#[should_panic(expected = "Request sent while the previous SansResponse is held")]
+fn stale_response_after_start() {
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
+
+    let task = pin!(async {
+        let response = sans.start(&Request(&[1])).await;
+        let _ = sans.start(&Request(&[2])).await;
+        let _ = response.response();
+    });
+
+    let request = io.start(task).unwrap();
+    let request = io.handle(request, &Response(&vec![1; 10])).unwrap();
+    io.handle(request, &Response(&vec![2; 10]));
+}
+
+#[test]
+#[should_panic(expected = "SansResponse used after the next step of the session")]
+fn stale_response_outside_of_task() {
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
+    let slot = RefCell::new(None::<SansResponse<Proto>>);
+
+    let task = pin!(async {
+        let response = sans.start(&Request(&[1])).await;
+        *slot.borrow_mut() = Some(response);
+    });
+
+    let request = io.start(task).unwrap();
+    let response_buf = vec![1; 10];
+    assert!(io.handle(request, &Response(&response_buf)).is_none());
+    drop(response_buf);
+
+    let _ = slot.borrow().as_ref().unwrap().response();
+}
+
+#[cfg(feature = "std")]
+#[test]
+#[should_panic(expected = "SansResponse used by other thread than the one polling the Sans task")]
+fn response_on_another_thread() {
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
+
+    let task = pin!(async {
+        let response = sans.start(&Request(&[1])).await;
+        let reader = thread::scope(|scope| {
+            scope
+                .spawn(move || {
+                    let _ = response.response();
+                })
+                .join()
+        });
+        if let Err(payload) = reader {
+            panic::resume_unwind(payload);
+        }
+    });
+
+    let request = io.start(task).unwrap();
+    io.handle(request, &Response(&vec![1; 10]));
+}
+
+#[test]
+#[should_panic(expected = "Sans task pending outside of Sans::start or Sans::handle")]
+fn response_held_across_foreign_await() {
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
+
+    let task = pin!(async {
+        let response = sans.start(&Request(&[1])).await;
+        Yield(false).await;
+        let _ = response.response();
+    });
+
+    let request = io.start(task).unwrap();
+    io.handle(request, &Response(&vec![1; 10]));
+}
+
+#[test]
+fn dropped_task_mid_await() {
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
+
+    {
+        let task = pin!(async {
+            let request_buf = vec![1; 10];
+            let response = sans.start(&Request(&request_buf)).await;
+            let request_buf = vec![2; 10];
+            sans.handle(response, &Request(&request_buf)).await;
+            unreachable!();
+        });
+
+        let request = io.start(task).unwrap();
+        let request = io.handle(request, &Response(&vec![1; 10])).unwrap();
+        assert_eq!(request.request().0, [2; 10]);
+    }
+
+    let task = pin!(async {
+        let response = sans.start(&Request(&[3])).await;
+        assert_eq!(response.response().0, [4]);
+    });
+
+    let request = io.start(task).unwrap();
+    assert_eq!(request.request().0, [3]);
+    assert!(io.handle(request, &Response(&[4])).is_none());
+}
+
+#[test]
+fn abandoned_io_request() {
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
+
+    let mut task = pin!(async {
+        let response = sans.start(&Request(&[1])).await;
+        assert_eq!(response.response().0, [2]);
+    });
+
+    assert!(io.start(task.as_mut()).is_some());
+
+    let request = io.start(task.as_mut()).unwrap();
+    assert_eq!(request.request().0, [1]);
+    assert!(io.handle(request, &Response(&[2])).is_none());
+}
+
+#[test]
+#[should_panic(expected = "Sans task pending outside of Sans::start or Sans::handle")]
+fn dropped_sans_handle() {
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
+
+    let task = pin!(async {
+        {
+            let mut handle = pin!(sans.start(&Request(&[1])));
+            std::future::poll_fn(|cx| {
+                assert!(handle.as_mut().poll(cx).is_pending());
+                Poll::Ready(())
+            })
+            .await;
+        }
+        Yield(false).await;
+    });
+
+    io.start(task);
+}
+
+#[test]
+#[should_panic(expected = "SansHandle leaked while the Sans task is pending")]
+fn leaked_sans_handle() {
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
+
+    let task = pin!(async {
+        let request_buf = vec![1; 10];
+        let request = Request(&request_buf);
+        let mut handle = Box::pin(sans.start(&request));
+        std::future::poll_fn(|cx| {
+            let _ = handle.as_mut().poll(cx);
+            Poll::Ready(())
+        })
+        .await;
+        mem::forget(handle);
+        drop(request_buf);
+        Yield(false).await;
+    });
+
+    io.start(task);
+}
+
+#[test]
+#[should_panic(expected = "IoRequest handled by the Io of a different session")]
+fn mismatched_io_request() {
+    let mut session_a = Session::<Proto>::new();
+    let (mut sans_a, io_a) = session_a.split();
+    let mut session_b = Session::<Proto>::new();
+    let (_, io_b) = session_b.split();
+
+    let task = pin!(async {
+        sans_a.start(&Request(&[1])).await;
+    });
+
+    let request = io_a.start(task).unwrap();
+    io_b.handle(request, &Response(&[2]));
+}
+
+#[test]
+#[should_panic(expected = "Sans task polled by the Io of a different session")]
+fn mismatched_sans() {
+    let mut session_a = Session::<Proto>::new();
+    let (mut sans_a, _) = session_a.split();
+    let mut session_b = Session::<Proto>::new();
+    let (_, io_b) = session_b.split();
+
+    let task = pin!(async {
+        sans_a.start(&Request(&[1])).await;
+    });
+
+    io_b.start(task);
+}
+
+#[test]
+#[should_panic(expected = "Sans task polled outside of the Io")]
+fn foreign_executor() {
+    let mut session = Session::<Proto>::new();
+    let (mut sans, _) = session.split();
+
+    let task = pin!(async {
+        sans.start(&Request(&[1])).await;
+    });
+
+    let _ = task.poll(&mut Context::from_waker(Waker::noop()));
+}
+
+#[test]
+#[should_panic(expected = "Sans task polled outside of the Io")]
+fn cloned_waker() {
+    async fn sans_task(mut sans: Sans<'_, Proto>) {
+        let mut handle = pin!(sans.start(&Request(&[1])));
+        std::future::poll_fn(|cx| {
+            let waker = cx.waker().clone();
+            let _ = handle.as_mut().poll(&mut Context::from_waker(&waker));
+            Poll::Ready(())
+        })
+        .await;
+    }
+
+    let mut session = Session::<Proto>::new();
+    let (sans, io) = session.split();
+
+    let task = pin!(sans_task(sans));
+    io.start(task);
+}
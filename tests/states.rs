@@ -0,0 +1,103 @@
+use asansio::Sans;
+use asansio::Session;
+use asansio::Tracer;
+use std::pin::pin;
+use std::sync::Mutex;
+
+enum Request {
+    Hello,
+    Login,
+    Data,
+}
+
+struct Response(bool);
+
+struct Proto;
+
+asansio::message! {
+    Proto {
+        type Request<'r> = Request;
+        type Response<'r> = Response;
+    }
+}
+
+/// Logs in until the login is accepted, then reads data until the end
+async fn client(mut sans: Sans<'_, Proto>) {
+    sans.state("handshake");
+    let mut response = sans.start(&Request::Hello).await;
+    loop {
+        sans.state("auth");
+        response = sans.handle(response, &Request::Login).await;
+        if response.response().0 {
+            break;
+        }
+    }
+    sans.state("data");
+    while response.response().0 {
+        response = sans.handle(response, &Request::Data).await;
+    }
+}
+
+type Transition = (usize, Option<&'static str>, &'static str);
+
+struct Log(Mutex<Vec<Transition>>);
+
+impl Tracer for Log {
+    fn transition(&self, step: usize, from: Option<&'static str>, to: &'static str) {
+        self.0.lock().unwrap().push((step, from, to));
+    }
+}
+
+#[test]
+fn state_of_pending_session() {
+    let mut session = Session::<Proto>::new();
+    let (sans, io) = session.split();
+    let mut task = pin!(client(sans));
+
+    let mut states = Vec::new();
+    let mut handler = io.start(task.as_mut());
+    for response in [true, false, true, true, false] {
+        let request = handler.unwrap();
+        states.push(request.state().unwrap());
+        handler = io.handle(request, &Response(response));
+    }
+    assert!(handler.is_none());
+    assert_eq!(states, ["handshake", "auth", "auth", "data", "data"]);
+}
+
+#[test]
+fn unlabeled_session() {
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
+    let mut task = pin!(async {
+        sans.start(&Request::Hello).await;
+    });
+
+    let request = io.start(task.as_mut()).unwrap();
+    assert_eq!(request.state(), None);
+}
+
+#[test]
+fn traced_transitions() {
+    static LOG: Log = Log(Mutex::new(Vec::new()));
+
+    let mut session = Session::<Proto>::new().with_tracer(&LOG);
+    let (sans, io) = session.split();
+    let mut task = pin!(client(sans));
+
+    let mut handler = io.start(task.as_mut());
+    for response in [true, false, true, false] {
+        handler = io.handle(handler.unwrap(), &Response(response));
+    }
+    assert!(handler.is_none());
+
+    // The repeated login does not change the state
+    assert_eq!(
+        *LOG.0.lock().unwrap(),
+        [
+            (0, None, "handshake"),
+            (1, Some("handshake"), "auth"),
+            (3, Some("auth"), "data"),
+        ]
+    );
+}
@@ -0,0 +1,118 @@
+use asansio::Io;
+use asansio::IoRequest;
+use asansio::Sans;
+use asansio::SansHandle;
+use asansio::SansResponse;
+use asansio::Session;
+use std::pin::pin;
+use std::sync::Mutex;
+use std::thread;
+use std::thread::ThreadId;
+
+struct Request<'a>(&'a [u8]);
+struct Response<'a>(&'a [u8]);
+
+struct Proto;
+
+asansio::message! {
+    Proto {
+        type Request<'r> = Request<'r>;
+        type Response<'r> = Response<'r>;
+    }
+}
+
+const STEPS: u8 = 10;
+
+/// Sends the received payload incremented by one, until it reaches STEPS
+async fn sans_task(mut sans: Sans<'_, Proto>, threads: &Mutex<Vec<ThreadId>>) {
+    let mut sans_resp = sans.start(&Request(&[0])).await;
+    loop {
+        threads.lock().unwrap().push(thread::current().id());
+        let payload = sans_resp.response().0[0] + 1;
+        if payload > STEPS {
+            break;
+        }
+        sans_resp = sans.handle(sans_resp, &Request(&[payload])).await;
+    }
+}
+
+fn response_for(request: &Request) -> Vec<u8> {
+    request.0.to_vec()
+}
+
+fn assert_send<T: Send>() {}
+fn assert_sync<T: Sync>() {}
+
+#[test]
+fn parts_are_send_and_sync() {
+    assert_send::<Session<Proto>>();
+    assert_sync::<Session<Proto>>();
+    assert_send::<Sans<Proto>>();
+    assert_sync::<Sans<Proto>>();
+    assert_send::<Io<Proto>>();
+    assert_send::<SansHandle<Proto>>();
+    assert_sync::<SansHandle<Proto>>();
+    assert_send::<SansResponse<Proto>>();
+    assert_send::<IoRequest<Proto, ()>>();
+    assert_sync::<IoRequest<Proto, ()>>();
+}
+
+#[test]
+fn scoped_threads() {
+    let threads = Mutex::new(Vec::new());
+
+    let mut session = Session::<Proto>::new();
+    let (sans, mut io) = session.split();
+
+    let task = pin!(sans_task(sans, &threads));
+
+    let mut request = io.start(task);
+    let mut steps = 0;
+    while let Some(handler) = request {
+        let response = response_for(handler.request());
+        (io, request) = thread::scope(|scope| {
+            scope
+                .spawn(move || {
+                    let request = io.handle(handler, &Response(&response));
+                    (io, request)
+                })
+                .join()
+                .unwrap()
+        });
+        steps += 1;
+    }
+    assert_eq!(steps, STEPS + 1);
+
+    let threads = threads.lock().unwrap();
+    assert_eq!(threads.len(), STEPS as usize + 1);
+    for (idx, id) in threads.iter().enumerate() {
+        assert!(!threads[idx + 1..].contains(id));
+    }
+}
+
+async fn session_process() -> usize {
+    let threads = Mutex::new(Vec::new());
+
+    let mut session = Session::<Proto>::new();
+    let (sans, io) = session.split();
+
+    let task = pin!(sans_task(sans, &threads));
+
+    let mut request = io.start(task);
+    let mut steps = 0;
+    while let Some(handler) = request {
+        let response = response_for(handler.request());
+        tokio::task::yield_now().await;
+        request = io.handle(handler, &Response(&response));
+        steps += 1;
+    }
+    steps
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn multi_thread_runtime() {
+    let sessions: Vec<_> = (0..32).map(|_| tokio::spawn(session_process())).collect();
+    for session in sessions {
+        assert_eq!(session.await.unwrap(), STEPS as usize + 1);
+    }
+}
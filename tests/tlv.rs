@@ -0,0 +1,257 @@
+use asansio::Io;
+use asansio::Sans;
+use asansio::Session;
+use asansio::codec::Decoder;
+use asansio::codec::Encoder;
+use asansio::codec::Error;
+use asansio::codec::Framed;
+use asansio::codec::length::Endian;
+use asansio::codec::tlv::Element;
+use asansio::codec::tlv::Handler;
+use asansio::codec::tlv::Registry;
+use asansio::codec::tlv::Route;
+use asansio::codec::tlv::Routed;
+use asansio::codec::tlv::RoutedRequest;
+use asansio::codec::tlv::RoutedResponse;
+use asansio::codec::tlv::Tlv;
+use asansio::codec::tlv::TlvError;
+use asansio::codec::tlv::Width;
+use asansio::net::Stream;
+use asansio::net::StreamRequest;
+use asansio::net::StreamResponse;
+use std::pin::Pin;
+use std::pin::pin;
+
+/// Encodes the element
+fn encode(codec: &mut Tlv, tag: u32, value: &[u8]) -> Result<Vec<u8>, TlvError> {
+    let element = Element { tag, value };
+    let mut buf = vec![0; codec.encoded_len(&element)?];
+    codec.encode(&element, &mut buf);
+    Ok(buf)
+}
+
+#[test]
+fn widths() {
+    let value = [7; 300];
+    let cases = [
+        (Tlv::new(), 0x5a, &value[..2], &[0x5a, 2][..]),
+        (Tlv::new(), 0x9f02, &value[..200], &[0x9f, 0x02, 0x81, 200]),
+        (
+            Tlv::new(),
+            0xdf8101,
+            &value[..],
+            &[0xdf, 0x81, 0x01, 0x82, 1, 44],
+        ),
+        (
+            Tlv::new().tag(Width::U16).length(Width::U32),
+            0x102,
+            &value[..],
+            &[1, 2, 0, 0, 1, 44],
+        ),
+        (
+            Tlv::new()
+                .tag(Width::U8)
+                .length(Width::U16)
+                .endian(Endian::Little),
+            3,
+            &value[..],
+            &[3, 44, 1],
+        ),
+    ];
+    for (mut codec, tag, value, header) in cases {
+        let frame = encode(&mut codec, tag, value).unwrap();
+        assert_eq!(&frame[..header.len()], header);
+        for len in 0..frame.len() {
+            assert_eq!(codec.frame_len(&frame[..len]), Ok(None));
+        }
+        assert_eq!(codec.frame_len(&frame), Ok(Some(frame.len())));
+        assert_eq!(codec.decode(&frame), Ok(Element { tag, value }));
+    }
+}
+
+#[test]
+fn nested_and_errors() {
+    let mut codec = Tlv::new();
+    let mut value = encode(&mut codec, 0x5a, b"12").unwrap();
+    let primitive = encode(&mut codec, 0x9f02, b"3").unwrap();
+    value.extend(encode(&mut codec, 0xbf0c, &primitive).unwrap());
+    let frame = encode(&mut codec, 0x70, &value).unwrap();
+
+    let outer = codec.decode(&frame).unwrap();
+    assert!(codec.is_constructed(outer.tag));
+    let nested: Vec<_> = codec.elements(outer.value).map(Result::unwrap).collect();
+    assert_eq!(
+        nested[0],
+        Element {
+            tag: 0x5a,
+            value: b"12"
+        }
+    );
+    assert!(!codec.is_constructed(nested[0].tag));
+    assert!(codec.is_constructed(nested[1].tag));
+    let inner: Vec<_> = codec.elements(nested[1].value).collect();
+    assert_eq!(
+        inner,
+        [Ok(Element {
+            tag: 0x9f02,
+            value: b"3"
+        })]
+    );
+    // The truncated nested element
+    let truncated: Vec<_> = codec.elements(&value[..value.len() - 1]).collect();
+    assert_eq!(truncated[1], Err(TlvError::Invalid));
+
+    // The indefinite length and the tag of five bytes
+    assert_eq!(codec.frame_len(b"\x30\x80"), Err(TlvError::Invalid));
+    assert_eq!(
+        codec.frame_len(b"\x9f\x81\x81\x81\x01"),
+        Err(TlvError::Invalid)
+    );
+    assert_eq!(encode(&mut codec, 0x9f, b""), Err(TlvError::Invalid));
+
+    let mut codec = Tlv::new()
+        .tag(Width::U8)
+        .length(Width::U8)
+        .max_value_len(16);
+    assert_eq!(encode(&mut codec, 0x100, b""), Err(TlvError::Invalid));
+    assert_eq!(
+        codec.frame_len(b"\x01\x20"),
+        Err(TlvError::TooLong { len: 32, max: 16 })
+    );
+    assert_eq!(
+        encode(&mut codec, 1, &[0; 17]),
+        Err(TlvError::TooLong { len: 17, max: 16 })
+    );
+    assert_eq!(
+        TlvError::Unrouted(0x9f02).to_string(),
+        "no handler waits for the tag 0x9f02"
+    );
+}
+
+/// Answers reads with chunks and then closes the stream, returns written data
+fn drive<T: Future<Output = ()>>(
+    io: &Io<'_, Stream>,
+    task: Pin<&mut T>,
+    chunks: &[&[u8]],
+) -> Vec<u8> {
+    let mut chunks = chunks.iter();
+    let mut written = Vec::new();
+    let mut handler = io.start(task);
+    while let Some(request) = handler {
+        let response = match request.request() {
+            StreamRequest::Read { .. } => match chunks.next() {
+                Some(data) => StreamResponse::Data { data },
+                None => StreamResponse::Closed,
+            },
+            StreamRequest::Write { data } => {
+                written.extend_from_slice(data);
+                StreamResponse::Written
+            }
+            StreamRequest::Sleep { .. } => StreamResponse::Woken,
+        };
+        handler = io.handle(request, &response);
+    }
+    written
+}
+
+/// Greets the peer and answers pings with pongs
+async fn ping(mut sans: Sans<'_, Routed>) {
+    let hello = RoutedRequest::Write(Element {
+        tag: 1,
+        value: b"hi",
+    });
+    let mut response = sans.start(&hello).await;
+    response = sans.handle(response, &RoutedRequest::Read).await;
+    while let RoutedResponse::Element(_) = response.response() {
+        let pong = RoutedRequest::Write(Element {
+            tag: 1,
+            value: b"pong",
+        });
+        response = sans.handle(response, &pong).await;
+        response = sans.handle(response, &RoutedRequest::Read).await;
+    }
+}
+
+/// Reverses values of its tags after the sleep
+async fn reverse(mut sans: Sans<'_, Routed>) {
+    let mut response = sans.start(&RoutedRequest::Read).await;
+    while let RoutedResponse::Element(element) = response.response() {
+        let mut value = element.value.to_vec();
+        value.reverse();
+        let sleep = StreamRequest::Sleep {
+            duration: std::time::Duration::from_millis(1),
+        };
+        response = sans.handle(response, &RoutedRequest::Stream(sleep)).await;
+        let reversed = RoutedRequest::Write(Element {
+            tag: 2,
+            value: &value,
+        });
+        response = sans.handle(response, &reversed).await;
+        response = sans.handle(response, &RoutedRequest::Read).await;
+    }
+}
+
+/// Routes elements of chunks to ping and reverse tasks
+fn route(chunks: &[&[u8]]) -> (Option<Result<(), Error<TlvError>>>, Vec<u8>) {
+    let mut session = Session::<Stream>::new();
+    let (mut sans, io) = session.split();
+    let mut result = None;
+    let task = async {
+        let mut ping_session = Session::<Routed>::new();
+        let (ping_sans, ping_io) = ping_session.split();
+        let ping_task = pin!(ping(ping_sans));
+        let mut ping = Route::new(&[1], ping_io, ping_task);
+        let mut reverse_session = Session::<Routed>::new();
+        let (reverse_sans, reverse_io) = reverse_session.split();
+        let reverse_task = pin!(reverse(reverse_sans));
+        let mut reverse = Route::new(&[2, 3], reverse_io, reverse_task);
+        let mut handlers: [&mut dyn Handler; 2] = [&mut ping, &mut reverse];
+
+        let (mut read_buf, mut write_buf) = ([0; 16], [0; 16]);
+        let mut framed = Framed::new(&mut sans, &mut read_buf, &mut write_buf);
+        let mut registry = Registry::new(&mut handlers);
+        result = Some(registry.run(&mut framed, &mut Tlv::new()).await);
+    };
+    let written = drive(&io, pin!(task), chunks);
+    (result, written)
+}
+
+#[test]
+fn registry() {
+    let (result, written) = route(&[b"\x02\x03ab", b"c\x01\x00\x03\x01d"]);
+    assert_eq!(result, Some(Ok(())));
+    assert_eq!(written, b"\x01\x02hi\x02\x03cba\x01\x04pong\x02\x01d");
+
+    let (result, written) = route(&[b"\x01\x00\x04\x00"]);
+    assert_eq!(result, Some(Err(Error::Codec(TlvError::Unrouted(4)))));
+    assert_eq!(written, b"\x01\x02hi\x01\x04pong");
+}
+
+#[test]
+fn stream_read() {
+    let mut session = Session::<Stream>::new();
+    let (mut sans, io) = session.split();
+    let mut result = None;
+    let task = async {
+        let mut raw_session = Session::<Routed>::new();
+        let (mut raw_sans, raw_io) = raw_session.split();
+        // The handler reading the stream directly would lose the buffered elements
+        let raw_task = pin!(async {
+            let read = StreamRequest::Read {
+                max: 1024,
+                timeout: None,
+            };
+            raw_sans.start(&RoutedRequest::Stream(read)).await;
+        });
+        let mut raw = Route::new(&[1], raw_io, raw_task);
+        let mut handlers: [&mut dyn Handler; 1] = [&mut raw];
+
+        let (mut read_buf, mut write_buf) = ([0; 16], [0; 16]);
+        let mut framed = Framed::new(&mut sans, &mut read_buf, &mut write_buf);
+        let mut registry = Registry::new(&mut handlers);
+        result = Some(registry.run(&mut framed, &mut Tlv::new()).await);
+    };
+    let written = drive(&io, pin!(task), &[b"\x01\x00"]);
+    assert_eq!(result, Some(Err(Error::Codec(TlvError::StreamRead))));
+    assert_eq!(written, b"");
+}
@@ -0,0 +1,204 @@
+use asansio::Sans;
+use asansio::Session;
+use asansio::trace::DebugTraceHook;
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::pin::pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use tracing::Event;
+use tracing::Id;
+use tracing::Metadata;
+use tracing::Subscriber;
+use tracing::field::Field;
+use tracing::field::Visit;
+use tracing::span::Attributes;
+use tracing::span::Record;
+
+#[derive(Debug)]
+enum Request {
+    Hello,
+    Login,
+}
+
+#[derive(Debug)]
+struct Response(bool);
+
+struct Proto;
+
+asansio::message! {
+    Proto {
+        type Request<'r> = Request;
+        type Response<'r> = Response;
+    }
+}
+
+/// Logs in until the login is accepted
+async fn client(mut sans: Sans<'_, Proto>) {
+    sans.state("handshake");
+    let mut response = sans.start(&Request::Hello).await;
+    sans.state("auth");
+    while !response.response().0 {
+        response = sans.handle(response, &Request::Login).await;
+    }
+}
+
+#[derive(Clone, Default)]
+struct Fields(BTreeMap<&'static str, String>);
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0.insert(field.name(), format!("{value:?}"));
+    }
+}
+
+/// Collects fields of spans and events with fields of their span
+#[derive(Clone, Default)]
+struct Collector {
+    spans: Arc<Mutex<Vec<Fields>>>,
+    events: Arc<Mutex<Vec<Fields>>>,
+    current: Arc<Mutex<Vec<usize>>>,
+}
+
+impl Subscriber for Collector {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut fields = Fields::default();
+        span.record(&mut fields);
+        let mut spans = self.spans.lock().unwrap();
+        spans.push(fields);
+        Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        values.record(&mut self.spans.lock().unwrap()[span.into_u64() as usize - 1]);
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let current = *self.current.lock().unwrap().last().unwrap();
+        let mut fields = self.spans.lock().unwrap()[current - 1].clone();
+        event.record(&mut fields);
+        self.events.lock().unwrap().push(fields);
+    }
+
+    fn enter(&self, span: &Id) {
+        self.current.lock().unwrap().push(span.into_u64() as usize);
+    }
+
+    fn exit(&self, _: &Id) {
+        self.current.lock().unwrap().pop();
+    }
+}
+
+/// Fields of the event without the session
+type EventFields = Vec<(&'static str, String)>;
+
+fn collect(run: impl FnOnce()) -> (Vec<Fields>, Vec<EventFields>) {
+    let collector = Collector::default();
+    tracing::subscriber::with_default(collector.clone(), run);
+    let events = collector
+        .events
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|fields| {
+            fields
+                .0
+                .iter()
+                .filter(|(name, _)| **name != "session")
+                .map(|(name, value)| (*name, value.clone()))
+                .collect()
+        })
+        .collect();
+    let spans = collector.spans.lock().unwrap().clone();
+    (spans, events)
+}
+
+#[test]
+fn spans_and_messages() {
+    let mut session = Session::<Proto>::new();
+    let (sans, io) = session.split();
+    let io = io.with_hook(&DebugTraceHook);
+    let mut task = pin!(client(sans));
+
+    let (spans, events) = collect(|| {
+        let mut handler = io.start(task.as_mut());
+        for response in [false, false, true] {
+            handler = io.handle(handler.unwrap(), &Response(response));
+        }
+        assert!(handler.is_none());
+    });
+
+    let event = |fields: &[(&'static str, &str)]| {
+        fields
+            .iter()
+            .map(|(name, value)| (*name, value.to_string()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        events,
+        [
+            event(&[("request", "Hello"), ("state", "handshake"), ("step", "0")]),
+            event(&[
+                ("response", "Response(false)"),
+                ("state", "handshake"),
+                ("step", "1")
+            ]),
+            event(&[("request", "Login"), ("state", "auth"), ("step", "1")]),
+            event(&[
+                ("response", "Response(false)"),
+                ("state", "auth"),
+                ("step", "2")
+            ]),
+            event(&[("request", "Login"), ("state", "auth"), ("step", "2")]),
+            event(&[
+                ("response", "Response(true)"),
+                ("state", "auth"),
+                ("step", "3")
+            ]),
+            event(&[("message", "finished"), ("state", "auth"), ("step", "3")]),
+        ]
+    );
+
+    // Every call is in its own span of the same session
+    assert_eq!(spans.len(), 4);
+    assert!(
+        spans
+            .iter()
+            .all(|span| span.0["session"] == spans[0].0["session"])
+    );
+}
+
+#[test]
+fn spans_without_hook() {
+    let mut session = Session::<Proto>::new();
+    let (sans, io) = session.split();
+    let mut task = pin!(client(sans));
+
+    let (spans, events) = collect(|| {
+        let handler = io.start(task.as_mut());
+        assert!(io.handle(handler.unwrap(), &Response(true)).is_none());
+    });
+
+    assert_eq!(
+        events,
+        [vec![
+            ("message", "finished".to_string()),
+            ("state", "auth".to_string()),
+            ("step", "1".to_string()),
+        ]]
+    );
+    // The state is updated after the poll
+    assert_eq!(spans.len(), 2);
+    assert_eq!(spans[0].0["state"], "handshake");
+    assert_eq!(spans[1].0["state"], "auth");
+}
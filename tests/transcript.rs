@@ -0,0 +1,169 @@
+use asansio::Drive;
+use asansio::Session;
+use asansio::transcript::DebugHook;
+use asansio::transcript::Event;
+use asansio::transcript::Hook;
+use asansio::transcript::Recorder;
+use std::pin::pin;
+
+#[derive(Debug)]
+struct Request<'a>(&'a [u8]);
+#[derive(Debug)]
+struct Response<'a>(&'a [u8]);
+
+struct Proto;
+
+asansio::message! {
+    Proto {
+        type Request<'r> = Request<'r>;
+        type Response<'r> = Response<'r>;
+    }
+}
+
+/// Records payloads of messages
+struct BytesHook;
+
+impl Hook<Proto> for BytesHook {
+    type Request = Vec<u8>;
+    type Response = Vec<u8>;
+
+    fn request(&mut self, request: &Request<'_>) -> Vec<u8> {
+        request.0.to_vec()
+    }
+
+    fn response(&mut self, response: &Response<'_>) -> Vec<u8> {
+        response.0.to_vec()
+    }
+}
+
+#[test]
+fn debug_hook() {
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
+    let mut recorder = Recorder::new(io, DebugHook);
+
+    let task = pin!(async {
+        let response = sans.start(&Request(&[1])).await;
+        sans.handle(response, &Request(&[3])).await;
+    });
+
+    let request = recorder.start(task).unwrap();
+    let request = recorder.handle(request, &Response(&[2])).unwrap();
+    assert!(recorder.handle(request, &Response(&[4])).is_none());
+
+    let transcript = recorder.into_transcript();
+    let events: Vec<_> = transcript
+        .entries()
+        .iter()
+        .map(|entry| entry.event.clone())
+        .collect();
+    assert_eq!(
+        events,
+        [
+            Event::Request("Request([1])".to_string()),
+            Event::Response("Response([2])".to_string()),
+            Event::Request("Request([3])".to_string()),
+            Event::Response("Response([4])".to_string()),
+            Event::Finished,
+        ]
+    );
+    for (seq, entry) in transcript.entries().iter().enumerate() {
+        assert_eq!(entry.seq, seq as u64);
+    }
+    assert!(
+        transcript
+            .entries()
+            .windows(2)
+            .all(|entries| entries[0].order < entries[1].order)
+    );
+    assert!(
+        transcript
+            .entries()
+            .windows(2)
+            .all(|entries| entries[0].timestamp <= entries[1].timestamp)
+    );
+}
+
+#[test]
+fn custom_hook() {
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
+    let mut recorder = Recorder::new(io, BytesHook);
+
+    let task = pin!(async {
+        let mut request_buf = vec![1, 2];
+        let mut response = sans.start(&Request(&request_buf)).await;
+        while let Some(&byte) = response.response().0.first() {
+            request_buf.push(byte);
+            response = sans.handle(response, &Request(&request_buf)).await;
+        }
+    });
+
+    let request = recorder.start(task).unwrap();
+    let request = recorder.handle(request, &Response(&[3])).unwrap();
+    assert!(recorder.handle(request, &Response(&[])).is_none());
+
+    let (_, transcript) = recorder.into_parts();
+    let events: Vec<_> = transcript
+        .entries()
+        .iter()
+        .map(|entry| entry.event.clone())
+        .collect();
+    assert_eq!(
+        events,
+        [
+            Event::Request(vec![1, 2]),
+            Event::Response(vec![3]),
+            Event::Request(vec![1, 2, 3]),
+            Event::Response(vec![]),
+            Event::Finished,
+        ]
+    );
+}
+
+#[test]
+fn panicked_task() {
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
+    let mut recorder = Recorder::new(io, DebugHook);
+
+    let task = pin!(async {
+        let response = sans.start(&Request(&[1])).await;
+        assert!(response.response().0.is_empty(), "not empty");
+    });
+
+    let request = recorder.try_start(task).unwrap().unwrap();
+    assert!(recorder.try_handle(request, &Response(&[2])).is_err());
+
+    let transcript = recorder.transcript();
+    assert_eq!(transcript.len(), 3);
+    assert_eq!(
+        transcript.entries()[2].event,
+        Event::Panicked(Some("not empty".to_string()))
+    );
+}
+
+#[test]
+fn display() {
+    let mut session = Session::<Proto>::new();
+    let (mut sans, io) = session.split();
+    let mut recorder = Recorder::new(io, BytesHook);
+
+    let task = pin!(async {
+        sans.start(&Request(&[1])).await;
+    });
+
+    let request = recorder.start(task).unwrap();
+    assert!(recorder.handle(request, &Response(&[2])).is_none());
+
+    let dump = recorder.transcript().to_string();
+    let lines: Vec<_> = dump
+        .lines()
+        .map(|line| {
+            let (seq, rest) = line.split_once(' ').unwrap();
+            let (_, event) = rest.split_once(' ').unwrap();
+            format!("{seq} {event}")
+        })
+        .collect();
+    assert_eq!(lines, ["0 > [1]", "1 < [2]", "2 finished"]);
+}